@@ -0,0 +1,115 @@
+// Solve time of candidate allocation against cohort size
+// Run with: cargo bench --bench allocation
+// or on the pure Rust backend: cargo bench --bench allocation --no-default-features --features microlp
+use std::{collections::HashMap, time::Instant};
+
+use backend::{
+    allocation_algo::{allocate_by_slot, Backend, Layout},
+    http::{allocations::Availability, candidates::Candidate, circuits::Circuit, stations::Station},
+};
+use comfy_table::{Attribute, Cell, Table};
+use sqlx::postgres::types::PgInterval;
+use uuid::Uuid;
+
+const NUM_STATIONS: usize = 10;
+const GROUP_SIZE: usize = 2;
+const COHORT_SIZES: [usize; 5] = [50, 120, 200, 300, 400];
+
+fn circuit(slot_id: Uuid, key: usize, female_only: bool) -> Circuit {
+    Circuit {
+        id: Uuid::new_v4(),
+        session_id: Uuid::nil(),
+        slot_id,
+        key: ((b'A' + key as u8) as char).to_string(),
+        female_only,
+        current_rotation: None,
+        status: String::from("new"),
+        feedback: true,
+        intermission: false,
+        timer_start: None,
+        timer_end: None,
+    }
+}
+
+fn station(index: usize) -> Station {
+    Station {
+        id: Uuid::new_v4(),
+        session_id: Uuid::nil(),
+        title: format!("Station {}", index + 1),
+        index: index as i16,
+        duration: PgInterval { months: 0, days: 0, microseconds: 8 * 60 * 1_000_000 },
+        required_tags: Vec::new(),
+        preferred_tags: Vec::new(),
+        kind: String::from("examined"),
+        needs_actor: false,
+        actor_gender: None,
+    }
+}
+
+// every 3rd candidate names a partner, every 5th a second choice, every 7th avoids someone, every 8th is female-only
+fn candidate(i: usize, cohort_size: usize) -> Candidate {
+    let shortcode = |k: usize| format!("c{}", k % cohort_size);
+    Candidate {
+        id: Uuid::new_v4(),
+        session_id: Uuid::nil(),
+        first_name: format!("Candidate {i}"),
+        last_name: String::from("Bench"),
+        shortcode: shortcode(i),
+        female_only: i.is_multiple_of(8),
+        partner_pref: i.is_multiple_of(3).then(|| shortcode(i + 1)),
+        checked_in: false,
+        am: Some(true),
+        pm: Some(true),
+        partner_pref_2: i.is_multiple_of(5).then(|| shortcode(i + 2)),
+        avoid: if i.is_multiple_of(7) { vec![shortcode(i + 3)] } else { Vec::new() },
+        placeholder: false,
+    }
+}
+
+fn main() {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .set_header(vec![
+            Cell::new("Candidates").add_attribute(Attribute::Bold),
+            Cell::new("Circuits").add_attribute(Attribute::Bold),
+            Cell::new("Stations").add_attribute(Attribute::Bold),
+            Cell::new("Groups").add_attribute(Attribute::Bold),
+            Cell::new("Solve time (ms)").add_attribute(Attribute::Bold),
+        ]);
+    println!("Solver backend: {}", std::any::type_name::<Backend>().rsplit("::").next().unwrap_or_default());
+
+    for cohort_size in COHORT_SIZES {
+        let slot_id = Uuid::new_v4();
+        let num_circuits = cohort_size.div_ceil(GROUP_SIZE * NUM_STATIONS) + 1; // spare circuit for the female-only one
+        let circuits: Vec<Circuit> = (0..num_circuits).map(|c| circuit(slot_id, c, c == 0)).collect();
+        let stations: Vec<Station> = (0..NUM_STATIONS).map(station).collect();
+        let candidates: Vec<Candidate> = (0..cohort_size).map(|i| candidate(i, cohort_size)).collect();
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+
+        let start = Instant::now();
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &[], pm_runs: &[], group_size: GROUP_SIZE };
+        let allocations = allocate_by_slot(&layout, &stations, &candidates, &[], &[], false)
+            .unwrap_or_else(|_| panic!("Allocation failed for {cohort_size} candidates"));
+        let elapsed = start.elapsed();
+
+        // same checks on every backend: each candidate allocated once, at most 1 group per station
+        for candidate in &candidates {
+            let count = allocations.iter().filter(|a| a.candidates.contains(&candidate.id)).count();
+            assert_eq!(count, 1, "{} allocated {} times", candidate.shortcode, count);
+        }
+        for allocation in &allocations {
+            let count = allocations.iter().filter(|a| a.circuit_id == allocation.circuit_id && a.station_id == allocation.station_id).count();
+            assert_eq!(count, 1, "Station allocated {} groups", count);
+        }
+
+        table.add_row(vec![
+            Cell::new(cohort_size),
+            Cell::new(num_circuits),
+            Cell::new(NUM_STATIONS),
+            Cell::new(allocations.len()),
+            Cell::new(elapsed.as_millis()),
+        ]);
+    }
+    println!("{table}");
+}
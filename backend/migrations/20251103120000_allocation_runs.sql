@@ -0,0 +1,58 @@
+-- allocations are stored per run, as examiners can change between runs of the same slot
+ALTER TABLE records.allocations
+ADD COLUMN run_id UUID REFERENCES records.runs(id) ON DELETE CASCADE,
+ALTER candidate_1 DROP NOT NULL, -- stations without a pair at the start of the run
+ALTER candidate_2 DROP NOT NULL,
+ALTER examiner DROP NOT NULL, -- rest stations are not examined
+DROP CONSTRAINT unique_candidate_pair,
+DROP CONSTRAINT unique_examiner;
+
+ALTER TABLE records.allocations_history
+ADD COLUMN run_id UUID REFERENCES records.runs(id) ON DELETE CASCADE,
+ALTER candidate_1 DROP NOT NULL,
+ALTER candidate_2 DROP NOT NULL,
+ALTER examiner DROP NOT NULL;
+
+-- Backfill: existing allocations are per slot, so copy each one to every run of its slot.
+-- Allocations in a slot without runs cannot be placed and are dropped
+INSERT INTO records.allocations (slot_id, circuit_id, station_id, candidate_1, candidate_2, examiner, modified_at, run_id)
+SELECT a.slot_id, a.circuit_id, a.station_id, a.candidate_1, a.candidate_2, a.examiner, a.modified_at, r.id
+FROM records.allocations a
+JOIN records.runs r ON r.slot_id = a.slot_id
+WHERE a.run_id IS NULL;
+
+DELETE FROM records.allocations WHERE run_id IS NULL;
+
+INSERT INTO records.allocations_history (batch_id, slot_id, circuit_id, station_id, candidate_1, candidate_2, examiner, modified_by, auto_gen, modified_at, run_id)
+SELECT h.batch_id, h.slot_id, h.circuit_id, h.station_id, h.candidate_1, h.candidate_2, h.examiner, h.modified_by, h.auto_gen, h.modified_at, r.id
+FROM records.allocations_history h
+JOIN records.runs r ON r.slot_id = h.slot_id
+WHERE h.run_id IS NULL;
+
+DELETE FROM records.allocations_history WHERE run_id IS NULL;
+
+-- An examiner could be in several circuits of the same slot before, which double-books them in every run.
+-- Keep them at their first allocation and leave their other stations unexamined
+UPDATE records.allocations a
+SET examiner = NULL
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY run_id, examiner ORDER BY modified_at, id) AS n
+    FROM records.allocations
+    WHERE examiner IS NOT NULL
+) d
+WHERE a.id = d.id AND d.n > 1;
+
+ALTER TABLE records.allocations
+ALTER run_id SET NOT NULL,
+-- Ensure a pair is not allocated twice in the same run & circuit
+ADD CONSTRAINT unique_candidate_pair UNIQUE (run_id, circuit_id, candidate_1, candidate_2),
+-- Ensure an examiner is not double-booked across circuits in the same run
+ADD CONSTRAINT unique_examiner UNIQUE (run_id, examiner),
+-- Ensure a station only has 1 allocation per run & circuit
+ADD CONSTRAINT unique_station UNIQUE (run_id, circuit_id, station_id);
+
+ALTER TABLE records.allocations_history
+ALTER run_id SET NOT NULL;
+
+CREATE INDEX idx_allocations_history_batch_id
+ON records.allocations_history (batch_id);
@@ -0,0 +1,8 @@
+-- locked allocations are kept fixed when the session is re-solved
+ALTER TABLE records.allocations
+ADD COLUMN pair_locked bool NOT NULL DEFAULT false,
+ADD COLUMN examiner_locked bool NOT NULL DEFAULT false;
+
+ALTER TABLE records.allocations_history
+ADD COLUMN pair_locked bool NOT NULL DEFAULT false,
+ADD COLUMN examiner_locked bool NOT NULL DEFAULT false;
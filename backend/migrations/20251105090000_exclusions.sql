@@ -0,0 +1,8 @@
+-- conflicts of interest: the examiner must never examine the candidate (tutees, relatives, flatmates)
+CREATE TABLE IF NOT EXISTS people.exclusions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    session_id UUID NOT NULL REFERENCES records.sessions(id) ON DELETE CASCADE,
+    examiner_shortcode text NOT NULL,
+    candidate_shortcode text NOT NULL,
+    CONSTRAINT unique_exclusion UNIQUE (session_id, examiner_shortcode, candidate_shortcode)
+);
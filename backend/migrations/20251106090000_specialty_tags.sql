@@ -0,0 +1,11 @@
+-- specialties, e.g. 'paediatrics' or 'ecg'. Examiners with a station's required tags must examine it, preferred tags are favoured
+ALTER TABLE people.examiners
+ADD COLUMN tags text[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE records.stations
+ADD COLUMN required_tags text[] NOT NULL DEFAULT '{}',
+ADD COLUMN preferred_tags text[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE templates.stations
+ADD COLUMN required_tags text[] NOT NULL DEFAULT '{}',
+ADD COLUMN preferred_tags text[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- examiners keep the same station across the runs of a slot. Preferred by default, required when true
+ALTER TABLE records.sessions
+ADD COLUMN require_examiner_continuity bool NOT NULL DEFAULT false;
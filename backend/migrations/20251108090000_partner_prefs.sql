@@ -0,0 +1,4 @@
+-- partner_pref is the first choice partner, partner_pref_2 the second. avoid lists shortcodes the candidate must not be paired with
+ALTER TABLE people.candidates
+ADD COLUMN partner_pref_2 text,
+ADD COLUMN avoid text[] NOT NULL DEFAULT '{}';
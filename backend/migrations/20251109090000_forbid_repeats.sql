@@ -0,0 +1,3 @@
+-- candidate pairs and examiner-candidate pairings from earlier sessions of the organisation. Penalised by default, forbidden when true
+ALTER TABLE records.sessions
+ADD COLUMN forbid_repeats bool NOT NULL DEFAULT false;
@@ -0,0 +1,10 @@
+-- examined stations have an examiner, rest stations are breaks and written stations are unmanned. Replaces matching the title 'rest'
+ALTER TABLE records.stations
+ADD COLUMN kind text NOT NULL DEFAULT 'examined' CHECK (kind IN ('examined', 'rest', 'written'));
+
+UPDATE records.stations SET kind = 'rest' WHERE LOWER(title) = 'rest';
+
+ALTER TABLE templates.stations
+ADD COLUMN kind text NOT NULL DEFAULT 'examined' CHECK (kind IN ('examined', 'rest', 'written'));
+
+UPDATE templates.stations SET kind = 'rest' WHERE LOWER(title) = 'rest';
@@ -0,0 +1,12 @@
+-- placeholder people hold a vacancy in an allocation until a late registrant replaces them. Hidden from people listings
+ALTER TABLE people.candidates
+ADD COLUMN placeholder bool NOT NULL DEFAULT false;
+
+ALTER TABLE people.examiners
+ADD COLUMN placeholder bool NOT NULL DEFAULT false;
+
+-- fill people created before the flag existed
+UPDATE people.candidates SET placeholder = true WHERE first_name = 'fill' AND last_name = 'candidate';
+UPDATE people.examiners SET placeholder = true WHERE first_name = 'fill' AND last_name = 'examiner';
+UPDATE people.candidates SET first_name = 'Vacancy', last_name = '' WHERE placeholder;
+UPDATE people.examiners SET first_name = 'Vacancy', last_name = '' WHERE placeholder;
@@ -0,0 +1,41 @@
+-- stations take 1 to 3 candidates, so the group at a station is stored as members instead of candidate_1 / candidate_2
+ALTER TABLE records.sessions
+ADD COLUMN candidates_per_station smallint NOT NULL DEFAULT 2
+CONSTRAINT candidates_per_station_range CHECK (candidates_per_station BETWEEN 1 AND 3);
+
+CREATE TABLE IF NOT EXISTS records.allocation_members (
+    allocation_id UUID NOT NULL REFERENCES records.allocations(id) ON DELETE CASCADE,
+    run_id UUID NOT NULL REFERENCES records.runs(id) ON DELETE CASCADE,
+    candidate_id UUID NOT NULL REFERENCES people.candidates(id) ON DELETE CASCADE,
+    position smallint NOT NULL, -- 1 is the first performer
+    PRIMARY KEY (allocation_id, position),
+    -- Ensure a candidate is only in 1 group per run
+    CONSTRAINT unique_member UNIQUE (run_id, candidate_id)
+);
+
+CREATE TABLE IF NOT EXISTS records.allocations_history_members (
+    allocation_id UUID NOT NULL REFERENCES records.allocations_history(id) ON DELETE CASCADE,
+    candidate_id UUID NOT NULL REFERENCES people.candidates(id) ON DELETE CASCADE,
+    position smallint NOT NULL,
+    PRIMARY KEY (allocation_id, position)
+);
+
+INSERT INTO records.allocation_members (allocation_id, run_id, candidate_id, position)
+SELECT id, run_id, candidate_1, 1 FROM records.allocations WHERE candidate_1 IS NOT NULL
+UNION ALL
+SELECT id, run_id, candidate_2, 2 FROM records.allocations WHERE candidate_2 IS NOT NULL;
+
+INSERT INTO records.allocations_history_members (allocation_id, candidate_id, position)
+SELECT id, candidate_1, 1 FROM records.allocations_history WHERE candidate_1 IS NOT NULL
+UNION ALL
+SELECT id, candidate_2, 2 FROM records.allocations_history WHERE candidate_2 IS NOT NULL;
+
+ALTER TABLE records.allocations
+DROP CONSTRAINT unique_candidate_pair,
+DROP CONSTRAINT different_candidates,
+DROP COLUMN candidate_1,
+DROP COLUMN candidate_2;
+
+ALTER TABLE records.allocations_history
+DROP COLUMN candidate_1,
+DROP COLUMN candidate_2;
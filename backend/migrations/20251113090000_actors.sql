@@ -0,0 +1,31 @@
+-- simulated patients (actors) play the patient at stations that need one, allocated per circuit and run like examiners
+CREATE TABLE IF NOT EXISTS people.actors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    session_id UUID NOT NULL REFERENCES records.sessions(id) ON DELETE CASCADE,
+    first_name text NOT NULL,
+    last_name text NOT NULL,
+    shortcode text NOT NULL,
+    gender text NOT NULL CHECK (gender IN ('female', 'male', 'other')),
+    am bool NOT NULL,
+    pm bool NOT NULL,
+    checked_in bool NOT NULL,
+    stations text[] NOT NULL DEFAULT '{}', -- lowercase titles of the stations they can play, any station when empty
+    CONSTRAINT unique_actors_session_shortcode UNIQUE (session_id, shortcode)
+);
+
+-- actor_gender is the gender the station's patient has to be played by, any actor when NULL
+ALTER TABLE records.stations
+ADD COLUMN needs_actor bool NOT NULL DEFAULT false,
+ADD COLUMN actor_gender text CHECK (actor_gender IN ('female', 'male'));
+
+ALTER TABLE templates.stations
+ADD COLUMN needs_actor bool NOT NULL DEFAULT false,
+ADD COLUMN actor_gender text CHECK (actor_gender IN ('female', 'male'));
+
+ALTER TABLE records.allocations
+ADD COLUMN actor UUID REFERENCES people.actors(id) ON DELETE SET NULL,
+-- Ensure an actor is only at 1 station per run
+ADD CONSTRAINT unique_actor UNIQUE (run_id, actor);
+
+ALTER TABLE records.allocations_history
+ADD COLUMN actor UUID REFERENCES people.actors(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- run times are stored in UTC, AM / PM and the timetable go by the session's local time
+ALTER TABLE records.sessions
+ADD COLUMN timezone text NOT NULL DEFAULT 'Europe/London'; -- IANA name
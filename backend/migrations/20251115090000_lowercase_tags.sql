@@ -0,0 +1,11 @@
+-- tags are matched exactly, so tags saved before they were lowercased on write are brought in line
+UPDATE people.examiners
+SET tags = ARRAY(SELECT lower(trim(tag)) FROM unnest(tags) AS tag);
+
+UPDATE records.stations
+SET required_tags = ARRAY(SELECT lower(trim(tag)) FROM unnest(required_tags) AS tag),
+    preferred_tags = ARRAY(SELECT lower(trim(tag)) FROM unnest(preferred_tags) AS tag);
+
+UPDATE templates.stations
+SET required_tags = ARRAY(SELECT lower(trim(tag)) FROM unnest(required_tags) AS tag),
+    preferred_tags = ARRAY(SELECT lower(trim(tag)) FROM unnest(preferred_tags) AS tag);
@@ -1,184 +1,2145 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::{Context, anyhow};
-use good_lp::{solvers::highs::highs, variable, variables, Expression, IntoAffineExpression, Solution, SolverModel, Variable, Constraint};
+use good_lp::{variable, variables, Expression, IntoAffineExpression, ProblemVariables, ResolutionError, Solution, SolverModel, Variable, Constraint};
+use serde::Serialize;
 use uuid::Uuid;
 use crate::error::AppError;
 
 use super::http::{
     candidates::Candidate,
     examiners::Examiner,
+    actors::Actor,
     stations::Station,
     circuits::Circuit,
+    runs::Run,
+    allocations::Availability,
 };
 
-#[derive(Debug)]
+// penalty for each station an examiner uses, above any preferred tag bonus so staying put wins
+const CONTINUITY_WEIGHT: f64 = 10.0;
+// penalty for pairing candidates who were partners in an earlier session, below a first choice bonus so explicit preferences still win
+const PARTNER_REPEAT_WEIGHT: f64 = 1.5;
+// penalty for each candidate an examiner already examined in an earlier session, between a preferred tag bonus and continuity
+const EXAMINER_REPEAT_WEIGHT: f64 = 2.0;
+// penalty for each fill person a slot needs, above everything else in the slot assignment
+const FILL_WEIGHT: f64 = 100.0;
+// reward per preference bonus for partners in the same slot, above balancing the slots
+const SLOT_PARTNER_WEIGHT: f64 = 10.0;
+// cost of each placeholder used when reallocating, above moving a candidate so real people fill gaps first
+const REALLOCATE_FILL_WEIGHT: f64 = 3.0;
+// reward for each reserve (present but unallocated) placed when reallocating, below a move so nobody moves to make room
+const RESERVE_WEIGHT: f64 = 0.5;
+
+/// MILP backend the allocation problems are solved with, picked at build time by the `highs` (default) or `microlp` feature
+pub trait MilpBackend {
+    type Solution: Solution;
+
+    fn maximise(vars: ProblemVariables, objective: Expression, constraints: Vec<Constraint>) -> Result<Self::Solution, ResolutionError>;
+}
+
+#[cfg(feature = "highs")]
+pub struct Highs; // needs cmake + clang to build
+
+#[cfg(feature = "highs")]
+impl MilpBackend for Highs {
+    type Solution = good_lp::solvers::highs::HighsSolution;
+
+    fn maximise(vars: ProblemVariables, objective: Expression, constraints: Vec<Constraint>) -> Result<Self::Solution, ResolutionError> {
+        vars.maximise(objective)
+            .using(good_lp::solvers::highs::highs)
+            .with_all(constraints)
+            .solve()
+    }
+}
+
+#[cfg(feature = "microlp")]
+pub struct MicroLp; // pure Rust, slower on large problems
+
+#[cfg(feature = "microlp")]
+impl MilpBackend for MicroLp {
+    type Solution = good_lp::solvers::microlp::MicroLpSolution;
+
+    fn maximise(vars: ProblemVariables, objective: Expression, constraints: Vec<Constraint>) -> Result<Self::Solution, ResolutionError> {
+        vars.maximise(objective)
+            .using(good_lp::solvers::microlp::microlp)
+            .with_all(constraints)
+            .solve()
+    }
+}
+
+// HiGHS wins when both features are enabled
+#[cfg(feature = "highs")]
+pub type Backend = Highs;
+#[cfg(all(feature = "microlp", not(feature = "highs")))]
+pub type Backend = MicroLp;
+#[cfg(not(any(feature = "highs", feature = "microlp")))]
+compile_error!("No solver backend enabled, build with the `highs` or `microlp` feature");
+
+#[derive(Debug, Clone)]
 pub struct SlotAllocation {
     pub circuit_id: Uuid,
     pub station_id: Uuid,
-    pub candidate_1: Uuid,
-    pub candidate_2: Uuid,
+    pub candidates: Vec<Uuid>, // the group at the station, in position order
 }
 
 #[derive(Debug)]
 pub struct TimeAllocation {
+    pub run_id: Uuid,
     pub circuit_id: Uuid,
     pub station_id: Uuid,
     pub examiner: Uuid,
 }
 
+#[derive(Debug, Clone)]
+pub struct ActorAllocation {
+    pub run_id: Uuid,
+    pub circuit_id: Uuid,
+    pub station_id: Uuid,
+    pub actor: Uuid,
+}
+
+#[derive(Debug, Default)]
+pub struct SlotAssignment { // which slot everyone goes to, and the fill people each slot needs
+    pub candidates: HashMap<Uuid, Uuid>, // candidate id -> slot id
+    pub examiners: Vec<(Uuid, Uuid)>, // (examiner id, slot id), examiners can take several slots that do not overlap
+    pub fill_candidates: HashMap<Uuid, usize>, // slot id -> fill candidates, who can go in female-only circuits
+    pub fill_examiners: HashMap<Uuid, (usize, usize)>, // slot id -> (female, other) fill examiners
+}
+
+/// The circuits and runs being allocated, shared by every allocation step
+pub struct Layout<'a> {
+    pub circuits: &'a [Circuit],
+    pub slot_times: &'a HashMap<Uuid, Availability>, // whether each slot has AM and/or PM runs
+    pub am_runs: &'a [Run],
+    pub pm_runs: &'a [Run],
+    pub group_size: usize, // candidates per station, 1 to 3
+}
+
+impl<'a> Layout<'a> {
+    /// Runs of the circuits' slots, with whether each is an AM run
+    fn runs(&self) -> Vec<(&'a Run, bool)> {
+        self.am_runs
+            .iter()
+            .map(|run| (run, true))
+            .chain(self.pm_runs.iter().map(|run| (run, false)))
+            .filter(|(run, _)| self.circuits.iter().any(|circuit| circuit.slot_id == run.slot_id))
+            .collect()
+    }
+}
+
+/// Who examiners can examine, and how strictly they keep their station
+pub struct ExaminerRules<'a> {
+    pub exclusions: &'a [(Uuid, Uuid)], // (examiner id, candidate id) conflicts of interest
+    pub past_examiners: &'a [(Uuid, Uuid)], // (examiner id, candidate id) pairings from earlier sessions, penalised
+    pub require_continuity: bool, // examiners must keep 1 station per slot, instead of only preferring it
+}
+
+struct PairRules<'a> { // who can share a group, and what it is worth
+    past_pairs: &'a HashSet<(Uuid, Uuid)>, // candidate pairs from earlier sessions, both ways round
+    forbid_repeats: bool, // past pairs are never grouped again, instead of only penalised
+}
+
+// (candidate indices, preferred pairs (i, j) made) of a circuit after stage 1
+type CircuitMembers = (Vec<usize>, Vec<(usize, usize)>);
+
+/// Assigns every candidate and examiner to a slot before they are placed at stations.
+/// Respects AM / PM availability, keeps preferred partners in the same slot, makes every circuit's head-count a multiple
+/// of the group size (adding as few fill people as possible) and balances the candidates per circuit across slots
+pub fn assign_slots(
+    layout: &Layout,
+    num_stations: usize, // shared stations
+    num_examined: usize, // stations needing an examiner
+    candidates: &[Candidate], // only candidates who can attend a slot
+    examiners: &[Examiner],
+    locked_groups: &[SlotAllocation],
+    locked_examiners: &[(Uuid, Uuid)], // (examiner id, slot id) of locked examiner allocations
+) -> Result<SlotAssignment, AppError> {
+    let Layout { circuits, slot_times, group_size, .. } = *layout;
+    let mut vars = variables!();
+    let mut slot_ids: Vec<Uuid> = slot_times.keys().copied().collect();
+    slot_ids.sort();
+    let slot_circuits: Vec<Vec<&Circuit>> = slot_ids
+        .iter()
+        .map(|slot_id| circuits.iter().filter(|c| c.slot_id == *slot_id).collect())
+        .collect();
+    let slot_runs: Vec<Vec<(&Run, bool)>> = slot_ids
+        .iter()
+        .map(|slot_id| {
+            layout.am_runs.iter().map(|run| (run, true))
+                .chain(layout.pm_runs.iter().map(|run| (run, false)))
+                .filter(|(run, _)| run.slot_id == *slot_id)
+                .collect()
+        })
+        .collect();
+
+    // candidate_vars[i][t]: candidate i goes to slot t, None when they cannot attend it
+    let candidate_vars: Vec<Vec<Option<Variable>>> = candidates
+        .iter()
+        .map(|candidate| {
+            slot_ids
+                .iter()
+                .map(|slot_id| can_attend(candidate, &slot_times[slot_id]).then(|| vars.add(variable().binary())))
+                .collect()
+        })
+        .collect();
+
+    // group_count_vars[t][k]: groups in the k-th circuit of slot t
+    let group_count_vars: Vec<Vec<Variable>> = slot_circuits
+        .iter()
+        .map(|circuits| circuits.iter().map(|_| vars.add(variable().integer().min(0).max(num_stations as f64))).collect())
+        .collect();
+
+    // fill_candidate_vars[t], fill_female_vars[t], fill_other_vars[t]: fill people added to slot t
+    let fill_candidate_vars: Vec<Variable> = slot_ids.iter().map(|_| vars.add(variable().integer().min(0))).collect();
+    let fill_female_vars: Vec<Variable> = slot_ids.iter().map(|_| vars.add(variable().integer().min(0))).collect();
+    let fill_other_vars: Vec<Variable> = slot_ids.iter().map(|_| vars.add(variable().integer().min(0))).collect();
+
+    // examiner_vars[e][t]: examiner e takes slot t
+    let examiner_vars: Vec<Vec<Variable>> = examiners
+        .iter()
+        .map(|_| slot_ids.iter().map(|_| vars.add(variable().binary())).collect())
+        .collect();
+
+    // preference_vars: (i, j, t, x, bonus) where x is preferred partners i and j both in slot t
+    let mut preference_vars = Vec::new();
+    for i in 0..candidates.len() {
+        for j in (i + 1)..candidates.len() {
+            let bonus = partner_bonus(&candidates[i], &candidates[j]) + partner_bonus(&candidates[j], &candidates[i]);
+            if bonus > 0.0 {
+                for (t, (var_i, var_j)) in candidate_vars[i].iter().zip(&candidate_vars[j]).enumerate() {
+                    if var_i.is_some() && var_j.is_some() {
+                        preference_vars.push((i, j, t, vars.add(variable().binary()), bonus));
+                    }
+                }
+            }
+        }
+    }
+
+    // highest and lowest candidates per circuit across slots
+    let most_per_circuit = vars.add(variable().min(0));
+    let least_per_circuit = vars.add(variable().min(0));
+
+    let mut constraints: Vec<Constraint> = Vec::new();
+
+    // Constraint: Each candidate goes to exactly one slot they can attend
+    for (i, candidate) in candidates.iter().enumerate() {
+        let slot_vars: Vec<Variable> = candidate_vars[i].iter().flatten().copied().collect();
+        if slot_vars.is_empty() {
+            return Err(AppError::from(anyhow!("{} {} cannot attend any slot", candidate.first_name, candidate.last_name)));
+        }
+        let slot_sum: Expression = slot_vars.into_iter().sum();
+        constraints.push(slot_sum.eq(1));
+    }
+
+    // Constraint: Locked groups stay in the slot of their circuit
+    for locked in locked_groups {
+        let slot_id = circuits.iter().find(|circuit| circuit.id == locked.circuit_id).map(|circuit| circuit.slot_id)
+            .ok_or_else(|| anyhow!("Locked group is in a circuit that no longer exists"))?;
+        let t = slot_ids.iter().position(|id| *id == slot_id)
+            .ok_or_else(|| anyhow!("Locked group is in a slot that no longer exists"))?;
+        for &candidate_id in &locked.candidates {
+            let i = candidates.iter().position(|candidate| candidate.id == candidate_id)
+                .ok_or_else(|| anyhow!("Locked group has a candidate who can no longer be allocated"))?;
+            let var = candidate_vars[i][t]
+                .ok_or_else(|| anyhow!("Locked group has a candidate who can no longer attend their slot"))?;
+            constraints.push(var.into_expression().eq(1));
+        }
+    }
+
+    // Constraint: Examiners only take slots with a run they can attend
+    for (e, examiner) in examiners.iter().enumerate() {
+        for t in 0..slot_ids.len() {
+            if !slot_runs[t].iter().any(|&(_, is_am)| if is_am { examiner.am } else { examiner.pm }) {
+                constraints.push(examiner_vars[e][t].into_expression().eq(0));
+            }
+        }
+    }
+
+    // Constraint: Locked examiners keep the slot they are locked in
+    for (examiner_id, slot_id) in locked_examiners {
+        if let (Some(e), Some(t)) = (examiners.iter().position(|e| e.id == *examiner_id), slot_ids.iter().position(|id| id == slot_id)) {
+            constraints.push(examiner_vars[e][t].into_expression().eq(1));
+        }
+    }
+
+    for t in 0..slot_ids.len() {
+        let slot_candidates = |filter: &dyn Fn(&Candidate) -> bool| -> Expression {
+            (0..candidates.len())
+                .filter(|&i| filter(&candidates[i]))
+                .filter_map(|i| candidate_vars[i][t])
+                .sum()
+        };
+
+        // Constraint: Every circuit's head-count is a multiple of the group size, made up with fill candidates
+        let group_sum: Expression = group_count_vars[t].iter().copied().sum();
+        constraints.push((slot_candidates(&|_| true) + fill_candidate_vars[t]).eq(group_size as f64 * group_sum));
+
+        // Constraint: Candidates who are not female-only fit in the circuits that are not female-only
+        let mixed_sum: Expression = slot_circuits[t]
+            .iter()
+            .enumerate()
+            .filter(|(_, circuit)| !circuit.female_only)
+            .map(|(k, _)| group_count_vars[t][k])
+            .sum();
+        constraints.push(slot_candidates(&|c| !c.female_only).leq(group_size as f64 * mixed_sum));
+
+        // Constraint: Every examined station has an examiner in every run, made up with fill examiners
+        let female_circuits = slot_circuits[t].iter().filter(|circuit| circuit.female_only).count();
+        for &(_, is_am) in &slot_runs[t] {
+            let available = |examiner: &Examiner| if is_am { examiner.am } else { examiner.pm };
+            let examiner_sum: Expression = (0..examiners.len())
+                .filter(|&e| available(&examiners[e]))
+                .map(|e| examiner_vars[e][t])
+                .sum();
+            constraints.push((examiner_sum + fill_female_vars[t] + fill_other_vars[t]).geq((slot_circuits[t].len() * num_examined) as f64));
+            let female_sum: Expression = (0..examiners.len())
+                .filter(|&e| examiners[e].female && available(&examiners[e]))
+                .map(|e| examiner_vars[e][t])
+                .sum();
+            constraints.push((female_sum + fill_female_vars[t]).geq((female_circuits * num_examined) as f64));
+        }
+
+        // Constraint: Balance candidates per circuit across slots
+        if !slot_circuits[t].is_empty() {
+            let per_circuit = (slot_candidates(&|_| true) + fill_candidate_vars[t]) * (1.0 / slot_circuits[t].len() as f64);
+            constraints.push(per_circuit.clone().leq(most_per_circuit));
+            constraints.push(per_circuit.geq(least_per_circuit));
+        }
+    }
+
+    // Constraint: Examiners cannot take slots with runs at the same time
+    for t in 0..slot_ids.len() {
+        for u in (t + 1)..slot_ids.len() {
+            let overlapping = slot_runs[t].iter().any(|(a, _)| {
+                slot_runs[u].iter().any(|(b, _)| a.scheduled_start < b.scheduled_end && b.scheduled_start < a.scheduled_end)
+            });
+            if overlapping {
+                for slot_vars in &examiner_vars {
+                    constraints.push((slot_vars[t] + slot_vars[u]).leq(1));
+                }
+            }
+        }
+    }
+
+    // Constraint: Preferred partners only count as together when both are in the slot
+    for &(i, j, t, x, _) in &preference_vars {
+        if let (Some(var_i), Some(var_j)) = (candidate_vars[i][t], candidate_vars[j][t]) {
+            constraints.push(x.into_expression().leq(var_i));
+            constraints.push(x.into_expression().leq(var_j));
+        }
+    }
+
+    // Objective: as few fill people as possible, then preferred partners together, then balanced slots.
+    // Examiners take every slot they can so the examiner allocation has the most choice
+    let mut objective = Expression::from(0.0);
+    for t in 0..slot_ids.len() {
+        objective -= FILL_WEIGHT * fill_candidate_vars[t] + FILL_WEIGHT * fill_female_vars[t] + FILL_WEIGHT * fill_other_vars[t];
+    }
+    for &(_, _, _, x, bonus) in &preference_vars {
+        objective += SLOT_PARTNER_WEIGHT * bonus * x;
+    }
+    objective += least_per_circuit - most_per_circuit;
+    for &var in examiner_vars.iter().flatten() {
+        objective += 0.01 * var;
+    }
+
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| "Error when assigning people to slots. Check there is enough space in the circuits of each slot")?;
+
+    let mut assignment = SlotAssignment::default();
+    for (i, candidate) in candidates.iter().enumerate() {
+        if let Some(t) = (0..slot_ids.len()).find(|&t| candidate_vars[i][t].is_some_and(|var| solution.value(var) > 0.5)) {
+            assignment.candidates.insert(candidate.id, slot_ids[t]);
+        }
+    }
+    for (e, examiner) in examiners.iter().enumerate() {
+        for t in 0..slot_ids.len() {
+            if solution.value(examiner_vars[e][t]) > 0.5 {
+                assignment.examiners.push((examiner.id, slot_ids[t]));
+            }
+        }
+    }
+    for (t, slot_id) in slot_ids.iter().enumerate() {
+        assignment.fill_candidates.insert(*slot_id, solution.value(fill_candidate_vars[t]).round() as usize);
+        assignment.fill_examiners.insert(*slot_id, (solution.value(fill_female_vars[t]).round() as usize, solution.value(fill_other_vars[t]).round() as usize));
+    }
+    Ok(assignment)
+}
+
 pub fn allocate_by_slot( // for candidates ONLY
-    circuits: &[Circuit],
+    layout: &Layout,
     stations: &[Station], // shared stations
     candidates: &[Candidate],
+    locked_groups: &[SlotAllocation], // groups kept at their station
+    past_pairs: &[(Uuid, Uuid)], // candidate pairs from earlier sessions
+    forbid_repeats: bool, // past pairs are never grouped again, instead of only penalised
 ) -> Result<Vec<SlotAllocation>, AppError> {
+    let Layout { circuits, group_size, .. } = *layout;
+    // Solved in 2 stages so the problem grows with candidates x circuits instead of candidates^k x circuits x stations:
+    // 1. assign candidates to circuits, bringing preferred partners together along the way
+    // 2. group the rest within each circuit, then hand groups out to free stations (stations are interchangeable)
+    let mut locked_stations: Vec<Vec<Option<Vec<usize>>>> = vec![vec![None; stations.len()]; circuits.len()]; // [c][s]: locked group
+    let mut locked_circuit: Vec<Option<usize>> = vec![None; candidates.len()];
+    for locked in locked_groups {
+        let c = circuits.iter().position(|circuit| circuit.id == locked.circuit_id)
+            .ok_or_else(|| anyhow!("Locked group is in a circuit that no longer exists"))?;
+        let s = stations.iter().position(|station| station.id == locked.station_id)
+            .ok_or_else(|| anyhow!("Locked group is at a station that no longer exists"))?;
+        if locked.candidates.len() != group_size {
+            return Err(AppError::from(anyhow!("Locked group has {} candidates, the session has {} per station", locked.candidates.len(), group_size)));
+        }
+        let mut group = Vec::new();
+        for candidate_id in &locked.candidates {
+            let i = candidates.iter().position(|candidate| candidate.id == *candidate_id)
+                .ok_or_else(|| anyhow!("Locked group has a candidate who can no longer be allocated"))?;
+            locked_circuit[i] = Some(c);
+            group.push(i);
+        }
+        locked_stations[c][s] = Some(group);
+    }
+
+    let past_pairs: HashSet<(Uuid, Uuid)> = past_pairs.iter().flat_map(|&(a, b)| [(a, b), (b, a)]).collect();
+    let pair_rules = PairRules { past_pairs: &past_pairs, forbid_repeats };
+    let circuit_members = assign_circuits(layout, stations.len(), candidates, &locked_circuit, &pair_rules)?;
+
+    let mut allocations = Vec::new();
+    for (c, (members, preferred_pairs)) in circuit_members.into_iter().enumerate() {
+        // in pairs, preferred partners from stage 1 are already a full group
+        let preferred_groups: Vec<Vec<usize>> = if group_size == 2 {
+            preferred_pairs.into_iter().map(|(i, j)| vec![i, j]).collect()
+        } else {
+            Vec::new()
+        };
+        let ungrouped: Vec<usize> = members
+            .into_iter()
+            .filter(|&i| locked_circuit[i].is_none() && !preferred_groups.iter().any(|group| group.contains(&i)))
+            .collect();
+        let mut groups = preferred_groups;
+        match group_within_circuit(&circuits[c], candidates, &ungrouped, &pair_rules, group_size) {
+            Ok(circuit_groups) => groups.extend(circuit_groups),
+            // stage 1 can still hand a circuit candidates who cannot all be grouped (e.g. groups of 3), place everyone at once instead
+            Err(_) => return assign_stations(layout, stations, candidates, &locked_stations, &pair_rules),
+        }
+
+        let mut free_stations = (0..stations.len()).filter(|&s| locked_stations[c][s].is_none());
+        for (s, group) in locked_stations[c].iter().enumerate().filter_map(|(s, group)| group.as_ref().map(|group| (s, group))) {
+            allocations.push(SlotAllocation {
+                circuit_id: circuits[c].id,
+                station_id: stations[s].id,
+                candidates: group.iter().map(|&i| candidates[i].id).collect(),
+            });
+        }
+        for group in groups {
+            let s = free_stations.next()
+                .ok_or_else(|| anyhow!("Circuit {} has more groups than stations", circuits[c].key))?;
+            allocations.push(SlotAllocation {
+                circuit_id: circuits[c].id,
+                station_id: stations[s].id,
+                candidates: group.iter().map(|&i| candidates[i].id).collect(),
+            });
+        }
+    }
+
+    Ok(allocations)
+}
+
+/// Stage 1: assigns every candidate to a circuit, with a head-count per circuit that is a multiple of the group size.
+/// Preferred partners are brought together here, over preference edges only, so the model stays sparse
+fn assign_circuits(
+    layout: &Layout,
+    num_stations: usize,
+    candidates: &[Candidate],
+    locked_circuit: &[Option<usize>], // [i]: circuit of a locked candidate
+    pair_rules: &PairRules,
+) -> Result<Vec<CircuitMembers>, AppError> {
+    let Layout { circuits, slot_times, group_size, .. } = *layout;
     let mut vars = variables!();
     let num_circuits = circuits.len();
-    let num_stations = stations.len();
 
-    // candidate_vars[c][i][s]: candidate i assigned to station s in circuit c
-    let candidate_vars: Vec<Vec<Vec<Variable>>> = (0..num_circuits)
-    .map(|_| {
-        (0..candidates.len())
-            .map(|_| (0..num_stations).map(|_| vars.add(variable().binary())).collect())
-            .collect()
-    })
-    .collect();
+    // circuit_vars[c][i]: candidate i assigned to circuit c
+    let circuit_vars: Vec<Vec<Variable>> = (0..num_circuits)
+        .map(|_| (0..candidates.len()).map(|_| vars.add(variable().binary())).collect())
+        .collect();
+
+    // group_count_vars[c]: number of groups in circuit c (stations can be left empty if there are fewer candidates)
+    let group_count_vars: Vec<Variable> = (0..num_circuits)
+        .map(|_| vars.add(variable().integer().min(0).max(num_stations as f64)))
+        .collect();
 
-    // pair_vars[c][(i,j)][s]: candidates i and j paired at station s in circuit c
-    let mut pair_vars = Vec::new();
+    // preference_edges: (i, j, bonus) for candidates who want each other as partners and can be grouped. Nobody has a partner alone
+    let mut preference_edges = Vec::new();
+    for i in 0..candidates.len() {
+        for j in (i + 1)..candidates.len() {
+            let bonus = pair_rules.weight(&candidates[i], &candidates[j]);
+            let blocked = !pair_rules.allowed(&candidates[i], &candidates[j])
+                || locked_circuit[i].is_some() || locked_circuit[j].is_some();
+            if bonus > 0.0 && !blocked && group_size > 1 {
+                preference_edges.push((i, j, bonus));
+            }
+        }
+    }
+
+    // preference_vars[c][e]: preference edge e brought together in circuit c
+    let preference_vars: Vec<Vec<Variable>> = (0..num_circuits)
+        .map(|_| preference_edges.iter().map(|_| vars.add(variable().binary())).collect())
+        .collect();
+
+    let mut constraints: Vec<Constraint> = Vec::new();
+
+    // Constraint: Each candidate must be assigned to exactly one circuit
+    for i in 0..candidates.len() {
+        let circuit_sum: Expression = circuit_vars.iter().map(|candidate_vars| candidate_vars[i]).sum();
+        constraints.push(circuit_sum.eq(1));
+    }
+
+    // Constraint: Each circuit has a whole number of groups, at most 1 group per station
+    for (candidate_vars, &group_count) in circuit_vars.iter().zip(&group_count_vars) {
+        let candidate_sum: Expression = candidate_vars.iter().copied().sum();
+        constraints.push(candidate_sum.eq(group_size as f64 * group_count));
+    }
+
+    for (c, circuit) in circuits.iter().enumerate() {
+        let slot_time = slot_times.get(&circuit.slot_id);
+        for (i, candidate) in candidates.iter().enumerate() {
+            // Constraint: Force candidates to 0 for circuits in slots they cannot attend (AM / PM)
+            let available = slot_time.is_some_and(|time| can_attend(candidate, time));
+            // Constraint: Force non-female candidates to 0 for female-only circuits
+            let female_ok = !circuit.female_only || candidate.female_only;
+            match locked_circuit[i] {
+                // Constraint: Locked groups stay in their circuit
+                Some(locked_c) => constraints.push(circuit_vars[c][i].into_expression().eq(if locked_c == c { 1 } else { 0 })),
+                None if !available || !female_ok => constraints.push(circuit_vars[c][i].into_expression().eq(0)),
+                None => {}
+            }
+        }
+    }
+
+    // Constraint: A preference edge can only be brought together if both candidates are in that circuit
     for c in 0..num_circuits {
-        for i in 0..candidates.len() {
-            for j in (i + 1)..candidates.len() {
-                for s in 0..num_stations {
-                    pair_vars.push(((c, i, j, s), vars.add(variable().binary())));
+        for (e, &(i, j, _)) in preference_edges.iter().enumerate() {
+            constraints.push(preference_vars[c][e].into_expression().leq(circuit_vars[c][i]));
+            constraints.push(preference_vars[c][e].into_expression().leq(circuit_vars[c][j]));
+        }
+    }
+
+    // Constraint: Each candidate has at most as many preferred partners as there are other places in their group
+    for i in 0..candidates.len() {
+        let edge_sum: Expression = preference_edges
+            .iter()
+            .enumerate()
+            .filter(|(_, &(a, b, _))| a == i || b == i)
+            .flat_map(|(e, _)| preference_vars.iter().map(move |edge_vars| edge_vars[e]))
+            .sum();
+        constraints.push(edge_sum.leq((group_size - 1) as f64));
+    }
+
+    // Keep stage 2 solvable: the unlocked candidates of a circuit must split into groups nobody objects to.
+    // Both constraints below hold in any valid allocation, so they never rule out a solution
+    if group_size > 1 {
+        let unlocked: Vec<usize> = (0..candidates.len()).filter(|&i| locked_circuit[i].is_none()).collect();
+        let allowed = |i: usize, j: usize| pair_rules.allowed(&candidates[i], &candidates[j]);
+        for (c, (candidate_vars, &group_count)) in circuit_vars.iter().zip(&group_count_vars).enumerate() {
+            let locked_groups = locked_circuit.iter().filter(|&&locked_c| locked_c == Some(c)).count() / group_size;
+            for (a, &i) in unlocked.iter().enumerate() {
+                for &j in unlocked[(a + 1)..].iter().filter(|&&j| !allowed(i, j)) {
+                    // Constraint: A conflicting pair (avoided or forbidden repeat) only shares a circuit with 2+ unlocked groups in it
+                    constraints.push((candidate_vars[i] + candidate_vars[j]).leq(group_count - locked_groups as f64));
+                }
+                if unlocked.iter().any(|&j| j != i && !allowed(i, j)) {
+                    // Constraint: A candidate with conflicts has enough others in their circuit they can be grouped with
+                    let compatible_sum: Expression = unlocked
+                        .iter()
+                        .filter(|&&j| j != i && allowed(i, j))
+                        .map(|&j| candidate_vars[j])
+                        .sum();
+                    constraints.push(compatible_sum.geq((group_size - 1) as f64 * candidate_vars[i]));
                 }
             }
         }
     }
 
-    // Constraint: Each station must have exactly 2 candidates.
-    let st_can_constraints: Vec<Constraint> = (0..num_circuits)
-        .flat_map(|c| {
-            let val_vars = candidate_vars.clone();
-            (0..num_stations).map(move |s| {
-                let candidate_sum: Expression = (0..candidates.len())
-                    .map(|i| val_vars[c][i][s])
-                    .sum();
-                candidate_sum.eq(2)
-            })
+    // Objective: maximize candidate preference satisfaction, weighted by rank
+    let mut objective = Expression::from(0.0);
+    for edge_vars in &preference_vars {
+        for (&var, &(_, _, bonus)) in edge_vars.iter().zip(&preference_edges) {
+            objective += bonus * var;
+        }
+    }
+
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| "Error when solving problem")?;
+
+    Ok((0..num_circuits)
+        .map(|c| {
+            let members = (0..candidates.len())
+                .filter(|&i| solution.value(circuit_vars[c][i]) > 0.5)
+                .collect();
+            let preferred_pairs = preference_edges
+                .iter()
+                .enumerate()
+                .filter(|&(e, _)| solution.value(preference_vars[c][e]) > 0.5)
+                .map(|(_, &(i, j, _))| (i, j))
+                .collect();
+            (members, preferred_pairs)
         })
+        .collect())
+}
+
+/// Stage 2: groups the remaining candidates of one circuit, never grouping anyone with someone they avoid
+fn group_within_circuit(
+    circuit: &Circuit,
+    candidates: &[Candidate],
+    ungrouped: &[usize], // candidate indices, a multiple of group_size in number
+    pair_rules: &PairRules,
+    group_size: usize,
+) -> Result<Vec<Vec<usize>>, AppError> {
+    if group_size == 1 {
+        return Ok(ungrouped.iter().map(|&i| vec![i]).collect());
+    }
+    if ungrouped.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut vars = variables!();
+
+    // group_vars: (group, x) where x is the group chosen, groups with an avoided (or forbidden repeat) pair are left out
+    let allowed = |i: usize, j: usize| pair_rules.allowed(&candidates[i], &candidates[j]);
+    let group_vars: Vec<(Vec<usize>, Variable)> = combinations(ungrouped, group_size)
+        .into_iter()
+        .filter(|group| group.iter().enumerate().all(|(a, &i)| group[(a + 1)..].iter().all(|&j| allowed(i, j))))
+        .map(|group| (group, vars.add(variable().binary())))
         .collect();
 
-    // Each candidate can be assigned to at most one station across all circuits
-    let can_st_constraints: Vec<Constraint> = (0..candidates.len())
-        .map(|i| {
-            let station_sum: Expression = (0..num_circuits)
-                .flat_map(|c| {
-                    let val_vars = candidate_vars.clone();
-                    (0..num_stations).map(move |s| val_vars[c][i][s])
-                })
+    // Constraint: Each candidate is in exactly one group
+    let constraints: Vec<Constraint> = ungrouped
+        .iter()
+        .map(|&k| {
+            let group_sum: Expression = group_vars
+                .iter()
+                .filter(|(group, _)| group.contains(&k))
+                .map(|&(_, x)| x)
                 .sum();
-            station_sum.leq(1)
+            group_sum.eq(1)
         })
         .collect();
 
-    // Constraint: Force non-female candidates/examiners to 0 for female-only stations
-    let female_only_constraints: Vec<Constraint> = (0..num_circuits)
-        .flat_map(|c| {
-            let mut circuit_constraints = Vec::new();
-            if circuits[c].female_only {
-                for (s, _station) in stations.iter().enumerate() {
-                    for (i, candidate) in candidates.iter().enumerate() {
-                        if !candidate.female_only {
-                            circuit_constraints.push(candidate_vars[c][i][s].into_expression().eq(0));
-                        }
-                    }
-                }
-            }
-            circuit_constraints.into_iter() // Return an iterator of constraints
+    // Objective: any partner preferences left over (stage 1 has already brought the best ones together), less repeated partners
+    let mut objective = Expression::from(0.0);
+    for (group, x) in &group_vars {
+        let weight: f64 = group
+            .iter()
+            .enumerate()
+            .flat_map(|(a, &i)| group[(a + 1)..].iter().map(move |&j| (i, j)))
+            .map(|(i, j)| pair_rules.weight(&candidates[i], &candidates[j]))
+            .sum();
+        if weight != 0.0 {
+            objective += weight * *x;
+        }
+    }
+
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| format!("Error when grouping candidates in circuit {}. Check the avoid lists and repeated partners", circuit.key))?;
+
+    let groups: Vec<Vec<usize>> = group_vars
+        .into_iter()
+        .filter(|(_, x)| solution.value(*x) > 0.5)
+        .map(|(group, _)| group)
+        .collect();
+    if groups.len() * group_size != ungrouped.len() {
+        return Err(AppError::from(anyhow!("Each station must have either 0 or {} candidates", group_size)));
+    }
+    Ok(groups)
+}
+
+/// Fallback for when the 2 stages cannot group a circuit: assigns candidates straight to stations in one model.
+/// Larger (candidates x circuits x stations), but never misses an allocation that exists
+fn assign_stations(
+    layout: &Layout,
+    stations: &[Station],
+    candidates: &[Candidate],
+    locked_stations: &[Vec<Option<Vec<usize>>>], // [c][s]: locked group
+    pair_rules: &PairRules,
+) -> Result<Vec<SlotAllocation>, AppError> {
+    let Layout { circuits, slot_times, group_size, .. } = *layout;
+    let mut vars = variables!();
+
+    // candidate_vars[c][s][i]: candidate i assigned to station s in circuit c
+    let candidate_vars: Vec<Vec<Vec<Variable>>> = circuits
+        .iter()
+        .map(|_| {
+            stations
+                .iter()
+                .map(|_| candidates.iter().map(|_| vars.add(variable().binary())).collect())
+                .collect()
         })
         .collect();
+    // station_vars[c][s]: station s in circuit c has a group (stations can be left empty if there are fewer candidates)
+    let station_vars: Vec<Vec<Variable>> = circuits
+        .iter()
+        .map(|_| stations.iter().map(|_| vars.add(variable().binary())).collect())
+        .collect();
 
-    // Constraint 6: Link candidate assignment variables to pairing variables.
-    // For each pair variable x(i,j,s) we add:
-    //   x(i,j,s) <= candidate_vars[i][s]
-    //   x(i,j,s) <= candidate_vars[j][s]
-    //   x(i,j,s) >= candidate_vars[i][s] + candidate_vars[j][s] - 1
-    let pairing_constraints: Vec<Constraint> = pair_vars
-    .iter()
-    .flat_map(|&((c, i, j, s), x_var)| {
-        vec![
-            x_var.into_expression().leq(candidate_vars[c][i][s]),
-            x_var.into_expression().leq(candidate_vars[c][j][s]),
-            x_var.into_expression().geq(candidate_vars[c][i][s] + candidate_vars[c][j][s] - 1),
-        ]
-    })
-    .collect();
+    // conflicts: (i, j) who cannot share a group, weighted_pairs: (i, j, weight) whose grouping is worth something
+    let mut conflicts = Vec::new();
+    let mut weighted_pairs = Vec::new();
+    for i in 0..candidates.len() {
+        for j in (i + 1)..candidates.len() {
+            if !pair_rules.allowed(&candidates[i], &candidates[j]) {
+                conflicts.push((i, j));
+            } else {
+                let weight = pair_rules.weight(&candidates[i], &candidates[j]);
+                if weight != 0.0 {
+                    weighted_pairs.push((i, j, weight));
+                }
+            }
+        }
+    }
 
-    let all_constraints: Vec<Constraint> = vec![
-        st_can_constraints,
-        can_st_constraints,
-        female_only_constraints,
-        pairing_constraints,
-    ]
-    .into_iter()
-    .flatten()
-    .collect();
+    let mut constraints: Vec<Constraint> = Vec::new();
 
+    // Constraint: Each candidate must be assigned to exactly one station across all circuits
+    for i in 0..candidates.len() {
+        let station_sum: Expression = candidate_vars.iter().flatten().map(|station_vars| station_vars[i]).sum();
+        constraints.push(station_sum.eq(1));
+    }
 
-    // Objective: maximize candidate preference satisfaction.
-    // For each pair (i, j, s), if candidate i prefers candidate j or vice versa, add a bonus.
     let mut objective = Expression::from(0.0);
-    for &((c, i, j, s), x_var) in &pair_vars {
-        if let Some(i_pref) = &candidates[i].partner_pref {
-            if i_pref == &candidates[j].shortcode {
-                objective = objective + 1.0 * x_var;
+    for (c, circuit) in circuits.iter().enumerate() {
+        let slot_time = slot_times.get(&circuit.slot_id);
+        for s in 0..stations.len() {
+            let station_candidates = &candidate_vars[c][s];
+            // Constraint: Each station must have either 0 or group_size candidates
+            let candidate_sum: Expression = station_candidates.iter().copied().sum();
+            constraints.push(candidate_sum.eq(group_size as f64 * station_vars[c][s]));
+
+            for (i, candidate) in candidates.iter().enumerate() {
+                // Constraint: Force candidates to 0 for circuits in slots they cannot attend (AM / PM), and non-female candidates for female-only circuits
+                let available = slot_time.is_some_and(|time| can_attend(candidate, time));
+                let female_ok = !circuit.female_only || candidate.female_only;
+                match &locked_stations[c][s] {
+                    // Constraint: Locked groups are fixed to their station
+                    Some(group) => constraints.push(station_candidates[i].into_expression().eq(if group.contains(&i) { 1 } else { 0 })),
+                    None if !available || !female_ok => constraints.push(station_candidates[i].into_expression().eq(0)),
+                    None => {}
+                }
             }
-        }
-        if let Some(j_pref) = &candidates[j].partner_pref {
-            if j_pref == &candidates[i].shortcode {
-                objective = objective + 1.0 * x_var;
+
+            // Constraint: Candidates are never grouped with someone either of them avoids, or a forbidden repeat
+            for &(i, j) in &conflicts {
+                constraints.push((station_candidates[i] + station_candidates[j]).leq(1));
+            }
+
+            // Objective: partner preferences, less repeated partners.
+            // A bonus can only be had with both candidates there, and a penalty cannot be dodged while both are
+            for &(i, j, weight) in &weighted_pairs {
+                let pair_var = vars.add(variable().binary());
+                if weight > 0.0 {
+                    constraints.push(pair_var.into_expression().leq(station_candidates[i]));
+                    constraints.push(pair_var.into_expression().leq(station_candidates[j]));
+                } else {
+                    constraints.push(pair_var.into_expression().geq(station_candidates[i] + station_candidates[j] - 1));
+                }
+                objective += weight * pair_var;
             }
         }
     }
 
-    let solution = vars
-        .maximise(objective)
-        .using(highs)
-        .with_all(all_constraints)
-        .solve()
-        .with_context(|| "Error when solving problem")?;
-    
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| "Error when grouping candidates. Check the avoid lists and repeated partners")?;
+
     let mut allocations = Vec::new();
-    for c in 0..num_circuits {
-        let circuit_id = circuits[c].id;
+    for (c, circuit) in circuits.iter().enumerate() {
         for (s, station) in stations.iter().enumerate() {
-            let assigned_candidates: Vec<Uuid> = (0..candidates.len())
-                .filter(|&i| solution.value(candidate_vars[c][i][s]) > 0.5)
-                .map(|i| candidates[i].id)
-                .collect();
-            if assigned_candidates.len() != 2 {
-                return Err(AppError::from(anyhow!("Each station must have exactly 2 candidates")));
+            let group: Vec<usize> = match &locked_stations[c][s] {
+                Some(group) => group.clone(), // keeps its position order
+                None => (0..candidates.len()).filter(|&i| solution.value(candidate_vars[c][s][i]) > 0.5).collect(),
+            };
+            if group.is_empty() {
+                continue;
+            }
+            if group.len() != group_size {
+                return Err(AppError::from(anyhow!("Each station must have either 0 or {} candidates", group_size)));
             }
-            let candidate_1 = assigned_candidates[0];
-            let candidate_2 = assigned_candidates[1];
-
             allocations.push(SlotAllocation {
-                circuit_id,
+                circuit_id: circuit.id,
                 station_id: station.id,
-                candidate_1,
-                candidate_2,
+                candidates: group.iter().map(|&i| candidates[i].id).collect(),
             });
         }
     }
-
     Ok(allocations)
 }
 
+/// Every way of choosing k of the items, in order
+fn combinations(items: &[usize], k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for (a, &item) in items.iter().enumerate() {
+        for mut rest in combinations(&items[(a + 1)..], k - 1) {
+            rest.insert(0, item);
+            result.push(rest);
+        }
+    }
+    result
+}
+
+/// Bonus for candidate pairing with partner, first choice outweighs second choice
+pub fn partner_bonus(candidate: &Candidate, partner: &Candidate) -> f64 {
+    if candidate.partner_pref.as_ref() == Some(&partner.shortcode) {
+        2.0
+    } else if candidate.partner_pref_2.as_ref() == Some(&partner.shortcode) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl PairRules<'_> {
+    /// Objective weight of 2 candidates in the same group: both partner bonuses, less a penalty if they were partners in an earlier session
+    fn weight(&self, candidate: &Candidate, partner: &Candidate) -> f64 {
+        let bonus = partner_bonus(candidate, partner) + partner_bonus(partner, candidate);
+        if self.past_pairs.contains(&(candidate.id, partner.id)) {
+            bonus - PARTNER_REPEAT_WEIGHT
+        } else {
+            bonus
+        }
+    }
+
+    /// Whether 2 candidates can be in the same group: neither avoids the other, and they are not a forbidden repeat
+    fn allowed(&self, candidate: &Candidate, other: &Candidate) -> bool {
+        !avoids(candidate, other) && !avoids(other, candidate)
+            && (!self.forbid_repeats || !self.past_pairs.contains(&(candidate.id, other.id)))
+    }
+}
+
+/// Whether candidate must not be paired with other
+pub fn avoids(candidate: &Candidate, other: &Candidate) -> bool {
+    candidate.avoid.contains(&other.shortcode)
+}
+
+/// Whether an examiner has every tag in the list
+pub fn has_tags(examiner: &Examiner, tags: &[String]) -> bool {
+    tags.iter().all(|tag| examiner.tags.contains(tag))
+}
+
+/// Whether a candidate is available for every run of a slot with the given AM / PM runs
+pub fn can_attend(candidate: &Candidate, slot_time: &Availability) -> bool {
+    (!slot_time.am || candidate.am == Some(true)) && (!slot_time.pm || candidate.pm == Some(true))
+}
+
+/// Whether an actor can play a station in a circuit: the station's actor gender, female-only circuits (like examiners)
+/// and the stations the actor plays all have to match
+pub fn can_play(actor: &Actor, station: &Station, circuit: &Circuit) -> bool {
+    station.actor_gender.as_ref().is_none_or(|gender| *gender == actor.gender)
+        && (!circuit.female_only || actor.gender == "female")
+        && (actor.stations.is_empty() || actor.stations.contains(&station.title.to_lowercase()))
+}
+
 pub fn allocate_by_time( // for examiners ONLY
-    circuits: &[Circuit],
-    stations: &[Station], // shared stations
+    layout: &Layout,
+    stations: &[Station], // shared stations, examined ones only
     examiners: &[Examiner],
+    locked_examiners: &[TimeAllocation], // examiners kept at their station for that run
+    candidate_allocations: &[SlotAllocation], // solved beforehand by allocate_by_slot
+    rules: &ExaminerRules,
+    examiner_slots: &[(Uuid, Uuid)], // (examiner id, slot id) from assign_slots, examiners only examine in their slots
 ) -> Result<Vec<TimeAllocation>, AppError> {
-    // some hardcoded algo for assigning examiners
-    Ok(Vec::new())
-}
\ No newline at end of file
+    let circuits = layout.circuits;
+    let mut vars = variables!();
+    let num_stations = stations.len();
+
+    // runs[r]: (run, whether it is an AM run), only for runs of the given circuits' slots
+    let runs = layout.runs();
+
+    // run_circuits[r]: indices of the circuits running during run r
+    let run_circuits: Vec<Vec<usize>> = runs
+        .iter()
+        .map(|(run, _)| {
+            (0..circuits.len())
+                .filter(|&c| circuits[c].slot_id == run.slot_id)
+                .collect()
+        })
+        .collect();
+
+    // examiner_vars[r][e][k][s]: examiner e assigned to station s in circuit run_circuits[r][k] during run r
+    let examiner_vars: Vec<Vec<Vec<Vec<Variable>>>> = (0..runs.len())
+        .map(|r| {
+            (0..examiners.len())
+                .map(|_| {
+                    (0..run_circuits[r].len())
+                        .map(|_| (0..num_stations).map(|_| vars.add(variable().binary())).collect())
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+
+    // position_vars[e][c][s]: examiner e examines station s in circuit c during at least 1 run
+    let position_vars: Vec<Vec<Vec<Variable>>> = (0..examiners.len())
+        .map(|_| {
+            (0..circuits.len())
+                .map(|_| (0..num_stations).map(|_| vars.add(variable().binary())).collect())
+                .collect()
+        })
+        .collect();
+
+    // Constraint: Each station must have exactly 1 examiner in every run.
+    let st_exam_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let val_vars = &examiner_vars;
+            (0..run_circuits[r].len()).flat_map(move |k| {
+                (0..num_stations).map(move |s| {
+                    let examiner_sum: Expression = (0..examiners.len())
+                        .map(|e| val_vars[r][e][k][s])
+                        .sum();
+                    examiner_sum.eq(1)
+                })
+            })
+        })
+        .collect();
+
+    // Constraint: Examiners can only be assigned to runs they can attend (AM / PM), in the slots they were assigned to
+    let availability_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let mut run_constraints = Vec::new();
+            let (run, is_am) = runs[r];
+            for (e, examiner) in examiners.iter().enumerate() {
+                let available = (if is_am { examiner.am } else { examiner.pm }) && examiner_slots.contains(&(examiner.id, run.slot_id));
+                if !available {
+                    let station_sum: Expression = (0..run_circuits[r].len())
+                        .flat_map(|k| (0..num_stations).map(move |s| (k, s)))
+                        .map(|(k, s)| examiner_vars[r][e][k][s])
+                        .sum();
+                    run_constraints.push(station_sum.eq(0));
+                }
+            }
+            run_constraints.into_iter()
+        })
+        .collect();
+
+    // Constraint: An examiner is at most at one station across all circuits of runs happening at the same time.
+    // Runs overlapping each other all overlap at the latest start, so checking every run's start covers all clashes
+    let double_booking_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let start = runs[r].0.scheduled_start;
+            let overlapping: Vec<usize> = (0..runs.len())
+                .filter(|&o| runs[o].0.scheduled_start <= start && start < runs[o].0.scheduled_end)
+                .collect();
+            let val_vars = &examiner_vars;
+            let run_circuits = &run_circuits;
+            (0..examiners.len()).map(move |e| {
+                let station_sum: Expression = overlapping
+                    .iter()
+                    .flat_map(|&o| {
+                        (0..run_circuits[o].len()).flat_map(move |k| (0..num_stations).map(move |s| val_vars[o][e][k][s]))
+                    })
+                    .sum();
+                station_sum.leq(1)
+            })
+        })
+        .collect();
+
+    // Constraint: Force non-female examiners to 0 for female-only circuits
+    let female_only_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let mut run_constraints = Vec::new();
+            for (k, &c) in run_circuits[r].iter().enumerate() {
+                if circuits[c].female_only {
+                    for (e, examiner) in examiners.iter().enumerate() {
+                        if !examiner.female {
+                            for &var in &examiner_vars[r][e][k] {
+                                run_constraints.push(var.into_expression().eq(0));
+                            }
+                        }
+                    }
+                }
+            }
+            run_constraints.into_iter()
+        })
+        .collect();
+
+    // Constraint: Examiners must have every required tag of the station
+    for station in stations.iter().filter(|station| !station.required_tags.is_empty()) {
+        if !examiners.iter().any(|examiner| has_tags(examiner, &station.required_tags)) {
+            return Err(AppError::from(anyhow!("No examiner has the tags required by station '{}': {}", station.title, station.required_tags.join(", "))));
+        }
+    }
+    let tag_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let mut run_constraints = Vec::new();
+            for (s, station) in stations.iter().enumerate() {
+                for (e, examiner) in examiners.iter().enumerate() {
+                    if !has_tags(examiner, &station.required_tags) {
+                        for station_vars in &examiner_vars[r][e] {
+                            run_constraints.push(station_vars[s].into_expression().eq(0));
+                        }
+                    }
+                }
+            }
+            run_constraints.into_iter()
+        })
+        .collect();
+
+    // Constraint: Examiners cannot be in a circuit with a candidate they are excluded from.
+    // Candidates rotate through every station of their circuit, so sharing a station means sharing the circuit
+    let exclusion_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let mut run_constraints = Vec::new();
+            for (k, &c) in run_circuits[r].iter().enumerate() {
+                let circuit_candidates: Vec<Uuid> = candidate_allocations
+                    .iter()
+                    .filter(|a| a.circuit_id == circuits[c].id)
+                    .flat_map(|a| a.candidates.iter().copied())
+                    .collect();
+                for (e, examiner) in examiners.iter().enumerate() {
+                    let excluded = rules.exclusions
+                        .iter()
+                        .any(|(examiner_id, candidate_id)| *examiner_id == examiner.id && circuit_candidates.contains(candidate_id));
+                    if excluded {
+                        let station_sum: Expression = (0..num_stations).map(|s| examiner_vars[r][e][k][s]).sum();
+                        run_constraints.push(station_sum.eq(0));
+                    }
+                }
+            }
+            run_constraints.into_iter()
+        })
+        .collect();
+
+    // Constraint: Link examiner assignments to the positions they use
+    let position_constraints: Vec<Constraint> = (0..runs.len())
+        .flat_map(|r| {
+            let mut run_constraints = Vec::new();
+            for (k, &c) in run_circuits[r].iter().enumerate() {
+                for s in 0..num_stations {
+                    for e in 0..examiners.len() {
+                        run_constraints.push(examiner_vars[r][e][k][s].into_expression().leq(position_vars[e][c][s]));
+                    }
+                }
+            }
+            run_constraints.into_iter()
+        })
+        .collect();
+
+    // Constraint: When continuity is required, examiners keep a single station in a single circuit for every run of a slot
+    let mut continuity_constraints: Vec<Constraint> = Vec::new();
+    if rules.require_continuity {
+        let mut slot_ids: Vec<Uuid> = circuits.iter().map(|circuit| circuit.slot_id).collect();
+        slot_ids.sort();
+        slot_ids.dedup();
+        for slot_id in slot_ids {
+            for examiner_positions in &position_vars {
+                let position_sum: Expression = (0..circuits.len())
+                    .filter(|&c| circuits[c].slot_id == slot_id)
+                    .flat_map(|c| examiner_positions[c].iter().copied())
+                    .sum();
+                continuity_constraints.push(position_sum.leq(1));
+            }
+        }
+    }
+
+    // Constraint: Locked examiners are fixed to their station
+    let mut locked_constraints: Vec<Constraint> = Vec::new();
+    for locked in locked_examiners {
+        let r = runs.iter().position(|(run, _)| run.id == locked.run_id)
+            .ok_or_else(|| anyhow!("Locked examiner is in a run that no longer exists"))?;
+        let k = run_circuits[r].iter().position(|&c| circuits[c].id == locked.circuit_id)
+            .ok_or_else(|| anyhow!("Locked examiner is in a circuit that no longer exists"))?;
+        let s = stations.iter().position(|station| station.id == locked.station_id)
+            .ok_or_else(|| anyhow!("Locked examiner is at a station that no longer exists"))?;
+        let e = examiners.iter().position(|examiner| examiner.id == locked.examiner)
+            .ok_or_else(|| anyhow!("Locked examiner can no longer be allocated"))?;
+        locked_constraints.push(examiner_vars[r][e][k][s].into_expression().eq(1));
+    }
+
+    let all_constraints: Vec<Constraint> = vec![
+        st_exam_constraints,
+        availability_constraints,
+        double_booking_constraints,
+        female_only_constraints,
+        tag_constraints,
+        exclusion_constraints,
+        position_constraints,
+        continuity_constraints,
+        locked_constraints,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    // Objective: maximise the preferred tags matched between examiners and their stations,
+    // whilst strongly penalising every extra station an examiner has to learn, and examining the same candidates again
+    let mut objective = Expression::from(0.0);
+    for &var in position_vars.iter().flatten().flatten() {
+        objective -= CONTINUITY_WEIGHT * var;
+    }
+    // every candidate an examiner has examined before counts against each station they take in that candidate's circuit
+    let past_examiners: HashSet<&(Uuid, Uuid)> = rules.past_examiners.iter().collect();
+    for (e, examiner) in examiners.iter().enumerate() {
+        for (c, circuit) in circuits.iter().enumerate() {
+            let repeats = candidate_allocations
+                .iter()
+                .filter(|a| a.circuit_id == circuit.id)
+                .flat_map(|a| a.candidates.iter().copied())
+                .filter(|candidate_id| past_examiners.contains(&(examiner.id, *candidate_id)))
+                .count();
+            if repeats > 0 {
+                for &var in &position_vars[e][c] {
+                    objective -= EXAMINER_REPEAT_WEIGHT * (repeats as f64) * var;
+                }
+            }
+        }
+    }
+    for run_vars in &examiner_vars {
+        for (examiner, circuit_vars) in examiners.iter().zip(run_vars) {
+            for station_vars in circuit_vars {
+                for (station, &var) in stations.iter().zip(station_vars) {
+                    let matched = station.preferred_tags.iter().filter(|tag| examiner.tags.contains(tag)).count();
+                    if matched > 0 {
+                        objective += (matched as f64) * var;
+                    }
+                }
+            }
+        }
+    }
+
+    let solution = Backend::maximise(vars, objective, all_constraints)
+        .with_context(|| "Error when solving examiner allocation. Check there are enough AM / PM and female examiners")?;
+
+    let mut allocations = Vec::new();
+    for (r, (run, _)) in runs.iter().enumerate() {
+        for (k, &c) in run_circuits[r].iter().enumerate() {
+            for (s, station) in stations.iter().enumerate() {
+                let assigned_examiners: Vec<Uuid> = (0..examiners.len())
+                    .filter(|&e| solution.value(examiner_vars[r][e][k][s]) > 0.5)
+                    .map(|e| examiners[e].id)
+                    .collect();
+                if assigned_examiners.len() != 1 {
+                    return Err(AppError::from(anyhow!("Each station must have exactly 1 examiner")));
+                }
+
+                allocations.push(TimeAllocation {
+                    run_id: run.id,
+                    circuit_id: circuits[c].id,
+                    station_id: station.id,
+                    examiner: assigned_examiners[0],
+                });
+            }
+        }
+    }
+
+    Ok(allocations)
+}
+
+/// Refills the stations of absent candidates whilst changing as little of the current allocation as possible.
+/// Groups whose candidates are all present stay where they are. Candidates left in a broken group either stay and get
+/// reserves or placeholders as new partners, or move to another station's gap. Placeholders are only used when needed
+pub fn reallocate_by_slot( // for candidates ONLY
+    layout: &Layout,
+    stations: &[Station], // shared stations
+    candidates: &[Candidate], // present candidates, reserves and placeholders. Anyone missing is absent
+    current: &[SlotAllocation], // current groups
+    locked_groups: &[SlotAllocation], // present candidates of locked groups keep their station
+    blocked: &[(Uuid, Uuid)], // (candidate id, circuit id) the candidate cannot move to, e.g. excluded from an examiner there
+) -> Result<Vec<SlotAllocation>, AppError> {
+    let Layout { circuits, slot_times, group_size, .. } = *layout;
+    let present: HashSet<Uuid> = candidates.iter().map(|candidate| candidate.id).collect();
+    let mut kept = Vec::new();
+    let mut origin: HashMap<Uuid, (Uuid, Uuid)> = HashMap::new(); // candidate id -> (circuit id, station id) of present candidates in broken groups
+    let mut occupied: HashSet<(Uuid, Uuid)> = HashSet::new();
+    for group in current {
+        if group.candidates.len() == group_size && group.candidates.iter().all(|id| present.contains(id)) {
+            kept.push(group.clone());
+            occupied.insert((group.circuit_id, group.station_id));
+        } else {
+            for &candidate_id in group.candidates.iter().filter(|id| present.contains(id)) {
+                origin.insert(candidate_id, (group.circuit_id, group.station_id));
+            }
+        }
+    }
+    let allocated: HashSet<Uuid> = current.iter().flat_map(|group| group.candidates.iter().copied()).collect();
+
+    // gaps: every station without a full group, movable: candidates in broken groups, reserves and placeholders
+    let gaps: Vec<(&Circuit, &Station)> = circuits
+        .iter()
+        .flat_map(|circuit| stations.iter().map(move |station| (circuit, station)))
+        .filter(|(circuit, station)| !occupied.contains(&(circuit.id, station.id)))
+        .collect();
+    let movable: Vec<&Candidate> = candidates
+        .iter()
+        .filter(|candidate| origin.contains_key(&candidate.id) || !allocated.contains(&candidate.id))
+        .collect();
+    if movable.is_empty() {
+        return Ok(kept);
+    }
+
+    let mut vars = variables!();
+    // gap_vars[m][g]: movable candidate m goes to gap g, None when they cannot
+    let gap_vars: Vec<Vec<Option<Variable>>> = movable
+        .iter()
+        .map(|candidate| {
+            gaps.iter()
+                .map(|(circuit, _)| {
+                    let available = slot_times.get(&circuit.slot_id).is_some_and(|time| can_attend(candidate, time));
+                    let female_ok = !circuit.female_only || candidate.female_only;
+                    let allowed = available && female_ok && !blocked.contains(&(candidate.id, circuit.id));
+                    allowed.then(|| vars.add(variable().binary()))
+                })
+                .collect()
+        })
+        .collect();
+    // filled_vars[g]: gap g gets a group
+    let filled_vars: Vec<Variable> = gaps.iter().map(|_| vars.add(variable().binary())).collect();
+
+    let mut constraints: Vec<Constraint> = Vec::new();
+
+    // Constraint: A gap is either empty or has a full group
+    for g in 0..gaps.len() {
+        let gap_sum: Expression = (0..movable.len()).filter_map(|m| gap_vars[m][g]).sum();
+        constraints.push(gap_sum.eq(group_size as f64 * filled_vars[g]));
+    }
+
+    // Constraint: Candidates already allocated must stay allocated, reserves and placeholders are optional
+    for (m, candidate) in movable.iter().enumerate() {
+        let candidate_sum: Expression = gap_vars[m].iter().flatten().copied().sum();
+        if origin.contains_key(&candidate.id) {
+            constraints.push(candidate_sum.eq(1));
+        } else {
+            constraints.push(candidate_sum.leq(1));
+        }
+    }
+
+    // Constraint: Candidates in a locked group keep their station
+    for (m, candidate) in movable.iter().enumerate() {
+        let locked = locked_groups.iter().any(|group| group.candidates.contains(&candidate.id));
+        if let (true, Some(&(circuit_id, station_id))) = (locked, origin.get(&candidate.id)) {
+            let g = gaps.iter().position(|(circuit, station)| circuit.id == circuit_id && station.id == station_id)
+                .ok_or_else(|| anyhow!("Locked group is at a station that no longer exists"))?;
+            let var = gap_vars[m][g]
+                .ok_or_else(|| anyhow!("{} {} is in a locked group but can no longer stay at their station", candidate.first_name, candidate.last_name))?;
+            constraints.push(var.into_expression().eq(1));
+        }
+    }
+
+    // Constraint: Candidates who avoid each other cannot share a gap
+    for m in 0..movable.len() {
+        for n in (m + 1)..movable.len() {
+            if avoids(movable[m], movable[n]) || avoids(movable[n], movable[m]) {
+                for (&var_m, &var_n) in gap_vars[m].iter().zip(&gap_vars[n]) {
+                    if let (Some(var_m), Some(var_n)) = (var_m, var_n) {
+                        constraints.push((var_m + var_n).leq(1));
+                    }
+                }
+            }
+        }
+    }
+
+    // Objective: keep candidates at their station, then place reserves, and use as few placeholders as possible
+    let mut objective = Expression::from(0.0);
+    for (m, candidate) in movable.iter().enumerate() {
+        for (g, (circuit, station)) in gaps.iter().enumerate() {
+            let Some(var) = gap_vars[m][g] else { continue };
+            if origin.get(&candidate.id) == Some(&(circuit.id, station.id)) {
+                objective += var;
+            }
+            if !origin.contains_key(&candidate.id) {
+                let weight = if candidate.placeholder { -REALLOCATE_FILL_WEIGHT } else { RESERVE_WEIGHT };
+                objective += weight * var;
+            }
+        }
+    }
+
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| "Error when reallocating candidates. Check there are enough places for the candidates left in broken groups")?;
+
+    for (g, (circuit, station)) in gaps.iter().enumerate() {
+        let mut group: Vec<Uuid> = (0..movable.len())
+            .filter(|&m| gap_vars[m][g].is_some_and(|var| solution.value(var) > 0.5))
+            .map(|m| movable[m].id)
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+        if group.len() != group_size {
+            return Err(AppError::from(anyhow!("Each station must have exactly {} candidates", group_size)));
+        }
+        // whoever stayed keeps their place in the group, newcomers take the places after them
+        let original = current.iter().find(|a| a.circuit_id == circuit.id && a.station_id == station.id);
+        let position = |id: &Uuid| original.and_then(|a| a.candidates.iter().position(|c| c == id)).unwrap_or(usize::MAX);
+        group.sort_by_key(position);
+        kept.push(SlotAllocation { circuit_id: circuit.id, station_id: station.id, candidates: group });
+    }
+    Ok(kept)
+}
+
+/// Refills the stations of absent examiners with present examiners who are free in that run, or placeholders.
+/// Examiners who are present keep their stations, unless a candidate who moved into their circuit is excluded from them
+pub fn reallocate_by_time( // for examiners ONLY
+    layout: &Layout,
+    stations: &[Station], // shared stations, examined ones only
+    examiners: &[Examiner], // present examiners, reserves and placeholders. Anyone missing is absent
+    current: &[TimeAllocation],
+    candidate_allocations: &[SlotAllocation], // solved beforehand by reallocate_by_slot
+    exclusions: &[(Uuid, Uuid)], // (examiner id, candidate id) conflicts of interest
+) -> Result<Vec<TimeAllocation>, AppError> {
+    let circuits = layout.circuits;
+    let runs = layout.runs();
+    let excluded = |examiner_id: Uuid, circuit_id: Uuid| {
+        candidate_allocations
+            .iter()
+            .filter(|a| a.circuit_id == circuit_id)
+            .flat_map(|a| a.candidates.iter().copied())
+            .any(|candidate_id| exclusions.contains(&(examiner_id, candidate_id)))
+    };
+
+    let mut kept: Vec<TimeAllocation> = Vec::new();
+    for allocation in current {
+        if examiners.iter().any(|e| e.id == allocation.examiner) && !excluded(allocation.examiner, allocation.circuit_id) {
+            kept.push(TimeAllocation { run_id: allocation.run_id, circuit_id: allocation.circuit_id, station_id: allocation.station_id, examiner: allocation.examiner });
+        }
+    }
+
+    // gaps: (run, circuit, station) without an examiner
+    let mut gaps: Vec<(usize, &Circuit, &Station)> = Vec::new();
+    for (r, (run, _)) in runs.iter().enumerate() {
+        for circuit in circuits.iter().filter(|circuit| circuit.slot_id == run.slot_id) {
+            for station in stations {
+                if !kept.iter().any(|a| a.run_id == run.id && a.circuit_id == circuit.id && a.station_id == station.id) {
+                    gaps.push((r, circuit, station));
+                }
+            }
+        }
+    }
+    if gaps.is_empty() {
+        return Ok(kept);
+    }
+
+    let overlaps = |a: &Run, b: &Run| a.scheduled_start < b.scheduled_end && b.scheduled_start < a.scheduled_end;
+    let mut vars = variables!();
+    // gap_vars[e][g]: examiner e takes gap g, None when they cannot
+    let gap_vars: Vec<Vec<Option<Variable>>> = examiners
+        .iter()
+        .map(|examiner| {
+            gaps.iter()
+                .map(|&(r, circuit, station)| {
+                    let (run, is_am) = runs[r];
+                    let available = if is_am { examiner.am } else { examiner.pm };
+                    let busy = kept.iter().any(|a| {
+                        a.examiner == examiner.id && runs.iter().any(|(other, _)| other.id == a.run_id && overlaps(run, other))
+                    });
+                    let allowed = available
+                        && !busy
+                        && (!circuit.female_only || examiner.female)
+                        && has_tags(examiner, &station.required_tags)
+                        && !excluded(examiner.id, circuit.id);
+                    allowed.then(|| vars.add(variable().binary()))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut constraints: Vec<Constraint> = Vec::new();
+
+    // Constraint: Every gap gets exactly 1 examiner
+    for (g, (_, circuit, station)) in gaps.iter().enumerate() {
+        let examiner_vars: Vec<Variable> = (0..examiners.len()).filter_map(|e| gap_vars[e][g]).collect();
+        if examiner_vars.is_empty() {
+            return Err(AppError::from(anyhow!("No examiner can take station '{}' in circuit {}", station.title, circuit.key)));
+        }
+        let examiner_sum: Expression = examiner_vars.into_iter().sum();
+        constraints.push(examiner_sum.eq(1));
+    }
+
+    // Constraint: An examiner takes at most 1 gap in runs happening at the same time
+    for examiner_vars in &gap_vars {
+        for (run, _) in &runs {
+            let gap_sum: Expression = gaps
+                .iter()
+                .zip(examiner_vars)
+                .filter(|((o, _, _), _)| runs[*o].0.scheduled_start <= run.scheduled_start && run.scheduled_start < runs[*o].0.scheduled_end)
+                .filter_map(|(_, &var)| var)
+                .sum();
+            constraints.push(gap_sum.leq(1));
+        }
+    }
+
+    // Objective: use as few placeholders as possible, keep examiners at a station they already have in another run,
+    // then match the preferred tags
+    let mut objective = Expression::from(0.0);
+    for (e, examiner) in examiners.iter().enumerate() {
+        for (g, (_, circuit, station)) in gaps.iter().enumerate() {
+            let Some(var) = gap_vars[e][g] else { continue };
+            if examiner.placeholder {
+                objective -= REALLOCATE_FILL_WEIGHT * var;
+            }
+            if kept.iter().any(|a| a.examiner == examiner.id && a.circuit_id == circuit.id && a.station_id == station.id) {
+                objective += var;
+            }
+            let matched = station.preferred_tags.iter().filter(|tag| examiner.tags.contains(tag)).count();
+            objective += 0.1 * (matched as f64) * var;
+        }
+    }
+
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| "Error when reallocating examiners. Check there are enough AM / PM and female examiners")?;
+
+    for (g, &(r, circuit, station)) in gaps.iter().enumerate() {
+        let examiner = (0..examiners.len())
+            .find(|&e| gap_vars[e][g].is_some_and(|var| solution.value(var) > 0.5))
+            .ok_or_else(|| anyhow!("Each station must have exactly 1 examiner"))?;
+        kept.push(TimeAllocation { run_id: runs[r].0.id, circuit_id: circuit.id, station_id: station.id, examiner: examiners[examiner].id });
+    }
+    Ok(kept)
+}
+
+/// Puts an actor at each station that needs one, for every circuit and run. Actors only play stations that suit them in
+/// runs they are available for, and are never at 2 stations in runs happening at the same time. Actors in `current` who are
+/// still listed keep their stations. Stations nobody can play are left without an actor, so the organiser can find someone
+pub fn allocate_actors( // for actors ONLY
+    layout: &Layout,
+    stations: &[Station], // shared stations, ones needing an actor only
+    actors: &[Actor], // anyone missing is absent
+    current: &[ActorAllocation], // empty when allocating from scratch
+) -> Result<Vec<ActorAllocation>, AppError> {
+    let circuits = layout.circuits;
+    let runs = layout.runs();
+
+    let mut kept: Vec<ActorAllocation> = Vec::new();
+    for allocation in current {
+        let actor = actors.iter().find(|a| a.id == allocation.actor);
+        let station = stations.iter().find(|s| s.id == allocation.station_id);
+        let circuit = circuits.iter().find(|c| c.id == allocation.circuit_id);
+        if let (Some(actor), Some(station), Some(circuit)) = (actor, station, circuit) {
+            if can_play(actor, station, circuit) {
+                kept.push(allocation.clone());
+            }
+        }
+    }
+
+    // gaps: (run, circuit, station) without an actor
+    let mut gaps: Vec<(usize, &Circuit, &Station)> = Vec::new();
+    for (r, (run, _)) in runs.iter().enumerate() {
+        for circuit in circuits.iter().filter(|circuit| circuit.slot_id == run.slot_id) {
+            for station in stations {
+                if !kept.iter().any(|a| a.run_id == run.id && a.circuit_id == circuit.id && a.station_id == station.id) {
+                    gaps.push((r, circuit, station));
+                }
+            }
+        }
+    }
+    if gaps.is_empty() || actors.is_empty() {
+        return Ok(kept);
+    }
+
+    let overlaps = |a: &Run, b: &Run| a.scheduled_start < b.scheduled_end && b.scheduled_start < a.scheduled_end;
+    let mut vars = variables!();
+    // gap_vars[a][g]: actor a plays gap g, None when they cannot
+    let gap_vars: Vec<Vec<Option<Variable>>> = actors
+        .iter()
+        .map(|actor| {
+            gaps.iter()
+                .map(|&(r, circuit, station)| {
+                    let (run, is_am) = runs[r];
+                    let available = if is_am { actor.am } else { actor.pm };
+                    let busy = kept.iter().any(|a| {
+                        a.actor == actor.id && runs.iter().any(|(other, _)| other.id == a.run_id && overlaps(run, other))
+                    });
+                    (available && !busy && can_play(actor, station, circuit)).then(|| vars.add(variable().binary()))
+                })
+                .collect()
+        })
+        .collect();
+    // position_vars[(a, circuit, station)]: actor a plays the station of the circuit in at least 1 run
+    let mut position_vars: HashMap<(usize, Uuid, Uuid), Variable> = HashMap::new();
+    for (a, actor_vars) in gap_vars.iter().enumerate() {
+        for ((_, circuit, station), var) in gaps.iter().zip(actor_vars) {
+            if var.is_some() {
+                position_vars.entry((a, circuit.id, station.id)).or_insert_with(|| vars.add(variable().binary()));
+            }
+        }
+    }
+
+    let mut constraints: Vec<Constraint> = Vec::new();
+
+    // Constraint: Every gap gets at most 1 actor
+    for g in 0..gaps.len() {
+        let actor_sum: Expression = gap_vars.iter().filter_map(|actor_vars| actor_vars[g]).sum();
+        constraints.push(actor_sum.leq(1));
+    }
+
+    // Constraint: An actor plays at most 1 gap in runs happening at the same time
+    for actor_vars in &gap_vars {
+        for (run, _) in &runs {
+            let gap_sum: Expression = gaps
+                .iter()
+                .zip(actor_vars)
+                .filter(|((o, _, _), _)| runs[*o].0.scheduled_start <= run.scheduled_start && run.scheduled_start < runs[*o].0.scheduled_end)
+                .filter_map(|(_, &var)| var)
+                .sum();
+            constraints.push(gap_sum.leq(1));
+        }
+    }
+
+    // Constraint: Playing a gap uses the actor's position
+    for a in 0..actors.len() {
+        for (g, (_, circuit, station)) in gaps.iter().enumerate() {
+            if let Some(var) = gap_vars[a][g] {
+                constraints.push((var - position_vars[&(a, circuit.id, station.id)]).leq(0));
+            }
+        }
+    }
+
+    // Objective: cover as many stations as possible, then keep actors at the same station across runs.
+    // A station the actor already plays in another run is free
+    let mut objective = Expression::from(0.0);
+    for var in gap_vars.iter().flatten().flatten() {
+        objective += FILL_WEIGHT * *var;
+    }
+    for (&(a, circuit_id, station_id), &var) in &position_vars {
+        if !kept.iter().any(|k| k.actor == actors[a].id && k.circuit_id == circuit_id && k.station_id == station_id) {
+            objective -= CONTINUITY_WEIGHT * var;
+        }
+    }
+
+    let solution = Backend::maximise(vars, objective, constraints)
+        .with_context(|| "Error when allocating actors")?;
+
+    for (g, &(r, circuit, station)) in gaps.iter().enumerate() {
+        if let Some(a) = (0..actors.len()).find(|&a| gap_vars[a][g].is_some_and(|var| solution.value(var) > 0.5)) {
+            kept.push(ActorAllocation { run_id: runs[r].0.id, circuit_id: circuit.id, station_id: station.id, actor: actors[a].id });
+        }
+    }
+    Ok(kept)
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    UngroupedCandidates, // candidates cannot all be grouped
+    CandidateCapacity, // more candidates than places in the circuits they can attend
+    ExaminerShortage, // fewer examiners than stations to examine in a run
+    FemaleExaminerShortage, // fewer female examiners than stations in female-only circuits in a run
+    TaggedExaminerShortage, // fewer examiners with a station's required tags than circuits running it
+}
+
+#[derive(Debug, Serialize)]
+pub struct Infeasibility { // a condition the solver cannot meet, with the numbers involved
+    pub condition: Condition,
+    pub slot_id: Option<Uuid>,
+    pub run_id: Option<Uuid>,
+    pub station_id: Option<Uuid>,
+    pub needed: usize,
+    pub available: usize,
+    pub message: String,
+}
+
+/// Checks capacities per circuit, slot, AM / PM and female-only against the people before solving.
+/// Only conditions that make the problem infeasible are reported, an empty list does not guarantee a solution
+pub fn check_feasibility(
+    layout: &Layout,
+    stations: &[Station], // shared stations
+    examined_stations: &[Station],
+    candidates: &[Candidate], // only candidates who can attend a slot
+    examiners: &[Examiner],
+) -> Vec<Infeasibility> {
+    let Layout { circuits, slot_times, group_size, .. } = *layout;
+    let mut infeasibilities = Vec::new();
+    // e.g. "Circuit A needs", "Female-only circuits B, D need"
+    let circuits_need = |indices: &[usize], female_only: bool| {
+        let keys = indices.iter().map(|&c| circuits[c].key.as_str()).collect::<Vec<_>>().join(", ");
+        let noun = match (female_only, indices.len() == 1) {
+            (true, true) => "Female-only circuit",
+            (true, false) => "Female-only circuits",
+            (false, true) => "Circuit",
+            (false, false) => "Circuits",
+        };
+        format!("{} {} {}", noun, keys, if indices.len() == 1 { "needs" } else { "need" })
+    };
+
+    // CANDIDATES
+    if !candidates.len().is_multiple_of(group_size) {
+        let missing = group_size - candidates.len() % group_size;
+        infeasibilities.push(Infeasibility {
+            condition: Condition::UngroupedCandidates,
+            slot_id: None,
+            run_id: None,
+            station_id: None,
+            needed: candidates.len() + missing,
+            available: candidates.len(),
+            message: format!("{} candidates cannot all be put in groups of {}, {} more {} needed", candidates.len(), group_size, missing, if missing == 1 { "candidate is" } else { "candidates are" }),
+        });
+    }
+
+    // attendable[i]: circuits candidate i can be in, going by slot availability only
+    let attendable: Vec<Vec<usize>> = candidates
+        .iter()
+        .map(|candidate| {
+            (0..circuits.len())
+                .filter(|&c| slot_times.get(&circuits[c].slot_id).is_some_and(|time| can_attend(candidate, time)))
+                .collect()
+        })
+        .collect();
+    let mut circuit_sets: Vec<&Vec<usize>> = attendable.iter().collect();
+    circuit_sets.sort();
+    circuit_sets.dedup();
+
+    // Every group of candidates limited to the same circuits must fit in them, non female-only candidates also need to fit outside female-only circuits
+    for circuit_set in circuit_sets.into_iter().filter(|set| !set.is_empty()) {
+        let within = |i: usize| attendable[i].iter().all(|c| circuit_set.contains(c));
+        let mixed_circuits: Vec<usize> = circuit_set.iter().copied().filter(|&c| !circuits[c].female_only).collect();
+        let slot_id = circuits[circuit_set[0]].slot_id;
+        let slot_id = circuit_set.iter().all(|&c| circuits[c].slot_id == slot_id).then_some(slot_id);
+
+        let needed = (0..candidates.len()).filter(|&i| within(i)).count();
+        let available = circuit_set.len() * stations.len() * group_size;
+        if needed > available {
+            infeasibilities.push(Infeasibility {
+                condition: Condition::CandidateCapacity,
+                slot_id,
+                run_id: None,
+                station_id: None,
+                needed,
+                available,
+                message: format!("{} space for {} candidates, {} places", circuits_need(circuit_set, false), needed, available),
+            });
+        }
+
+        let needed = (0..candidates.len()).filter(|&i| within(i) && !candidates[i].female_only).count();
+        let available = mixed_circuits.len() * stations.len() * group_size;
+        if needed > available {
+            infeasibilities.push(Infeasibility {
+                condition: Condition::CandidateCapacity,
+                slot_id,
+                run_id: None,
+                station_id: None,
+                needed,
+                available,
+                message: if mixed_circuits.is_empty() {
+                    format!("{} space for {} candidates who are not female-only, 0 places outside female-only circuits", circuits_need(circuit_set, true), needed)
+                } else {
+                    format!("{} space for {} candidates who are not female-only, {} places", circuits_need(&mixed_circuits, false), needed, available)
+                },
+            });
+        }
+    }
+
+    // EXAMINERS
+    let runs = layout.runs();
+
+    // Runs overlapping each other all overlap at the latest start, same as the solver's double booking check
+    let mut overlap_groups: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (r, (run, _)) in runs.iter().enumerate() {
+        let overlapping: Vec<usize> = (0..runs.len())
+            .filter(|&o| runs[o].0.scheduled_start <= run.scheduled_start && run.scheduled_start < runs[o].0.scheduled_end)
+            .collect();
+        if !overlap_groups.iter().any(|(_, group)| *group == overlapping) {
+            overlap_groups.push((r, overlapping));
+        }
+    }
+
+    for (r, group) in overlap_groups {
+        let run = runs[r].0;
+        let start = run.scheduled_start;
+        let run_name = format!("the {:02}:{:02} run", start.hour(), start.minute());
+        let group_circuits: Vec<usize> = group
+            .iter()
+            .flat_map(|&o| {
+                let slot_id = runs[o].0.slot_id;
+                (0..circuits.len()).filter(move |&c| circuits[c].slot_id == slot_id)
+            })
+            .collect();
+        let female_circuits: Vec<usize> = group_circuits.iter().copied().filter(|&c| circuits[c].female_only).collect();
+        let available_for = |examiner: &Examiner| group.iter().any(|&o| if runs[o].1 { examiner.am } else { examiner.pm });
+
+        let needed = group_circuits.len() * examined_stations.len();
+        let available = examiners.iter().filter(|e| available_for(e)).count();
+        if needed > available {
+            infeasibilities.push(Infeasibility {
+                condition: Condition::ExaminerShortage,
+                slot_id: Some(run.slot_id),
+                run_id: Some(run.id),
+                station_id: None,
+                needed,
+                available,
+                message: format!("{} {} examiners in {}, {} available", circuits_need(&group_circuits, false), needed, run_name, available),
+            });
+        }
+
+        let needed = female_circuits.len() * examined_stations.len();
+        let available = examiners.iter().filter(|e| e.female && available_for(e)).count();
+        if needed > available {
+            infeasibilities.push(Infeasibility {
+                condition: Condition::FemaleExaminerShortage,
+                slot_id: Some(run.slot_id),
+                run_id: Some(run.id),
+                station_id: None,
+                needed,
+                available,
+                message: format!("{} {} female examiners in {}, {} eligible", circuits_need(&female_circuits, true), needed, run_name, available),
+            });
+        }
+
+        for station in examined_stations.iter().filter(|s| !s.required_tags.is_empty()) {
+            let needed = group_circuits.len();
+            let available = examiners.iter().filter(|e| has_tags(e, &station.required_tags) && available_for(e)).count();
+            if needed > available {
+                infeasibilities.push(Infeasibility {
+                    condition: Condition::TaggedExaminerShortage,
+                    slot_id: Some(run.slot_id),
+                    run_id: Some(run.id),
+                    station_id: Some(station.id),
+                    needed,
+                    available,
+                    message: format!("Station '{}' needs {} examiners tagged {} in {}, {} eligible", station.title, needed, station.required_tags.join(", "), run_name, available),
+                });
+            }
+        }
+    }
+
+    infeasibilities
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::types::PgInterval;
+    use time::{Duration, OffsetDateTime};
+
+    use super::*;
+
+    fn circuit(slot_id: Uuid, key: &str, female_only: bool) -> Circuit {
+        Circuit {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            slot_id,
+            key: key.to_string(),
+            female_only,
+            current_rotation: None,
+            status: String::from("new"),
+            feedback: true,
+            intermission: false,
+            timer_start: None,
+            timer_end: None,
+        }
+    }
+
+    fn station(index: usize) -> Station {
+        Station {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            title: format!("Station {}", index + 1),
+            index: index as i16,
+            duration: PgInterval { months: 0, days: 0, microseconds: 8 * 60 * 1_000_000 },
+            required_tags: Vec::new(),
+            preferred_tags: Vec::new(),
+            kind: String::from("examined"),
+            needs_actor: false,
+            actor_gender: None,
+        }
+    }
+
+    fn run(slot_id: Uuid, start_hour: i64) -> Run { // 1 hour long, on 1 Jan 1970
+        let start = OffsetDateTime::UNIX_EPOCH + Duration::hours(start_hour);
+        Run {
+            id: Uuid::new_v4(),
+            slot_id,
+            flip_allocation: false,
+            scheduled_start: start,
+            scheduled_end: start + Duration::hours(1),
+            timer_start: None,
+            timer_end: None,
+        }
+    }
+
+    fn examiner(shortcode: &str, female: bool, am: bool, pm: bool) -> Examiner {
+        Examiner {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            first_name: shortcode.to_string(),
+            last_name: String::from("Test"),
+            shortcode: shortcode.to_string(),
+            female,
+            am,
+            pm,
+            checked_in: false,
+            tags: Vec::new(),
+            placeholder: false,
+        }
+    }
+
+    fn candidate(shortcode: &str, female_only: bool, am: bool, pm: bool) -> Candidate {
+        Candidate {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            first_name: shortcode.to_string(),
+            last_name: String::from("Test"),
+            shortcode: shortcode.to_string(),
+            female_only,
+            partner_pref: None,
+            checked_in: false,
+            am: Some(am),
+            pm: Some(pm),
+            partner_pref_2: None,
+            avoid: Vec::new(),
+            placeholder: false,
+        }
+    }
+
+    fn actor(shortcode: &str, gender: &str, am: bool, pm: bool, stations: &[&str]) -> Actor {
+        Actor {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            first_name: shortcode.to_string(),
+            last_name: String::from("Test"),
+            shortcode: shortcode.to_string(),
+            gender: gender.to_string(),
+            am,
+            pm,
+            checked_in: false,
+            stations: stations.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn groups_of(allocations: &[SlotAllocation], candidates: &[Candidate]) -> Vec<Vec<String>> { // shortcodes, sorted
+        let shortcode = |id: &Uuid| candidates.iter().find(|c| c.id == *id).unwrap().shortcode.clone();
+        let mut groups: Vec<Vec<String>> = allocations
+            .iter()
+            .map(|a| {
+                let mut group: Vec<String> = a.candidates.iter().map(shortcode).collect();
+                group.sort();
+                group
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    #[test]
+    fn test_allocate_by_slot_hard_constraints() {
+        let am_slot = Uuid::new_v4();
+        let pm_slot = Uuid::new_v4();
+        let circuits = vec![circuit(am_slot, "A", true), circuit(pm_slot, "B", false)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let mut candidates = vec![
+            candidate("f_am_1", true, true, false),
+            candidate("f_am_2", true, true, false),
+            candidate("f_am_3", true, true, false),
+            candidate("f_am_4", true, true, false),
+            candidate("f_pm_1", true, false, true),
+            candidate("f_pm_2", true, false, true),
+            candidate("m_pm_1", false, false, true),
+            candidate("m_pm_2", false, false, true),
+        ];
+        candidates[0].partner_pref = Some(String::from("f_am_3"));
+        candidates[2].partner_pref = Some(String::from("f_am_1"));
+        let slot_times = HashMap::from([
+            (am_slot, Availability { am: true, pm: false }),
+            (pm_slot, Availability { am: false, pm: true }),
+        ]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &[], pm_runs: &[], group_size: 2 };
+
+        let allocations = allocate_by_slot(&layout, &stations, &candidates, &[], &[], false)
+            .expect("allocation is feasible");
+
+        for candidate in &candidates {
+            let placed: Vec<&SlotAllocation> = allocations.iter().filter(|a| a.candidates.contains(&candidate.id)).collect();
+            // every candidate is at exactly 1 station
+            assert_eq!(placed.len(), 1, "{} is at {} stations", candidate.shortcode, placed.len());
+            let circuit = circuits.iter().find(|c| c.id == placed[0].circuit_id).unwrap();
+            // AM / PM availability
+            assert!(can_attend(candidate, &slot_times[&circuit.slot_id]), "{} allocated to a slot they cannot attend", candidate.shortcode);
+            // female-only circuits only get female-only candidates
+            assert!(!circuit.female_only || candidate.female_only, "{} allocated to female-only circuit {}", candidate.shortcode, circuit.key);
+        }
+        for allocation in &allocations {
+            // group sizes
+            assert_eq!(allocation.candidates.len(), 2);
+            // at most 1 group per station
+            assert_eq!(allocations.iter().filter(|a| a.circuit_id == allocation.circuit_id && a.station_id == allocation.station_id).count(), 1);
+        }
+        // mutual first choices are paired
+        assert!(groups_of(&allocations, &candidates).contains(&vec![String::from("f_am_1"), String::from("f_am_3")]));
+    }
+
+    #[test]
+    fn test_allocate_by_slot_keeps_avoided_pairs_apart() {
+        // c and d want each other, which would leave a and b (who avoid each other) as the only pair of the other circuit
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", false), circuit(slot_id, "B", false)];
+        let stations = vec![station(0)];
+        let mut candidates: Vec<Candidate> = ["a", "b", "c", "d"].iter().map(|code| candidate(code, false, true, true)).collect();
+        candidates[0].avoid = vec![String::from("b")];
+        candidates[2].partner_pref = Some(String::from("d"));
+        candidates[3].partner_pref = Some(String::from("c"));
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &[], pm_runs: &[], group_size: 2 };
+
+        let allocations = allocate_by_slot(&layout, &stations, &candidates, &[], &[], false)
+            .expect("allocation is feasible");
+
+        let groups = groups_of(&allocations, &candidates);
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|group| !(group.contains(&String::from("a")) && group.contains(&String::from("b")))));
+    }
+
+    #[test]
+    fn test_allocate_by_slot_forbidden_repeats() {
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", false)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let candidates: Vec<Candidate> = ["a", "b", "c", "d"].iter().map(|code| candidate(code, false, true, true)).collect();
+        let past_pairs = vec![(candidates[0].id, candidates[1].id), (candidates[2].id, candidates[3].id)];
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &[], pm_runs: &[], group_size: 2 };
+
+        let allocations = allocate_by_slot(&layout, &stations, &candidates, &[], &past_pairs, true)
+            .expect("allocation is feasible");
+
+        let groups = groups_of(&allocations, &candidates);
+        assert!(!groups.contains(&vec![String::from("a"), String::from("b")]));
+        assert!(!groups.contains(&vec![String::from("c"), String::from("d")]));
+    }
+
+    #[test]
+    fn test_allocate_by_slot_groups_of_3() {
+        // a, b and c avoid d, e and f, so the only grouping is {a, b, c} and {d, e, f}
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", false)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let mut candidates: Vec<Candidate> = ["a", "b", "c", "d", "e", "f"].iter().map(|code| candidate(code, false, true, true)).collect();
+        for candidate in &mut candidates[..3] {
+            candidate.avoid = vec![String::from("d"), String::from("e"), String::from("f")];
+        }
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &[], pm_runs: &[], group_size: 3 };
+        let pair_rules = PairRules { past_pairs: &HashSet::new(), forbid_repeats: false };
+        let expected = vec![
+            vec![String::from("a"), String::from("b"), String::from("c")],
+            vec![String::from("d"), String::from("e"), String::from("f")],
+        ];
+
+        let staged = allocate_by_slot(&layout, &stations, &candidates, &[], &[], false).expect("allocation is feasible");
+        assert_eq!(groups_of(&staged, &candidates), expected);
+        // the fallback model agrees
+        let locked_stations = vec![vec![None; stations.len()]; circuits.len()];
+        let monolithic = assign_stations(&layout, &stations, &candidates, &locked_stations, &pair_rules).expect("allocation is feasible");
+        assert_eq!(groups_of(&monolithic, &candidates), expected);
+    }
+
+    #[test]
+    fn test_allocate_by_slot_ungroupable() {
+        // everyone only accepts their 2 neighbours in a ring of 6, which has no group of 3 where everyone accepts each other
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", false)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let codes = ["a", "b", "c", "d", "e", "f"];
+        let candidates: Vec<Candidate> = codes
+            .iter()
+            .enumerate()
+            .map(|(i, code)| {
+                let mut candidate = candidate(code, false, true, true);
+                candidate.avoid = [(i + 2) % 6, (i + 3) % 6, (i + 4) % 6].iter().map(|&j| codes[j].to_string()).collect();
+                candidate
+            })
+            .collect();
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &[], pm_runs: &[], group_size: 3 };
+
+        assert!(allocate_by_slot(&layout, &stations, &candidates, &[], &[], false).is_err());
+    }
+
+    #[test]
+    fn test_allocate_by_time_hard_constraints() {
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", false), circuit(slot_id, "B", true)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let am_runs = vec![run(slot_id, 9)];
+        let pm_runs = vec![run(slot_id, 13)];
+        let examiners = vec![
+            examiner("f_am_1", true, true, false),
+            examiner("f_am_2", true, true, false),
+            examiner("f_pm_1", true, false, true),
+            examiner("f_pm_2", true, false, true),
+            examiner("m_both_1", false, true, true),
+            examiner("m_both_2", false, true, true),
+            examiner("m_am", false, true, false),
+            examiner("f_none", true, false, false),
+        ];
+        let examiner_slots: Vec<(Uuid, Uuid)> = examiners.iter().map(|e| (e.id, slot_id)).collect();
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: true })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &pm_runs, group_size: 2 };
+        let rules = ExaminerRules { exclusions: &[], past_examiners: &[], require_continuity: true };
+
+        let allocations = allocate_by_time(&layout, &stations, &examiners, &[], &[], &rules, &examiner_slots)
+            .expect("allocation is feasible");
+
+        let examiner = |id: Uuid| examiners.iter().find(|e| e.id == id).unwrap();
+        for (run, is_am) in am_runs.iter().map(|r| (r, true)).chain(pm_runs.iter().map(|r| (r, false))) {
+            let in_run: Vec<&TimeAllocation> = allocations.iter().filter(|a| a.run_id == run.id).collect();
+            // every station of every circuit has exactly 1 examiner
+            for circuit in &circuits {
+                for station in &stations {
+                    let count = in_run.iter().filter(|a| a.circuit_id == circuit.id && a.station_id == station.id).count();
+                    assert_eq!(count, 1, "circuit {} {} has {} examiners", circuit.key, station.title, count);
+                }
+            }
+            for allocation in &in_run {
+                let e = examiner(allocation.examiner);
+                // nobody is at 2 stations at once
+                assert_eq!(in_run.iter().filter(|a| a.examiner == e.id).count(), 1, "{} double booked", e.shortcode);
+                // AM / PM availability
+                assert!(if is_am { e.am } else { e.pm }, "{} allocated to a run they cannot attend", e.shortcode);
+                // female-only circuits only get female examiners
+                let circuit = circuits.iter().find(|c| c.id == allocation.circuit_id).unwrap();
+                assert!(!circuit.female_only || e.female, "{} allocated to female-only circuit {}", e.shortcode, circuit.key);
+            }
+        }
+        // with continuity required, examiners keep 1 station in 1 circuit across the runs of the slot
+        for e in &examiners {
+            let mut positions: Vec<(Uuid, Uuid)> = allocations.iter().filter(|a| a.examiner == e.id).map(|a| (a.circuit_id, a.station_id)).collect();
+            positions.dedup();
+            assert!(positions.len() <= 1, "{} moved between stations", e.shortcode);
+        }
+    }
+
+    #[test]
+    fn test_allocate_by_time_too_few_female_examiners() {
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", true)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let am_runs = vec![run(slot_id, 9)];
+        let examiners = vec![examiner("f", true, true, true), examiner("m_1", false, true, true), examiner("m_2", false, true, true)];
+        let examiner_slots: Vec<(Uuid, Uuid)> = examiners.iter().map(|e| (e.id, slot_id)).collect();
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &[], group_size: 2 };
+        let rules = ExaminerRules { exclusions: &[], past_examiners: &[], require_continuity: false };
+
+        let result = allocate_by_time(&layout, &stations, &examiners, &[], &[], &rules, &examiner_slots);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_check_feasibility() {
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", true)];
+        let stations: Vec<Station> = (0..2).map(station).collect();
+        let candidates = vec![candidate("f_1", true, true, true), candidate("f_2", true, true, true), candidate("m", false, true, true)];
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: false })]);
+        let am_runs = vec![run(slot_id, 9)];
+        let examiners = vec![examiner("f", true, true, true), examiner("m", false, true, true)];
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &[], group_size: 2 };
+
+        let conditions: Vec<Condition> = check_feasibility(&layout, &stations, &stations, &candidates, &examiners)
+            .into_iter()
+            .map(|infeasibility| infeasibility.condition)
+            .collect();
+        assert_eq!(conditions, vec![Condition::UngroupedCandidates, Condition::CandidateCapacity, Condition::FemaleExaminerShortage]);
+
+        // enough of everyone
+        let candidates = vec![candidate("f_1", true, true, true), candidate("f_2", true, true, true)];
+        let examiners = vec![examiner("f_1", true, true, true), examiner("f_2", true, true, true)];
+        assert!(check_feasibility(&layout, &stations, &stations, &candidates, &examiners).is_empty());
+    }
+
+    #[test]
+    fn test_allocate_actors_hard_constraints() {
+        let slot_id = Uuid::new_v4();
+        let circuits = vec![circuit(slot_id, "A", false), circuit(slot_id, "B", true)];
+        let mut stations: Vec<Station> = (0..2).map(station).collect();
+        stations[0].needs_actor = true;
+        stations[0].actor_gender = Some(String::from("female"));
+        stations[1].needs_actor = true;
+        let am_runs = vec![run(slot_id, 9)];
+        let pm_runs = vec![run(slot_id, 13)];
+        let actors = vec![
+            actor("f_am", "female", true, false, &[]),
+            actor("f_pm", "female", false, true, &[]),
+            actor("f_both_1", "female", true, true, &[]),
+            actor("f_both_2", "female", true, true, &[]),
+            actor("m_both", "male", true, true, &["station 2"]),
+            actor("m_none", "male", false, false, &[]),
+        ];
+        let slot_times = HashMap::from([(slot_id, Availability { am: true, pm: true })]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &pm_runs, group_size: 2 };
+
+        let allocations = allocate_actors(&layout, &stations, &actors, &[]).expect("allocation is feasible");
+
+        let actor = |id: Uuid| actors.iter().find(|a| a.id == id).unwrap();
+        for (run, is_am) in am_runs.iter().map(|r| (r, true)).chain(pm_runs.iter().map(|r| (r, false))) {
+            let in_run: Vec<&ActorAllocation> = allocations.iter().filter(|a| a.run_id == run.id).collect();
+            // there are enough actors for every station of every circuit
+            for circuit in &circuits {
+                for station in &stations {
+                    let count = in_run.iter().filter(|a| a.circuit_id == circuit.id && a.station_id == station.id).count();
+                    assert_eq!(count, 1, "circuit {} {} has {} actors", circuit.key, station.title, count);
+                }
+            }
+            for allocation in &in_run {
+                let a = actor(allocation.actor);
+                // nobody plays 2 stations at once
+                assert_eq!(in_run.iter().filter(|other| other.actor == a.id).count(), 1, "{} double booked", a.shortcode);
+                // AM / PM availability
+                assert!(if is_am { a.am } else { a.pm }, "{} allocated to a run they cannot attend", a.shortcode);
+                // station gender, female-only circuits and the stations they can play
+                let circuit = circuits.iter().find(|c| c.id == allocation.circuit_id).unwrap();
+                let station = stations.iter().find(|s| s.id == allocation.station_id).unwrap();
+                assert!(can_play(a, station, circuit), "{} cannot play {} in circuit {}", a.shortcode, station.title, circuit.key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_assign_slots_hard_constraints() {
+        let am_slot = Uuid::new_v4();
+        let pm_slot = Uuid::new_v4();
+        let circuits = vec![circuit(am_slot, "A", false), circuit(pm_slot, "B", true)];
+        let am_runs = vec![run(am_slot, 9)];
+        let pm_runs = vec![run(pm_slot, 13)];
+        let mut candidates = vec![
+            candidate("am_1", false, true, false),
+            candidate("am_2", false, true, false),
+            candidate("am_3", false, true, false),
+            candidate("f_pm", true, false, true),
+            candidate("f_both", true, true, true),
+        ];
+        candidates[0].partner_pref = Some(String::from("f_both"));
+        candidates[4].partner_pref = Some(String::from("am_1"));
+        let examiners = vec![
+            examiner("m_am", false, true, false),
+            examiner("f_am", true, true, false),
+            examiner("f_pm", true, false, true),
+            examiner("m_both", false, true, true),
+        ];
+        let slot_times = HashMap::from([
+            (am_slot, Availability { am: true, pm: false }),
+            (pm_slot, Availability { am: false, pm: true }),
+        ]);
+        let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &pm_runs, group_size: 2 };
+
+        let assignment = assign_slots(&layout, 3, 2, &candidates, &examiners, &[], &[]).expect("assignment is feasible");
+
+        for candidate in &candidates {
+            let slot_id = assignment.candidates[&candidate.id];
+            // AM / PM availability
+            assert!(can_attend(candidate, &slot_times[&slot_id]), "{} assigned to a slot they cannot attend", candidate.shortcode);
+            // the PM slot only has a female-only circuit
+            assert!(slot_id != pm_slot || candidate.female_only, "{} assigned to a slot without a circuit they can join", candidate.shortcode);
+        }
+        // mutual partners share a slot, when that costs no extra fill people
+        assert_eq!(assignment.candidates[&candidates[4].id], am_slot);
+        for slot_id in [am_slot, pm_slot] {
+            // head-counts are made up to a multiple of the group size with as few fill candidates as possible
+            let count = assignment.candidates.values().filter(|&&id| id == slot_id).count() + assignment.fill_candidates[&slot_id];
+            assert_eq!(count % 2, 0, "slot has {} candidates", count);
+        }
+        assert_eq!(assignment.fill_candidates.values().sum::<usize>(), 1);
+        for &(examiner_id, slot_id) in &assignment.examiners {
+            // examiners only take slots they can attend
+            let e = examiners.iter().find(|e| e.id == examiner_id).unwrap();
+            assert!(if slot_id == am_slot { e.am } else { e.pm }, "{} assigned to a slot they cannot attend", e.shortcode);
+        }
+        // 2 examined stations per run: the AM slot has enough examiners, the PM one is a female examiner short
+        assert_eq!(assignment.fill_examiners[&am_slot], (0, 0));
+        assert_eq!(assignment.fill_examiners[&pm_slot], (1, 0));
+    }
+}
@@ -69,7 +69,7 @@ async fn main() -> Result<()> {
             Some(Commands::CreateUser { username, password, organisation, admin }) => {
                 let username = username.clone().unwrap_or_else(|| String::from("testuser"));
                 let password = password.clone().unwrap_or_else(|| String::from("cookedpwongfrfr"));
-                let organisation_id = Organisation::get_id(&pool, &organisation).await?;
+                let organisation_id = Organisation::get_id(&pool, organisation).await?;
                 let test_user = UserCreation {
                     username,
                     password,
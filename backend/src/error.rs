@@ -1,22 +1,40 @@
 use axum::{
     response::{Response, IntoResponse},
     http::StatusCode,
+    Json,
 };
 use thiserror::Error;
 
+use crate::{allocation_algo::Infeasibility, http::timetable::ScheduleIssue};
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("{0}")]
     Anyhow(#[from] anyhow::Error),
+    #[error("Allocation is infeasible")]
+    Infeasible(Vec<Infeasibility>), // conditions found before solving, sent back as a list
+    #[error("Run times are invalid")]
+    InvalidSchedule(Vec<ScheduleIssue>), // runs that overlap or end after midnight, sent back as a list
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", self.to_string()),
-        )
-        .into_response()
+        match self {
+            AppError::Infeasible(infeasibilities) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(infeasibilities),
+            )
+            .into_response(),
+            AppError::InvalidSchedule(issues) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(issues),
+            )
+            .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                self.to_string(),
+            )
+            .into_response(),
+        }
     }
 }
-
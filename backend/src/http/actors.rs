@@ -0,0 +1,313 @@
+use axum::{
+    extract::{Extension, Json, Query, State},
+    http::StatusCode, response::IntoResponse,
+    routing::{get, post}
+};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use anyhow::{anyhow, Context};
+
+use crate::error::AppError;
+
+use super::{users::{AccessClaims, User}, AppState, SomethingID};
+
+pub const ACTOR_GENDERS: [&str; 3] = ["female", "male", "other"]; // stations can ask for female or male
+
+pub fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/get", get(get_by_id))
+        .route("/get-session-all", get(get_all_by_session))
+        .route("/create", post(create))
+        .route("/update", post(update))
+        .route("/delete", post(delete))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Actor { // simulated patient
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub shortcode: String,
+    pub gender: String, // one of ACTOR_GENDERS
+    pub am: bool,
+    pub pm: bool,
+    pub checked_in: bool,
+    pub stations: Vec<String>, // lowercase titles of the stations they can play, any station when empty
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActorPayload {
+    pub session_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub shortcode: String,
+    pub gender: String,
+    pub am: bool,
+    pub pm: bool,
+    pub checked_in: bool,
+    #[serde(default)]
+    pub stations: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActorExcel {
+    pub first_name: String,
+    pub last_name: String,
+    pub shortcode: String,
+    pub gender: String,
+    pub am: bool,
+    pub pm: bool,
+    pub stations: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActorChange {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub shortcode: Option<String>,
+    pub gender: Option<String>,
+    pub am: Option<bool>,
+    pub pm: Option<bool>,
+    pub checked_in: Option<bool>,
+    pub stations: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteActorPayload {
+    pub session_id: Uuid,
+    pub ids: Vec<Uuid>,
+}
+
+async fn get_by_id(
+    State(pool): State<sqlx::PgPool>,
+    actor: Query<SomethingID>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = Actor::get(&pool, &actor.0.id).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn get_all_by_session(
+    State(pool): State<sqlx::PgPool>,
+    session: Query<SomethingID>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = Actor::get_all_by_session(&pool, &session.0.id).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn create(
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(mut actor): Json<ActorPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    actor.gender = actor.gender.to_lowercase();
+    if !ACTOR_GENDERS.contains(&actor.gender.as_str()) {
+        return Ok((StatusCode::BAD_REQUEST, format!("Gender must be one of: {}", ACTOR_GENDERS.join(", "))).into_response())
+    }
+    actor.stations = actor.stations.iter().map(|title| title.trim().to_lowercase()).collect();
+    let result = Actor::create(&pool, claim.organisation_id, actor).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn update(
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(mut actor): Json<ActorChange>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    actor.gender = actor.gender.map(|gender| gender.to_lowercase());
+    if actor.gender.as_deref().is_some_and(|gender| !ACTOR_GENDERS.contains(&gender)) {
+        return Ok((StatusCode::BAD_REQUEST, format!("Gender must be one of: {}", ACTOR_GENDERS.join(", "))).into_response())
+    }
+    actor.stations = actor.stations.map(|stations| stations.iter().map(|title| title.trim().to_lowercase()).collect());
+    let result = Actor::update(pool, claim.organisation_id, actor).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn delete(
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(actor): Json<DeleteActorPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    Actor::delete(pool, claim.organisation_id, actor).await?;
+    Ok((StatusCode::OK).into_response())
+}
+
+impl Actor {
+    pub async fn get(
+        pool: &sqlx::PgPool,
+        actor_id: &Uuid,
+    ) -> Result<Actor, AppError> {
+        sqlx::query_as!(
+            Actor,
+            r#"
+            SELECT * FROM people.actors WHERE id = $1
+            "#,
+            actor_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get actor with id: {}", actor_id)))
+    }
+
+    pub async fn get_all_by_session(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<Actor>, AppError> {
+        sqlx::query_as!(
+            Actor,
+            r#"
+            SELECT * FROM people.actors WHERE session_id = $1
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get all actors with session_id: {}", session_id)))
+    }
+
+    pub async fn create(
+        pool: &sqlx::PgPool,
+        organisation_id: Uuid,
+        actor: ActorPayload,
+    ) -> Result<Actor, AppError> {
+        let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+
+        sqlx::query!(
+            r#"
+            SELECT organisation_id FROM records.sessions
+            WHERE id = $1 AND organisation_id = $2
+            "#,
+            &actor.session_id, // given by client
+            &organisation_id // from claims
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Session not found or you do not have permission for this operation")?;
+
+        let actor = sqlx::query_as!(
+            Actor,
+            r#"
+            INSERT INTO people.actors (session_id, first_name, last_name, shortcode, gender, am, pm, checked_in, stations)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+            actor.session_id,
+            actor.first_name,
+            actor.last_name,
+            actor.shortcode,
+            actor.gender,
+            actor.am,
+            actor.pm,
+            actor.checked_in,
+            &actor.stations
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Cannot create actor")?;
+
+        transaction.commit().await.context("Transaction failed to commit")?;
+        Ok(actor)
+    }
+
+    pub async fn update(
+        pool: sqlx::PgPool,
+        organisation_id: Uuid,
+        actor: ActorChange,
+    ) -> Result<Actor, AppError> {
+        let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+
+        sqlx::query!(
+            r#"
+            SELECT organisation_id FROM records.sessions
+            WHERE id = $1 AND organisation_id = $2
+            "#,
+            &actor.session_id, // given by client
+            &organisation_id // from claims
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Session not found or you do not have permission for this operation")?;
+
+        let actor = sqlx::query_as!(
+            Actor,
+            r#"
+            UPDATE people.actors
+            SET
+                first_name = COALESCE($3, first_name),
+                last_name = COALESCE($4, last_name),
+                shortcode = COALESCE($5, shortcode),
+                gender = COALESCE($6, gender),
+                am = COALESCE($7, am),
+                pm = COALESCE($8, pm),
+                checked_in = COALESCE($9, checked_in),
+                stations = COALESCE($10, stations)
+            WHERE id = $1 AND session_id = $2
+            RETURNING *
+            "#,
+            actor.id,
+            actor.session_id,
+            actor.first_name,
+            actor.last_name,
+            actor.shortcode,
+            actor.gender,
+            actor.am,
+            actor.pm,
+            actor.checked_in,
+            actor.stations.as_deref()
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Cannot update actor")?;
+
+        transaction.commit().await.context("Transaction failed to commit")?;
+        Ok(actor)
+    }
+
+    pub async fn delete(
+        pool: sqlx::PgPool,
+        organisation_id: Uuid,
+        actors: DeleteActorPayload,
+    ) -> Result<(), AppError> {
+        let mut transaction = pool.begin().await.with_context(|| "Failed to start database transaction")?;
+
+        sqlx::query!(
+            r#"
+            SELECT organisation_id FROM records.sessions
+            WHERE id = $1 AND organisation_id = $2
+            "#,
+            &actors.session_id, // given by client
+            &organisation_id // from claims
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Session not found or you do not have permission for this operation")?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM people.actors
+            WHERE id = ANY($1) AND session_id = $2
+            "#,
+            &actors.ids,
+            &actors.session_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete actors")?;
+
+        transaction.commit().await
+            .with_context(|| "Failed to commit changes to the database")?;
+
+        Ok(())
+    }
+}
@@ -1,20 +1,29 @@
-use std::cmp;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{Context, anyhow};
-use axum::{extract::{Query, State}, http::StatusCode, response::IntoResponse, routing::get, Extension};
+use axum::{extract::{Query, State}, http::StatusCode, response::IntoResponse, routing::{get, post}, Extension, Json};
 use serde::{Deserialize, Serialize};
 use sqlx::Transaction;
 use uuid::Uuid;
 use super::{
-    candidates::Candidate, circuits::Circuit, examiners::Examiner, runs::{Run, RunTime}, slots::Slot, stations::Station, users::{AccessClaims, User}, AppState, SomethingID};
+    actors::Actor, candidates::Candidate, circuits::Circuit, examiners::Examiner, exclusions::Exclusion, runs::{Run, RunTime}, slots::Slot, stations::Station, timetable::utc_offsets, users::{AccessClaims, User}, AppState, SomethingID};
 use crate::{
-    allocation_algo::{allocate_by_slot, allocate_by_time, SlotAllocation, TimeAllocation}, error::AppError
+    allocation_algo::{allocate_actors, allocate_by_slot, allocate_by_time, assign_slots, avoids, reallocate_by_slot, reallocate_by_time, can_attend, can_play, check_feasibility, has_tags, partner_bonus, ActorAllocation, Condition, ExaminerRules, Layout, SlotAllocation, TimeAllocation}, error::AppError
 };
 use tracing::trace;
 
 pub fn router() -> axum::Router<AppState> {
     axum::Router::new()
+        .route("/get-session", get(get_by_session))
         .route("/generate", get(gen_new))
+        .route("/preview", get(preview))
+        .route("/accept", post(accept))
+        .route("/history", get(get_history))
+        .route("/restore", post(restore))
+        .route("/edit", post(edit))
+        .route("/lock", post(lock))
+        .route("/reallocate", post(reallocate))
+        .route("/report", get(report))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,20 +32,37 @@ pub struct Allocation {
     pub slot_id: Uuid,
     pub circuit_id: Uuid,
     pub station_id: Uuid,
-    pub candidate_1: Uuid,
-    pub candidate_2: Uuid,
-    pub examiner: Uuid,
+    pub candidates: Vec<Uuid>, // the group at the station in position order, from records.allocation_members
+    pub examiner: Option<Uuid>,
     #[serde(with = "time::serde::iso8601")]
-    pub modified_at: time::OffsetDateTime
+    pub modified_at: time::OffsetDateTime,
+    pub run_id: Uuid,
+    pub pair_locked: bool,
+    pub examiner_locked: bool,
+    pub actor: Option<Uuid>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AllocationPayload {
     pub slot_id: Uuid,
+    pub run_id: Uuid,
     pub circuit_id: Uuid,
     pub station_id: Uuid,
-    pub candidate_1: Uuid,
-    pub candidate_2: Uuid,
-    pub examiner: Uuid
+    #[serde(default)]
+    pub candidates: Vec<Uuid>, // empty when the station starts without a group
+    pub examiner: Option<Uuid>, // None for rest and written stations
+    #[serde(default)]
+    pub actor: Option<Uuid>, // None for stations without a simulated patient, or when nobody could play it
+    #[serde(default)]
+    pub pair_locked: bool, // kept fixed when re-solving
+    #[serde(default)]
+    pub examiner_locked: bool,
+    #[serde(default)]
+    pub performer: Option<Uuid>, // first of the group unless the run has flip_allocation, derived on read and never stored
+    #[serde(default)]
+    pub observers: Vec<Uuid>,
+    #[serde(default)]
+    pub vacancies: Vec<Uuid>, // people of this row who are placeholders, shown as "Vacancy". Derived on read
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,1135 +72,2079 @@ pub struct AllocationHistory {
     pub slot_id: Uuid,
     pub circuit_id: Uuid,
     pub station_id: Uuid,
-    pub candidate_1: Uuid,
-    pub candidate_2: Uuid,
-    pub examiner: Uuid,
+    pub candidates: Vec<Uuid>,
+    pub examiner: Option<Uuid>,
     pub modified_by: Uuid,
     pub auto_gen: bool,
     #[serde(with = "time::serde::iso8601")]
-    pub modified_at: time::OffsetDateTime
+    pub modified_at: time::OffsetDateTime,
+    pub run_id: Uuid,
+    pub pair_locked: bool,
+    pub examiner_locked: bool,
+    pub actor: Option<Uuid>,
+}
+
+impl From<Allocation> for AllocationPayload {
+    fn from(a: Allocation) -> Self {
+        AllocationPayload {
+            slot_id: a.slot_id,
+            run_id: a.run_id,
+            circuit_id: a.circuit_id,
+            station_id: a.station_id,
+            candidates: a.candidates,
+            examiner: a.examiner,
+            actor: a.actor,
+            pair_locked: a.pair_locked,
+            examiner_locked: a.examiner_locked,
+            performer: None,
+            observers: Vec::new(),
+            vacancies: Vec::new(),
+        }
+    }
+}
+
+impl From<AllocationHistory> for AllocationPayload {
+    fn from(h: AllocationHistory) -> Self {
+        AllocationPayload {
+            slot_id: h.slot_id,
+            run_id: h.run_id,
+            circuit_id: h.circuit_id,
+            station_id: h.station_id,
+            candidates: h.candidates,
+            examiner: h.examiner,
+            actor: h.actor,
+            pair_locked: h.pair_locked,
+            examiner_locked: h.examiner_locked,
+            performer: None,
+            observers: Vec::new(),
+            vacancies: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AllocationBatch {
+    pub batch_id: Uuid,
+    pub modified_by: Uuid,
+    pub username: String,
+    pub auto_gen: bool,
+    #[serde(with = "time::serde::iso8601")]
+    pub modified_at: time::OffsetDateTime,
+    pub allocations: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AllocationEdit {
+    SwapCandidates { candidate_a: Uuid, candidate_b: Uuid }, // either can be unallocated
+    SwapExaminers { examiner_a: Uuid, examiner_b: Uuid, run_id: Option<Uuid> }, // swaps in every run when run_id is None
+    SwapActors { actor_a: Uuid, actor_b: Uuid, run_id: Option<Uuid> }, // either can be unallocated, every run when run_id is None
+    #[serde(alias = "move_pair")]
+    MoveGroup { candidate_id: Uuid, circuit_id: Uuid, station_id: Uuid }, // group at the target station (if any) is swapped back
+    FillVacancy { placeholder: Uuid, person: Uuid }, // a late registrant takes a placeholder's place, locks are kept
+}
+
+#[derive(Debug, Deserialize)]
+struct EditPayload {
+    session_id: Uuid,
+    edit: AllocationEdit,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockPayload {
+    session_id: Uuid,
+    circuit_id: Uuid,
+    station_id: Uuid,
+    run_id: Option<Uuid>, // examiners only
+    pair_locked: Option<bool>, // unchanged when None
+    examiner_locked: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportQuery {
+    session_id: Uuid,
+    batch_id: Option<Uuid>, // the live allocation when None
+}
+
+#[derive(Debug, Deserialize)]
+struct RestorePayload {
+    session_id: Uuid,
+    batch_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+struct GenerateResponse {
+    batch_id: Uuid,
+    allocations: usize,
+    fill_candidates: usize,
+    fill_examiners: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unmatched_stations: Vec<UnmatchedStation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    missing_actors: Vec<MissingActor>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReallocateResponse {
+    #[serde(flatten)]
+    saved: GenerateResponse,
+    absent_candidates: usize,
+    absent_examiners: usize,
+    absent_actors: usize,
+    diff: AllocationDiff, // against the allocation before reallocating
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewResponse {
+    session_id: Uuid,
+    allocations: Vec<AllocationPayload>,
+    fills: Fills,
+    diff: AllocationDiff,
+    unmatched_stations: Vec<UnmatchedStation>,
+    missing_actors: Vec<MissingActor>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnmatchedStation { // examined by someone without any of the station's preferred tags
+    pub run_id: Uuid,
+    pub circuit_id: Uuid,
+    pub station_id: Uuid,
+    pub examiner: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QualityReport { // how good an allocation is, before it is published
+    pub batch_id: Option<Uuid>,
+    pub preferences: PreferenceReport,
+    pub female_only: FemaleOnlyReport,
+    pub circuits: Vec<CircuitReport>,
+    pub examiners: ExaminerReport,
+    pub tag_matches: Vec<TagMatch>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreferenceReport { // partner preferences between real candidates, first and second choices alike
+    pub mutual: usize, // pairs who named each other
+    pub mutual_honoured: usize,
+    pub one_sided: usize, // preferences the partner did not return
+    pub one_sided_honoured: usize,
+    pub honoured_share: Option<f64>, // None when nobody named a partner
+}
+
+#[derive(Debug, Serialize)]
+pub struct FemaleOnlyReport {
+    pub candidates: usize, // real female-only candidates
+    pub in_female_only_circuits: usize,
+    pub unallocated: usize,
+    pub examined_stations: usize, // rows with an examiner in female-only circuits
+    pub female_examiners: usize, // of those, examined by a female examiner
+}
+
+#[derive(Debug, Serialize)]
+pub struct CircuitReport {
+    pub circuit_id: Uuid,
+    pub key: String,
+    pub candidates: usize, // real candidates
+    pub placeholder_candidates: usize,
+    pub placeholder_examiners: usize, // placeholders examining in any run of the circuit
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExaminerReport { // real examiners only, counted per run
+    pub available: usize, // runs examiners are available for
+    pub allocated: usize, // runs examiners examine in
+    pub utilisation: Option<f64>, // None when no examiner is available
+    pub idle_reserves: Vec<IdleReserves>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdleReserves { // available for a run but not examining anywhere at that time
+    pub run_id: Uuid,
+    pub examiners: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagMatch { // an examined station with preferred tags
+    pub run_id: Uuid,
+    pub circuit_id: Uuid,
+    pub station_id: Uuid,
+    pub examiner: Uuid,
+    pub matched: bool, // examiner has at least 1 of the preferred tags
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingActor { // a station needing an actor that nobody available could play
+    pub run_id: Uuid,
+    pub circuit_id: Uuid,
+    pub station_id: Uuid,
+}
+
+struct Generated { // output of generate, nothing saved yet
+    fills: Fills,
+    allocations: Vec<AllocationPayload>,
+    unmatched_stations: Vec<UnmatchedStation>,
+    missing_actors: Vec<MissingActor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviewPayload { // a preview sent back to be accepted
+    session_id: Uuid,
+    allocations: Vec<AllocationPayload>,
+    fills: Fills,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AllocationDiff {
+    pub stations: Vec<StationDiff>,
+    pub moved_candidates: Vec<PersonMove>,
+    pub moved_examiners: Vec<PersonMove>,
+    pub moved_actors: Vec<PersonMove>,
+    pub broken_pairs: Vec<BrokenPair>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StationDiff {
+    pub run_id: Uuid,
+    pub circuit_id: Uuid,
+    pub station_id: Uuid,
+    pub current_candidates: Vec<Uuid>,
+    pub current_examiner: Option<Uuid>,
+    pub current_actor: Option<Uuid>,
+    pub proposed_candidates: Vec<Uuid>,
+    pub proposed_examiner: Option<Uuid>,
+    pub proposed_actor: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PersonMove { // None when the person is not allocated on that side
+    pub id: Uuid,
+    pub run_id: Option<Uuid>, // examiners and actors only
+    pub from_circuit: Option<Uuid>,
+    pub from_station: Option<Uuid>,
+    pub to_circuit: Option<Uuid>,
+    pub to_station: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenPair { // 2 candidates who shared a group and no longer do
+    pub candidate_1: Uuid,
+    pub candidate_2: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Availability {
+    pub am: bool,
+    pub pm: bool,
+}
+
+#[derive(Debug)]
+pub enum PeopleType {
+    Candidate,
+    Examiner,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Fills { // fill people needed by an allocation, only inserted when the allocation is saved
+    pub candidates: Vec<Candidate>,
+    pub examiners: Vec<Examiner>,
+}
+
+impl Fills {
+    fn add(&mut self, session_id: &Uuid, ppl_type: &PeopleType, time: Availability, female: bool) {
+        match ppl_type {
+            PeopleType::Candidate => {
+                self.candidates.push(super::candidates::create_fill(*session_id, Some(time), female));
+            },
+            PeopleType::Examiner => {
+                self.examiners.push(super::examiners::create_fill(*session_id, Some(time), female));
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PastPairings { // shortcodes from the live allocations of earlier sessions in the same organisation
+    pub candidate_pairs: Vec<(String, String)>,
+    pub examiner_candidates: Vec<(String, String)>, // (examiner, candidate) in the same circuit and run
+}
+
+struct SlotPlan { // a slot with its circuits and runs, split into AM / PM
+    slot: Slot,
+    circuits: Vec<Circuit>,
+    am_runs: Vec<Run>,
+    pm_runs: Vec<Run>,
+}
+
+impl SlotPlan {
+    async fn get_all_by_session(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<SlotPlan>, AppError> {
+        let slots = Slot::get_all_by_session(pool, session_id).await?;
+        let timezone = sqlx::query_scalar!(
+            r#"
+            SELECT timezone FROM records.sessions WHERE id = $1
+            "#,
+            session_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get timezone of session: {}", session_id)))?;
+        let mut slot_plans = Vec::new();
+        for slot in slots {
+            let (circuit_result, am_runs_result, pm_runs_result) = tokio::join!(
+                Circuit::get_by_slot(pool, &slot.id),
+                Run::get_by_time(pool, &slot.id, RunTime::AM),
+                Run::get_by_time(pool, &slot.id, RunTime::PM)
+            );
+            let mut plan = SlotPlan {
+                slot,
+                circuits: circuit_result?,
+                am_runs: am_runs_result?,
+                pm_runs: pm_runs_result?,
+            };
+            for runs in [&mut plan.am_runs, &mut plan.pm_runs] { // run times in messages are local
+                let starts: Vec<time::OffsetDateTime> = runs.iter().map(|run| run.scheduled_start).collect();
+                for (run, offset) in runs.iter_mut().zip(utc_offsets(pool, &timezone, &starts).await?) {
+                    run.scheduled_start = run.scheduled_start.to_offset(offset);
+                    run.scheduled_end = run.scheduled_end.to_offset(offset);
+                }
+            }
+            if plan.am_runs.is_empty() && plan.pm_runs.is_empty() {
+                return Err(AppError::from(anyhow!("Slot has no runs")));
+            }
+            slot_plans.push(plan);
+        }
+        Ok(slot_plans)
+    }
+
+    fn time(&self) -> Availability {
+        Availability { am: !self.am_runs.is_empty(), pm: !self.pm_runs.is_empty() }
+    }
+}
+
+fn merge_allocations( // full rows per run, circuit and station
+    circuits: &[Circuit],
+    stations: &[Station],
+    runs: &[&Run],
+    candidate_allocations: Vec<SlotAllocation>,
+    examiner_allocations: Vec<TimeAllocation>,
+    actor_allocations: Vec<ActorAllocation>,
+) -> Vec<AllocationPayload> {
+    let groups: HashMap<(Uuid, Uuid), Vec<Uuid>> = candidate_allocations
+        .into_iter()
+        .map(|a| ((a.circuit_id, a.station_id), a.candidates))
+        .collect();
+    let examiners: HashMap<(Uuid, Uuid, Uuid), Uuid> = examiner_allocations
+        .into_iter()
+        .map(|a| ((a.run_id, a.circuit_id, a.station_id), a.examiner))
+        .collect();
+    let actors: HashMap<(Uuid, Uuid, Uuid), Uuid> = actor_allocations
+        .into_iter()
+        .map(|a| ((a.run_id, a.circuit_id, a.station_id), a.actor))
+        .collect();
+
+    let mut allocations = Vec::new();
+    for run in runs {
+        for circuit in circuits.iter().filter(|c| c.slot_id == run.slot_id) {
+            for station in stations {
+                let group = groups.get(&(circuit.id, station.id));
+                let examiner = examiners.get(&(run.id, circuit.id, station.id)).copied();
+                let actor = actors.get(&(run.id, circuit.id, station.id)).copied();
+                if group.is_none() && examiner.is_none() && actor.is_none() { // empty station without an examiner or actor
+                    continue;
+                }
+                allocations.push(AllocationPayload {
+                    slot_id: run.slot_id,
+                    run_id: run.id,
+                    circuit_id: circuit.id,
+                    station_id: station.id,
+                    candidates: group.cloned().unwrap_or_default(),
+                    examiner,
+                    actor,
+                    pair_locked: false,
+                    examiner_locked: false,
+                    performer: None,
+                    observers: Vec::new(),
+                    vacancies: Vec::new(),
+                });
+            }
+        }
+    }
+    allocations
+}
+
+/// Sets who performs and who observes in each group. The group moves along by 1 place in runs with flip_allocation,
+/// so partners in a pair swap roles
+fn assign_roles(
+    allocations: &mut [AllocationPayload],
+    runs: &[&Run],
+) {
+    for allocation in allocations.iter_mut() {
+        let flip = runs.iter().find(|run| run.id == allocation.run_id).is_some_and(|run| run.flip_allocation);
+        let mut order = allocation.candidates.clone();
+        if flip && !order.is_empty() {
+            order.rotate_left(1);
+        }
+        allocation.performer = order.first().copied();
+        allocation.observers = order.into_iter().skip(1).collect();
+    }
+}
+
+fn missing_actors( // stations that need an actor but were left without one
+    allocations: &[AllocationPayload],
+    circuits: &[Circuit],
+    actor_stations: &[Station],
+    runs: &[&Run],
+) -> Vec<MissingActor> {
+    let mut missing = Vec::new();
+    for run in runs {
+        for circuit in circuits.iter().filter(|c| c.slot_id == run.slot_id) {
+            for station in actor_stations {
+                let played = allocations.iter().any(|a| a.run_id == run.id && a.circuit_id == circuit.id && a.station_id == station.id && a.actor.is_some());
+                if !played {
+                    missing.push(MissingActor { run_id: run.id, circuit_id: circuit.id, station_id: station.id });
+                }
+            }
+        }
+    }
+    missing
+}
+
+fn tag_matches( // examined stations with preferred tags, and whether their examiner has any of them
+    allocations: &[AllocationPayload],
+    stations: &[Station],
+    examiners: &[Examiner],
+) -> Vec<TagMatch> {
+    allocations
+        .iter()
+        .filter_map(|a| {
+            let station = stations.iter().find(|s| s.id == a.station_id && !s.preferred_tags.is_empty())?;
+            let examiner = examiners.iter().find(|e| Some(e.id) == a.examiner)?;
+            let matched = station.preferred_tags.iter().any(|tag| examiner.tags.contains(tag));
+            Some(TagMatch { run_id: a.run_id, circuit_id: a.circuit_id, station_id: a.station_id, examiner: examiner.id, matched })
+        })
+        .collect()
+}
+
+fn mark_vacancies( // placeholders are listed per row, so they can be shown as "Vacancy"
+    allocations: &mut [AllocationPayload],
+    placeholders: &[Uuid],
+) {
+    for allocation in allocations.iter_mut() {
+        allocation.vacancies = allocation.candidates
+            .iter()
+            .copied()
+            .chain(allocation.examiner)
+            .filter(|id| placeholders.contains(id))
+            .collect();
+    }
+}
+
+/// Works out the fill people and allocation rows for a whole session. Nothing is written to the database
+async fn generate(
+    pool: &sqlx::PgPool,
+    session_id: &Uuid,
+) -> Result<Generated, AppError> {
+    let slot_plans = SlotPlan::get_all_by_session(pool, session_id).await?;
+    let stations = Station::get_by_session(pool, session_id).await?;
+    let examined_stations = Station::get_examined_by_session(pool, session_id).await?;
+    let actor_stations = Station::get_with_actor_by_session(pool, session_id).await?;
+    let (candidate_result, examiner_result, actor_result) = tokio::join!(
+        Candidate::get_all_by_session(pool, session_id),
+        Examiner::get_all_by_session(pool, session_id),
+        Actor::get_all_by_session(pool, session_id)
+    );
+    let candidates = candidate_result?;
+    let mut examiners = examiner_result?;
+    let actors = actor_result?;
+
+    if slot_plans.is_empty() {
+        return Err(AppError::from(anyhow!("Session has no slots")));
+    }
+
+    let slot_times: HashMap<Uuid, Availability> = slot_plans.iter().map(|p| (p.slot.id, p.time())).collect();
+    let (mut candidates, unavailable_candidates): (Vec<Candidate>, Vec<Candidate>) = candidates
+        .into_iter()
+        .partition(|c| slot_times.values().any(|time| can_attend(c, time)));
+    if !unavailable_candidates.is_empty() {
+        trace!("{} candidates cannot make it to any slot and will not be allocated", unavailable_candidates.len());
+    }
+
+    let mut circuits = Vec::new();
+    let mut am_runs = Vec::new();
+    let mut pm_runs = Vec::new();
+    for plan in slot_plans {
+        circuits.extend(plan.circuits);
+        am_runs.extend(plan.am_runs);
+        pm_runs.extend(plan.pm_runs);
+    }
+
+    let settings = sqlx::query!("SELECT require_examiner_continuity, forbid_repeats, candidates_per_station FROM records.sessions WHERE id = $1", session_id)
+        .fetch_one(pool)
+        .await
+        .with_context(|| "Cannot get session settings")?;
+    let group_size = settings.candidates_per_station as usize;
+
+    // locked allocations of the live allocation stay where they are
+    let current = Allocation::get_by_session(pool, session_id).await?;
+    let mut locked_groups: Vec<SlotAllocation> = Vec::new();
+    for allocation in current.iter().filter(|a| a.pair_locked && !a.candidates.is_empty()) {
+        if !locked_groups.iter().any(|g| g.circuit_id == allocation.circuit_id && g.station_id == allocation.station_id) { // same group in every run
+            locked_groups.push(SlotAllocation { circuit_id: allocation.circuit_id, station_id: allocation.station_id, candidates: allocation.candidates.clone() });
+        }
+    }
+    let locked_examiners: Vec<TimeAllocation> = current
+        .iter()
+        .filter(|a| a.examiner_locked)
+        .filter_map(|a| a.examiner.map(|examiner| TimeAllocation { run_id: a.run_id, circuit_id: a.circuit_id, station_id: a.station_id, examiner }))
+        .collect();
+    let locked_examiner_slots: Vec<(Uuid, Uuid)> = current
+        .iter()
+        .filter(|a| a.examiner_locked)
+        .filter_map(|a| a.examiner.map(|examiner| (examiner, a.slot_id)))
+        .collect();
+
+    // placeholders of locked allocations are kept, the others are replaced by the fill people this allocation needs
+    let (placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_placeholders_by_session(pool, session_id),
+        Examiner::get_placeholders_by_session(pool, session_id)
+    );
+    candidates.extend(placeholder_candidates?.into_iter().filter(|c| locked_groups.iter().any(|g| g.candidates.contains(&c.id))));
+    examiners.extend(placeholder_examiners?.into_iter().filter(|e| locked_examiners.iter().any(|l| l.examiner == e.id)));
+
+    // every candidate and examiner is given a slot, with the fill people each slot needs for full groups and full examiners
+    let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &pm_runs, group_size };
+    let assignment = match assign_slots(&layout, stations.len(), examined_stations.len(), &candidates, &examiners, &locked_groups, &locked_examiner_slots) {
+        Ok(assignment) => assignment,
+        Err(e) => { // conditions the real people cannot meet are more useful than the solver error
+            let infeasibilities = check_feasibility(&layout, &stations, &examined_stations, &candidates, &examiners);
+            if !infeasibilities.is_empty() {
+                return Err(AppError::Infeasible(infeasibilities));
+            }
+            return Err(e);
+        },
+    };
+
+    let mut fills = Fills::default();
+    let mut candidate_slots = assignment.candidates;
+    let mut examiner_slots = assignment.examiners;
+    for (slot_id, time) in &slot_times {
+        for _ in 0..assignment.fill_candidates[slot_id] {
+            fills.add(session_id, &PeopleType::Candidate, time.clone(), true);
+            candidate_slots.insert(fills.candidates.last().unwrap().id, *slot_id);
+        }
+        let (female_examiners, other_examiners) = assignment.fill_examiners[slot_id];
+        for female in std::iter::repeat_n(true, female_examiners).chain(std::iter::repeat_n(false, other_examiners)) {
+            fills.add(session_id, &PeopleType::Examiner, time.clone(), female);
+            examiner_slots.push((fills.examiners.last().unwrap().id, *slot_id));
+        }
+    }
+    trace!("Fills: {} candidates, {} examiners", fills.candidates.len(), fills.examiners.len());
+    let all_candidates: Vec<Candidate> = candidates.into_iter().chain(fills.candidates.iter().cloned()).collect();
+    let all_examiners: Vec<Examiner> = examiners.into_iter().chain(fills.examiners.iter().cloned()).collect();
+
+    // conditions the solver cannot meet are reported with their numbers, instead of a bare solver error
+    let infeasibilities = check_feasibility(&layout, &stations, &examined_stations, &all_candidates, &all_examiners);
+    if !infeasibilities.is_empty() {
+        return Err(AppError::Infeasible(infeasibilities));
+    }
+
+    let past = PastPairings::get_by_session(pool, session_id).await?;
+    let past_pairs = past.resolve_pairs(&all_candidates);
+    let past_examiners = past.resolve_examiners(&all_examiners, &all_candidates);
+
+    let mut candidate_allocations = Vec::new();
+    for (slot_id, time) in &slot_times { // each slot is solved on its own, with the candidates assigned to it
+        let slot_circuits: Vec<Circuit> = circuits.iter().filter(|c| c.slot_id == *slot_id).cloned().collect();
+        let slot_candidates: Vec<Candidate> = all_candidates.iter().filter(|c| candidate_slots.get(&c.id) == Some(slot_id)).cloned().collect();
+        let slot_locked_groups: Vec<SlotAllocation> = locked_groups
+            .iter()
+            .filter(|g| slot_circuits.iter().any(|c| c.id == g.circuit_id))
+            .cloned()
+            .collect();
+        let slot_time = HashMap::from([(*slot_id, time.clone())]);
+        let slot_layout = Layout { circuits: &slot_circuits, slot_times: &slot_time, ..layout };
+        candidate_allocations.extend(allocate_by_slot(&slot_layout, &stations, &slot_candidates, &slot_locked_groups, &past_pairs, settings.forbid_repeats)?);
+    }
+    let exclusions = Exclusion::get_all_by_session(pool, session_id).await?;
+    let mut exclusions = Exclusion::resolve(&exclusions, &all_examiners, &all_candidates);
+    if settings.forbid_repeats { // forbidden repeats are treated as conflicts of interest
+        exclusions.extend(past_examiners.iter().copied());
+    }
+    let rules = ExaminerRules { exclusions: &exclusions, past_examiners: &past_examiners, require_continuity: settings.require_examiner_continuity };
+    let examiner_allocations = allocate_by_time(&layout, &examined_stations, &all_examiners, &locked_examiners, &candidate_allocations, &rules, &examiner_slots)?;
+    let actor_allocations = allocate_actors(&layout, &actor_stations, &actors, &[])?;
+
+    let runs: Vec<&Run> = am_runs.iter().chain(pm_runs.iter()).collect();
+    let mut allocations = merge_allocations(&circuits, &stations, &runs, candidate_allocations, examiner_allocations, actor_allocations);
+    for allocation in allocations.iter_mut() {
+        allocation.pair_locked = locked_groups.iter().any(|g| g.circuit_id == allocation.circuit_id && g.station_id == allocation.station_id);
+        allocation.examiner_locked = locked_examiners.iter().any(|e| e.run_id == allocation.run_id && e.circuit_id == allocation.circuit_id && e.station_id == allocation.station_id);
+    }
+    assign_roles(&mut allocations, &runs);
+    let placeholders: Vec<Uuid> = all_candidates.iter().filter(|c| c.placeholder).map(|c| c.id)
+        .chain(all_examiners.iter().filter(|e| e.placeholder).map(|e| e.id))
+        .collect();
+    mark_vacancies(&mut allocations, &placeholders);
+
+    // fill people that were not needed in the end are not kept
+    fills.candidates.retain(|c| allocations.iter().any(|a| a.candidates.contains(&c.id)));
+    fills.examiners.retain(|e| allocations.iter().any(|a| a.examiner == Some(e.id)));
+
+    // stations where the solver could only use examiners without any preferred tag
+    let unmatched_stations = tag_matches(&allocations, &stations, &all_examiners)
+        .into_iter()
+        .filter(|m| !m.matched)
+        .map(|m| UnmatchedStation { run_id: m.run_id, circuit_id: m.circuit_id, station_id: m.station_id, examiner: m.examiner })
+        .collect();
+    let missing_actors = missing_actors(&allocations, &circuits, &actor_stations, &runs);
+
+    Ok(Generated { fills, allocations, unmatched_stations, missing_actors })
+}
+
+async fn check_session( // session must belong to the user's organisation
+    pool: &sqlx::PgPool,
+    session_id: &Uuid,
+    organisation_id: &Uuid,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        SELECT organisation_id FROM records.sessions
+        WHERE id = $1 AND organisation_id = $2
+        "#,
+        session_id, // given by client
+        organisation_id // from claims
+    )
+    .fetch_one(pool)
+    .await
+    .context("Session not found or you do not have permission for this operation")?;
+    Ok(())
 }
 
-#[derive(Debug, Serialize)]
-pub struct Availability {
-    pub am: bool,
-    pub pm: bool,
+async fn save_allocation( // inserts the fill people and replaces the live allocation in 1 transaction
+    pool: &sqlx::PgPool,
+    session_id: &Uuid,
+    user_id: &Uuid,
+    fills: &Fills,
+    allocations: &[AllocationPayload],
+) -> Result<GenerateResponse, AppError> {
+    let batch_id = Uuid::new_v4();
+    let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+
+    for candidate in &fills.candidates {
+        Candidate::create_fill_tx(&mut transaction, candidate).await?;
+    }
+    for examiner in &fills.examiners {
+        Examiner::create_fill_tx(&mut transaction, examiner).await?;
+    }
+    Allocation::add_batch(&mut transaction, session_id, allocations, &batch_id, user_id, true).await?;
+    // placeholders of the replaced allocation would otherwise pile up with every save
+    Candidate::delete_unused_placeholders_tx(&mut transaction, session_id).await?;
+    Examiner::delete_unused_placeholders_tx(&mut transaction, session_id).await?;
+
+    sqlx::query!("UPDATE records.sessions SET status = 'allocated' WHERE id = $1", session_id).execute(&mut *transaction).await.map_err(|err| anyhow!("Failed to change session allocation status: {}", err))?;
+    transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
+
+    Ok(GenerateResponse {
+        batch_id,
+        allocations: allocations.len(),
+        fill_candidates: fills.candidates.len(),
+        fill_examiners: fills.examiners.len(),
+        unmatched_stations: Vec::new(),
+        missing_actors: Vec::new(),
+    })
+}
+
+async fn get_by_session( // live allocation, with each group's roles for every run
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    session: Query<SomethingID> // session id
+) -> Result<impl IntoResponse, AppError> {
+    let session_id = session.0.id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let slot_plans = SlotPlan::get_all_by_session(&pool, &session_id).await?;
+    let runs: Vec<&Run> = slot_plans.iter().flat_map(|p| p.am_runs.iter().chain(p.pm_runs.iter())).collect();
+    let mut allocations: Vec<AllocationPayload> = Allocation::get_by_session(&pool, &session_id)
+        .await?
+        .into_iter()
+        .map(AllocationPayload::from)
+        .collect();
+    assign_roles(&mut allocations, &runs);
+    let (placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_placeholders_by_session(&pool, &session_id),
+        Examiner::get_placeholders_by_session(&pool, &session_id)
+    );
+    let placeholders: Vec<Uuid> = placeholder_candidates?.iter().map(|c| c.id)
+        .chain(placeholder_examiners?.iter().map(|e| e.id))
+        .collect();
+    mark_vacancies(&mut allocations, &placeholders);
+    Ok((StatusCode::OK, Json(allocations)).into_response())
+}
+
+async fn gen_new( // for static/initial allocation
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    session: Query<SomethingID> // session id
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = session.0.id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let generated = generate(&pool, &session_id).await?;
+    let mut response = save_allocation(&pool, &session_id, &claim.id, &generated.fills, &generated.allocations).await?;
+    response.unmatched_stations = generated.unmatched_stations;
+    response.missing_actors = generated.missing_actors;
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+async fn preview( // dry-run of gen_new, nothing is written
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    session: Query<SomethingID> // session id
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = session.0.id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let Generated { fills, allocations, unmatched_stations, missing_actors } = generate(&pool, &session_id).await?;
+    let current = Allocation::get_by_session(&pool, &session_id).await?;
+    let diff = diff_allocations(&current, &allocations);
+    Ok((StatusCode::OK, Json(PreviewResponse { session_id, allocations, fills, diff, unmatched_stations, missing_actors })).into_response())
+}
+
+async fn accept( // saves a previewed allocation, checked against the solver's rules like a manual edit
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(mut payload): Json<PreviewPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = payload.session_id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let slots = Slot::get_all_by_session(&pool, &session_id).await?;
+    if payload.allocations.iter().any(|a| !slots.iter().any(|s| s.id == a.slot_id)) {
+        return Ok((StatusCode::BAD_REQUEST, "Allocation contains slots from another session").into_response())
+    }
+    for candidate in payload.fills.candidates.iter_mut() {
+        candidate.session_id = session_id;
+        candidate.placeholder = true;
+    }
+    for examiner in payload.fills.examiners.iter_mut() {
+        examiner.session_id = session_id;
+        examiner.placeholder = true;
+    }
+
+    // the preview comes back from the client, so everyone in it must be a person of the session or a new fill person
+    let (candidate_result, examiner_result, placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_all_by_session(&pool, &session_id),
+        Examiner::get_all_by_session(&pool, &session_id),
+        Candidate::get_placeholders_by_session(&pool, &session_id),
+        Examiner::get_placeholders_by_session(&pool, &session_id)
+    );
+    let mut candidates = candidate_result?;
+    let mut examiners = examiner_result?;
+    candidates.extend(placeholder_candidates?);
+    examiners.extend(placeholder_examiners?);
+    let mut fill_ids: HashSet<Uuid> = HashSet::new();
+    for id in payload.fills.candidates.iter().map(|c| c.id).chain(payload.fills.examiners.iter().map(|e| e.id)) {
+        if !fill_ids.insert(id) || candidates.iter().any(|c| c.id == id) || examiners.iter().any(|e| e.id == id) {
+            return Ok((StatusCode::BAD_REQUEST, "Fill person is already a person of the session").into_response())
+        }
+    }
+    candidates.extend(payload.fills.candidates.iter().cloned());
+    examiners.extend(payload.fills.examiners.iter().cloned());
+
+    let slot_plans = SlotPlan::get_all_by_session(&pool, &session_id).await?;
+    let stations = Station::get_by_session(&pool, &session_id).await?;
+    let actors = Actor::get_all_by_session(&pool, &session_id).await?;
+    let settings = sqlx::query!("SELECT require_examiner_continuity, forbid_repeats, candidates_per_station FROM records.sessions WHERE id = $1", &session_id)
+        .fetch_one(&pool)
+        .await
+        .with_context(|| "Cannot get session settings")?;
+    let exclusions = Exclusion::get_all_by_session(&pool, &session_id).await?;
+    let mut exclusions = Exclusion::resolve(&exclusions, &examiners, &candidates);
+    if settings.forbid_repeats { // same as generate, forbidden repeats are conflicts of interest
+        let past = PastPairings::get_by_session(&pool, &session_id).await?;
+        exclusions.extend(past.resolve_examiners(&examiners, &candidates));
+    }
+    let rules = AllocationRules {
+        slot_plans: &slot_plans,
+        stations: &stations,
+        candidates: &candidates,
+        examiners: &examiners,
+        actors: &actors,
+        exclusions: &exclusions,
+        require_continuity: settings.require_examiner_continuity,
+        group_size: settings.candidates_per_station as usize,
+    };
+    if let Err(message) = rules.validate(&payload.allocations) {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response())
+    }
+
+    let response = save_allocation(&pool, &session_id, &claim.id, &payload.fills, &payload.allocations).await?;
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+/// Summarises an allocation: partner preferences honoured, female-only coverage, placeholders per circuit,
+/// examiner utilisation and which stations got an examiner with their preferred tags
+fn quality_report(
+    allocations: &[AllocationPayload],
+    slot_plans: &[SlotPlan],
+    stations: &[Station],
+    candidates: &[Candidate], // including placeholders
+    examiners: &[Examiner], // including placeholders
+) -> QualityReport {
+    let ratio = |part: usize, total: usize| (total > 0).then(|| part as f64 / total as f64);
+
+    // candidates keep the same group for every run of a slot
+    let mut groups: Vec<(Uuid, &Vec<Uuid>)> = Vec::new(); // (circuit, group)
+    for allocation in allocations.iter().filter(|a| !a.candidates.is_empty()) {
+        if !groups.iter().any(|(_, group)| **group == allocation.candidates) {
+            groups.push((allocation.circuit_id, &allocation.candidates));
+        }
+    }
+    let circuit_of = |candidate_id: Uuid| groups.iter().find(|(_, group)| group.contains(&candidate_id)).map(|(circuit_id, _)| *circuit_id);
+    let together = |a: Uuid, b: Uuid| groups.iter().any(|(_, group)| group.contains(&a) && group.contains(&b));
+
+    // partner preferences
+    let real_candidates: Vec<&Candidate> = candidates.iter().filter(|c| !c.placeholder).collect();
+    let names = |a: &Candidate, b: &Candidate| a.id != b.id && partner_bonus(a, b) > 0.0;
+    let (mut mutual, mut mutual_honoured, mut one_sided, mut one_sided_honoured) = (0, 0, 0, 0);
+    for (i, a) in real_candidates.iter().enumerate() {
+        for b in &real_candidates[(i + 1)..] {
+            let honoured = together(a.id, b.id) as usize;
+            match (names(a, b), names(b, a)) {
+                (true, true) => {
+                    mutual += 1;
+                    mutual_honoured += honoured;
+                },
+                (true, false) | (false, true) => {
+                    one_sided += 1;
+                    one_sided_honoured += honoured;
+                },
+                (false, false) => {},
+            }
+        }
+    }
+    let preferences = PreferenceReport {
+        mutual,
+        mutual_honoured,
+        one_sided,
+        one_sided_honoured,
+        honoured_share: ratio(mutual_honoured + one_sided_honoured, mutual + one_sided),
+    };
+
+    // female-only coverage
+    let circuits: Vec<&Circuit> = slot_plans.iter().flat_map(|p| p.circuits.iter()).collect();
+    let female_only_circuit = |circuit_id: Uuid| circuits.iter().any(|c| c.id == circuit_id && c.female_only);
+    let female_only_candidates: Vec<&&Candidate> = real_candidates.iter().filter(|c| c.female_only).collect();
+    let female_only_rows: Vec<&AllocationPayload> = allocations
+        .iter()
+        .filter(|a| a.examiner.is_some() && female_only_circuit(a.circuit_id))
+        .collect();
+    let female_only = FemaleOnlyReport {
+        candidates: female_only_candidates.len(),
+        in_female_only_circuits: female_only_candidates.iter().filter(|c| circuit_of(c.id).is_some_and(female_only_circuit)).count(),
+        unallocated: female_only_candidates.iter().filter(|c| circuit_of(c.id).is_none()).count(),
+        examined_stations: female_only_rows.len(),
+        female_examiners: female_only_rows
+            .iter()
+            .filter(|a| examiners.iter().any(|e| Some(e.id) == a.examiner && e.female))
+            .count(),
+    };
+
+    // placeholders per circuit
+    let is_placeholder_examiner = |id: &Uuid| examiners.iter().any(|e| e.id == *id && e.placeholder);
+    let circuit_reports = circuits
+        .iter()
+        .map(|circuit| {
+            let members: Vec<Uuid> = groups
+                .iter()
+                .filter(|(circuit_id, _)| *circuit_id == circuit.id)
+                .flat_map(|(_, group)| group.iter().copied())
+                .collect();
+            let placeholder_candidates = members.iter().filter(|id| candidates.iter().any(|c| c.id == **id && c.placeholder)).count();
+            let placeholder_examiners: HashSet<Uuid> = allocations
+                .iter()
+                .filter(|a| a.circuit_id == circuit.id)
+                .filter_map(|a| a.examiner)
+                .filter(is_placeholder_examiner)
+                .collect();
+            CircuitReport {
+                circuit_id: circuit.id,
+                key: circuit.key.clone(),
+                candidates: members.len() - placeholder_candidates,
+                placeholder_candidates,
+                placeholder_examiners: placeholder_examiners.len(),
+            }
+        })
+        .collect();
+
+    // examiner utilisation, per run they are available for
+    let runs: Vec<(&Run, bool)> = slot_plans
+        .iter()
+        .flat_map(|p| p.am_runs.iter().map(|run| (run, true)).chain(p.pm_runs.iter().map(|run| (run, false))))
+        .collect();
+    let examining = |examiner_id: Uuid, run: &Run| {
+        allocations.iter().any(|a| {
+            a.examiner == Some(examiner_id)
+                && runs.iter().any(|(other, _)| other.id == a.run_id && other.scheduled_start < run.scheduled_end && run.scheduled_start < other.scheduled_end)
+        })
+    };
+    let (mut available, mut allocated) = (0, 0);
+    let mut idle_reserves = Vec::new();
+    for (run, is_am) in &runs {
+        let mut idle = Vec::new();
+        for examiner in examiners.iter().filter(|e| !e.placeholder && (if *is_am { e.am } else { e.pm })) {
+            available += 1;
+            if allocations.iter().any(|a| a.run_id == run.id && a.examiner == Some(examiner.id)) {
+                allocated += 1;
+            } else if !examining(examiner.id, run) { // not busy in an overlapping run of another slot either
+                idle.push(examiner.id);
+            }
+        }
+        if !idle.is_empty() {
+            idle_reserves.push(IdleReserves { run_id: run.id, examiners: idle });
+        }
+    }
+    let examiner_report = ExaminerReport {
+        available,
+        allocated,
+        utilisation: ratio(allocated, available),
+        idle_reserves,
+    };
+
+    QualityReport {
+        batch_id: None,
+        preferences,
+        female_only,
+        circuits: circuit_reports,
+        examiners: examiner_report,
+        tag_matches: tag_matches(allocations, stations, examiners),
+    }
+}
+
+async fn report( // quality of the live allocation, or of a batch from its history
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Query(query): Query<ReportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = query.session_id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let allocations: Vec<AllocationPayload> = match query.batch_id {
+        Some(batch_id) => {
+            let history = AllocationHistory::get_by_batch(&pool, &session_id, &batch_id).await?;
+            if history.is_empty() {
+                return Ok((StatusCode::NOT_FOUND, "Allocation batch not found").into_response())
+            }
+            history.into_iter().map(AllocationPayload::from).collect()
+        },
+        None => Allocation::get_by_session(&pool, &session_id).await?.into_iter().map(AllocationPayload::from).collect(),
+    };
+    let slot_plans = SlotPlan::get_all_by_session(&pool, &session_id).await?;
+    let stations = Station::get_by_session(&pool, &session_id).await?;
+    let (candidate_result, examiner_result, placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_all_by_session(&pool, &session_id),
+        Examiner::get_all_by_session(&pool, &session_id),
+        Candidate::get_placeholders_by_session(&pool, &session_id),
+        Examiner::get_placeholders_by_session(&pool, &session_id)
+    );
+    let candidates: Vec<Candidate> = candidate_result?.into_iter().chain(placeholder_candidates?).collect();
+    let examiners: Vec<Examiner> = examiner_result?.into_iter().chain(placeholder_examiners?).collect();
+
+    let mut result = quality_report(&allocations, &slot_plans, &stations, &candidates, &examiners);
+    result.batch_id = query.batch_id;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn get_history( // batches of a session, newest first
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    session: Query<SomethingID> // session id
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    check_session(&pool, &session.0.id, &claim.organisation_id).await?;
+
+    let result = AllocationHistory::get_batches_by_session(&pool, &session.0.id).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn restore( // makes a previous batch the live allocation, saved as a new batch
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(payload): Json<RestorePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = payload.session_id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let history = AllocationHistory::get_by_batch(&pool, &session_id, &payload.batch_id).await?;
+    if history.is_empty() {
+        return Ok((StatusCode::NOT_FOUND, "Allocation batch not found").into_response())
+    }
+    let allocations: Vec<AllocationPayload> = history.into_iter().map(AllocationPayload::from).collect();
+
+    // people, availability and settings may have changed since the batch was saved, so it is checked like an accepted preview
+    let (candidate_result, examiner_result, placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_all_by_session(&pool, &session_id),
+        Examiner::get_all_by_session(&pool, &session_id),
+        Candidate::get_placeholders_by_session(&pool, &session_id),
+        Examiner::get_placeholders_by_session(&pool, &session_id)
+    );
+    let mut candidates = candidate_result?;
+    let mut examiners = examiner_result?;
+    candidates.extend(placeholder_candidates?);
+    examiners.extend(placeholder_examiners?);
+    let slot_plans = SlotPlan::get_all_by_session(&pool, &session_id).await?;
+    let stations = Station::get_by_session(&pool, &session_id).await?;
+    let actors = Actor::get_all_by_session(&pool, &session_id).await?;
+    let settings = sqlx::query!("SELECT require_examiner_continuity, forbid_repeats, candidates_per_station FROM records.sessions WHERE id = $1", &session_id)
+        .fetch_one(&pool)
+        .await
+        .with_context(|| "Cannot get session settings")?;
+    let exclusions = Exclusion::get_all_by_session(&pool, &session_id).await?;
+    let mut exclusions = Exclusion::resolve(&exclusions, &examiners, &candidates);
+    if settings.forbid_repeats { // same as generate, forbidden repeats are conflicts of interest
+        let past = PastPairings::get_by_session(&pool, &session_id).await?;
+        exclusions.extend(past.resolve_examiners(&examiners, &candidates));
+    }
+    let rules = AllocationRules {
+        slot_plans: &slot_plans,
+        stations: &stations,
+        candidates: &candidates,
+        examiners: &examiners,
+        actors: &actors,
+        exclusions: &exclusions,
+        require_continuity: settings.require_examiner_continuity,
+        group_size: settings.candidates_per_station as usize,
+    };
+    if let Err(message) = rules.validate(&allocations) {
+        return Ok((StatusCode::BAD_REQUEST, format!("Allocation batch cannot be restored: {}", message)).into_response())
+    }
+
+    let batch_id = Uuid::new_v4();
+    let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+    Allocation::add_batch(&mut transaction, &session_id, &allocations, &batch_id, &claim.id, false).await?;
+    sqlx::query!("UPDATE records.sessions SET status = 'allocated' WHERE id = $1", &session_id).execute(&mut *transaction).await.map_err(|err| anyhow!("Failed to change session allocation status: {}", err))?;
+    transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
+
+    let response = GenerateResponse {
+        batch_id,
+        allocations: allocations.len(),
+        fill_candidates: 0,
+        fill_examiners: 0,
+        unmatched_stations: Vec::new(),
+        missing_actors: Vec::new(),
+    };
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+async fn edit( // manual change to the live allocation, checked against the solver's rules
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(payload): Json<EditPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = payload.session_id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
+
+    let slot_plans = SlotPlan::get_all_by_session(&pool, &session_id).await?;
+    let (candidate_result, examiner_result, placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_all_by_session(&pool, &session_id),
+        Examiner::get_all_by_session(&pool, &session_id),
+        Candidate::get_placeholders_by_session(&pool, &session_id),
+        Examiner::get_placeholders_by_session(&pool, &session_id)
+    );
+    let mut candidates = candidate_result?;
+    let mut examiners = examiner_result?;
+    candidates.extend(placeholder_candidates?);
+    examiners.extend(placeholder_examiners?);
+    if let AllocationEdit::FillVacancy { placeholder, person } = &payload.edit { // only placeholders can be replaced, and only by real people
+        let candidate_vacancy = candidates.iter().any(|c| c.id == *placeholder && c.placeholder);
+        let examiner_vacancy = examiners.iter().any(|e| e.id == *placeholder && e.placeholder);
+        if !candidate_vacancy && !examiner_vacancy {
+            return Ok((StatusCode::BAD_REQUEST, "Person to replace is not a placeholder").into_response())
+        }
+        let is_person = if candidate_vacancy {
+            candidates.iter().any(|c| c.id == *person && !c.placeholder)
+        } else {
+            examiners.iter().any(|e| e.id == *person && !e.placeholder)
+        };
+        if !is_person {
+            return Ok((StatusCode::BAD_REQUEST, "Replacement must be a candidate or examiner of the session, of the same kind as the placeholder").into_response())
+        }
+    }
+    let stations = Station::get_by_session(&pool, &session_id).await?;
+    let actors = Actor::get_all_by_session(&pool, &session_id).await?;
+    let settings = sqlx::query!("SELECT require_examiner_continuity, forbid_repeats, candidates_per_station FROM records.sessions WHERE id = $1", &session_id)
+        .fetch_one(&pool)
+        .await
+        .with_context(|| "Cannot get session settings")?;
+    let exclusions = Exclusion::get_all_by_session(&pool, &session_id).await?;
+    let mut exclusions = Exclusion::resolve(&exclusions, &examiners, &candidates);
+    if settings.forbid_repeats { // same as generate, forbidden repeats are conflicts of interest
+        let past = PastPairings::get_by_session(&pool, &session_id).await?;
+        exclusions.extend(past.resolve_examiners(&examiners, &candidates));
+    }
+
+    let mut allocations: Vec<AllocationPayload> = Allocation::get_by_session(&pool, &session_id)
+        .await?
+        .into_iter()
+        .map(AllocationPayload::from)
+        .collect();
+    let rules = AllocationRules {
+        slot_plans: &slot_plans,
+        stations: &stations,
+        candidates: &candidates,
+        examiners: &examiners,
+        actors: &actors,
+        exclusions: &exclusions,
+        require_continuity: settings.require_examiner_continuity,
+        group_size: settings.candidates_per_station as usize,
+    };
+    // rules the live allocation already breaks, e.g. after someone's availability changed, do not block unrelated edits
+    let existing: HashSet<String> = rules.violations(&allocations).into_iter().collect();
+    if let Err(message) = apply_edit(&mut allocations, &payload.edit, &slot_plans) {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response())
+    }
+    if let Some(message) = rules.violations(&allocations).into_iter().find(|message| !existing.contains(message)) {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response())
+    }
+
+    let batch_id = Uuid::new_v4();
+    let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+    Allocation::add_batch(&mut transaction, &session_id, &allocations, &batch_id, &claim.id, false).await?;
+    transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
+
+    let response = GenerateResponse {
+        batch_id,
+        allocations: allocations.len(),
+        fill_candidates: 0,
+        fill_examiners: 0,
+        unmatched_stations: Vec::new(),
+        missing_actors: Vec::new(),
+    };
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+async fn lock( // locks / unlocks allocations of a station so re-solving keeps them
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(payload): Json<LockPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    check_session(&pool, &payload.session_id, &claim.organisation_id).await?;
+
+    let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+    if let Some(pair_locked) = payload.pair_locked { // a group stays at the same station for every run
+        sqlx::query!(
+            r#"
+            UPDATE records.allocations SET pair_locked = $3
+            WHERE circuit_id = $1 AND station_id = $2
+            AND id IN (SELECT allocation_id FROM records.allocation_members)
+            AND slot_id IN (SELECT id FROM records.slots WHERE session_id = $4)
+            "#,
+            payload.circuit_id,
+            payload.station_id,
+            pair_locked,
+            payload.session_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .with_context(|| "Failed to lock group")?;
+    }
+    if let Some(examiner_locked) = payload.examiner_locked { // every run when run_id is None
+        sqlx::query!(
+            r#"
+            UPDATE records.allocations SET examiner_locked = $3
+            WHERE circuit_id = $1 AND station_id = $2 AND examiner IS NOT NULL
+            AND ($4::uuid IS NULL OR run_id = $4)
+            AND slot_id IN (SELECT id FROM records.slots WHERE session_id = $5)
+            "#,
+            payload.circuit_id,
+            payload.station_id,
+            examiner_locked,
+            payload.run_id,
+            payload.session_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .with_context(|| "Failed to lock examiner")?;
+    }
+    transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
+
+    let result = Allocation::get_by_session(&pool, &payload.session_id).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
 }
 
-#[derive(Debug)]
-pub enum PeopleType {
-    Candidate,
-    Examiner,
-}
+async fn reallocate( // refills the gaps left by people who did not check in, changing as little as possible. Saved as a new batch
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(session): Json<SomethingID>, // session id
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let session_id = session.id;
+    check_session(&pool, &session_id, &claim.organisation_id).await?;
 
-// Examiners will just leave AM / PM regardless of overlapping slots in AM&PM
-async fn fill_by_time( // AM & PM EXAMINERS
-    session_id: &Uuid,
-    organisation_id: &Uuid,
-    pool: &sqlx::PgPool,
-    total_am_examiners: usize, // including all-day + AM
-    total_pm_examiners: usize, // including all-day + PM
-    total_am_cap: usize,
-    total_pm_cap: usize,
-    female: bool,
-) -> Result<(Vec<Examiner>, Vec<Examiner>, Vec<Examiner>), AppError> {
-    let mut am_examiner_diff = total_am_cap as isize - total_am_examiners as isize;
-    let mut pm_examiner_diff = total_pm_cap as isize - total_pm_examiners as isize;
-
-    // for AM / PM examiners fulfillment, examiners should include all-day examiners + AM/PM-only examiners
-    // all-day examiners are the only ones who can make it to "ANY" slots too
-    let mut new_full_examiners = Vec::new();
-    let mut new_am_examiners = Vec::new();
-    let mut new_pm_examiners = Vec::new();
-    if am_examiner_diff > 0 && pm_examiner_diff > 0 { // get minimum number of examiners, fill with full-day first
-        let mut common_diff = am_examiner_diff - pm_examiner_diff;
-        if common_diff > 0 || common_diff < 0 {
-            common_diff = cmp::min(am_examiner_diff, pm_examiner_diff);
-        } else {
-            common_diff = cmp::max(am_examiner_diff, pm_examiner_diff);
+    let current = Allocation::get_by_session(&pool, &session_id).await?;
+    if current.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, "Session has no allocation to reallocate").into_response())
+    }
+    let slot_plans = SlotPlan::get_all_by_session(&pool, &session_id).await?;
+    let stations = Station::get_by_session(&pool, &session_id).await?;
+    let examined_stations = Station::get_examined_by_session(&pool, &session_id).await?;
+    let actor_stations = Station::get_with_actor_by_session(&pool, &session_id).await?;
+    let (candidate_result, examiner_result, placeholder_candidates, placeholder_examiners) = tokio::join!(
+        Candidate::get_all_by_session(&pool, &session_id),
+        Examiner::get_all_by_session(&pool, &session_id),
+        Candidate::get_placeholders_by_session(&pool, &session_id),
+        Examiner::get_placeholders_by_session(&pool, &session_id)
+    );
+    let actors: Vec<Actor> = Actor::get_all_by_session(&pool, &session_id).await?.into_iter().filter(|a| a.checked_in).collect();
+
+    // placeholders never check in, so they always count as present. Present people not in the allocation are reserves
+    let candidates: Vec<Candidate> = candidate_result?.into_iter().chain(placeholder_candidates?).filter(|c| c.placeholder || c.checked_in).collect();
+    let examiners: Vec<Examiner> = examiner_result?.into_iter().chain(placeholder_examiners?).filter(|e| e.placeholder || e.checked_in).collect();
+    let present_candidates: HashSet<Uuid> = candidates.iter().map(|c| c.id).collect();
+    let present_examiners: HashSet<Uuid> = examiners.iter().map(|e| e.id).collect();
+    let absent_candidates: HashSet<Uuid> = current.iter().flat_map(|a| a.candidates.iter().copied()).filter(|id| !present_candidates.contains(id)).collect();
+    let absent_examiners: HashSet<Uuid> = current.iter().filter_map(|a| a.examiner).filter(|id| !present_examiners.contains(id)).collect();
+
+    // worst case placeholders: 1 candidate per absent candidate, and enough examiners for the busiest run of each slot
+    let mut fills = Fills::default();
+    for plan in &slot_plans {
+        let slot_rows: Vec<&Allocation> = current.iter().filter(|a| a.slot_id == plan.slot.id).collect();
+        let slot_absent = absent_candidates.iter().filter(|id| slot_rows.iter().any(|a| a.candidates.contains(id))).count();
+        for _ in 0..slot_absent {
+            fills.add(&session_id, &PeopleType::Candidate, plan.time(), true);
         }
-        for _ in 0..common_diff {
-            let new_examiner = super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, None, female).await?;
-            new_full_examiners.push(new_examiner);
+        let absent_in_run = |run: &Run, female_only: bool| {
+            slot_rows
+                .iter()
+                .filter(|a| a.run_id == run.id && a.examiner.is_some_and(|id| absent_examiners.contains(&id)))
+                .filter(|a| plan.circuits.iter().any(|c| c.id == a.circuit_id && c.female_only == female_only))
+                .count()
+        };
+        let runs: Vec<&Run> = plan.am_runs.iter().chain(plan.pm_runs.iter()).collect();
+        let female_examiners = runs.iter().map(|run| absent_in_run(run, true)).max().unwrap_or(0);
+        let other_examiners = runs.iter().map(|run| absent_in_run(run, false)).max().unwrap_or(0);
+        for female in std::iter::repeat_n(true, female_examiners).chain(std::iter::repeat_n(false, other_examiners)) {
+            fills.add(&session_id, &PeopleType::Examiner, plan.time(), female);
         }
-        am_examiner_diff -= common_diff;
-        pm_examiner_diff -= common_diff;
     }
-    if am_examiner_diff > 0 { // any standalone AM examiners
-        for _ in 0..am_examiner_diff {
-            let new_examiner = super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: false }), female).await?;
-            new_am_examiners.push(new_examiner);
+    let all_candidates: Vec<Candidate> = candidates.into_iter().chain(fills.candidates.iter().cloned()).collect();
+    let all_examiners: Vec<Examiner> = examiners.into_iter().chain(fills.examiners.iter().cloned()).collect();
+
+    let slot_times: HashMap<Uuid, Availability> = slot_plans.iter().map(|p| (p.slot.id, p.time())).collect();
+    let circuits: Vec<Circuit> = slot_plans.iter().flat_map(|p| p.circuits.iter().cloned()).collect();
+    let am_runs: Vec<Run> = slot_plans.iter().flat_map(|p| p.am_runs.iter().cloned()).collect();
+    let pm_runs: Vec<Run> = slot_plans.iter().flat_map(|p| p.pm_runs.iter().cloned()).collect();
+
+    let mut current_groups: Vec<SlotAllocation> = Vec::new();
+    let mut locked_groups: Vec<SlotAllocation> = Vec::new();
+    for allocation in current.iter().filter(|a| !a.candidates.is_empty()) {
+        if !current_groups.iter().any(|g| g.circuit_id == allocation.circuit_id && g.station_id == allocation.station_id) { // same group in every run
+            let group = SlotAllocation { circuit_id: allocation.circuit_id, station_id: allocation.station_id, candidates: allocation.candidates.clone() };
+            if allocation.pair_locked {
+                locked_groups.push(group.clone());
+            }
+            current_groups.push(group);
         }
     }
-    if pm_examiner_diff > 0 { // any standalone PM examiners
-        for _ in 0..pm_examiner_diff {
-            let new_examiner = super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: false, pm: true }), female).await?;
-            new_pm_examiners.push(new_examiner);
-        }
+    let current_examiners: Vec<TimeAllocation> = current
+        .iter()
+        .filter_map(|a| a.examiner.map(|examiner| TimeAllocation { run_id: a.run_id, circuit_id: a.circuit_id, station_id: a.station_id, examiner }))
+        .collect();
+    let current_actors: Vec<ActorAllocation> = current
+        .iter()
+        .filter_map(|a| a.actor.map(|actor| ActorAllocation { run_id: a.run_id, circuit_id: a.circuit_id, station_id: a.station_id, actor }))
+        .collect();
+    let absent_actors = current_actors.iter().map(|a| a.actor).filter(|id| !actors.iter().any(|a| a.id == *id)).collect::<HashSet<Uuid>>().len();
+
+    let settings = sqlx::query!("SELECT forbid_repeats, candidates_per_station FROM records.sessions WHERE id = $1", &session_id)
+        .fetch_one(&pool)
+        .await
+        .with_context(|| "Cannot get session settings")?;
+    let group_size = settings.candidates_per_station as usize;
+    let exclusions = Exclusion::get_all_by_session(&pool, &session_id).await?;
+    let mut exclusions = Exclusion::resolve(&exclusions, &all_examiners, &all_candidates);
+    if settings.forbid_repeats {
+        let past = PastPairings::get_by_session(&pool, &session_id).await?;
+        exclusions.extend(past.resolve_examiners(&all_examiners, &all_candidates));
     }
-    Ok((new_full_examiners, new_am_examiners, new_pm_examiners))
-}
-
-async fn fill_by_slot(  // EXAMINERS ONLY
-    session_id: &Uuid,
-    organisation_id: &Uuid,
-    pool: &sqlx::PgPool,
-    total_examiners: usize, // includes all examiners (female + not)
-    total_female_examiners: usize,
-    exam_circuit_cap: usize,
-    exam_female_circuit_cap: usize,
-    am: bool, // fill with slot's timing. E.g: AM-only: am=true, pm=false
-    pm: bool,
- ) -> Result<usize, AppError> {
-    // female examiners
-    let mut total_examiners = total_examiners.clone();
-    let female_examiner_diff = exam_female_circuit_cap as isize - total_female_examiners as isize;
-    let examiner_diff = exam_circuit_cap as isize - total_examiners as isize;
-    if total_female_examiners > exam_female_circuit_cap {
-        trace!("There are more female examiners than capacity. {} over from {}", total_female_examiners, exam_female_circuit_cap);
-        return Ok(total_female_examiners - exam_female_circuit_cap);
-    } else if total_female_examiners < exam_female_circuit_cap {
-        for _ in 0..female_examiner_diff {
-            let _ = super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am, pm, }), true).await?;
-            total_examiners += 1;
-        }
+    // candidates cannot move into a circuit where an examiner who stays is excluded from them
+    let blocked: Vec<(Uuid, Uuid)> = current_examiners
+        .iter()
+        .filter(|a| present_examiners.contains(&a.examiner))
+        .flat_map(|a| exclusions.iter().filter(move |(examiner_id, _)| *examiner_id == a.examiner).map(move |(_, candidate_id)| (*candidate_id, a.circuit_id)))
+        .collect();
+
+    let layout = Layout { circuits: &circuits, slot_times: &slot_times, am_runs: &am_runs, pm_runs: &pm_runs, group_size };
+    // same report as generate, but only for examiners: present candidates left over are reserves, not a failure
+    let infeasibilities: Vec<_> = check_feasibility(&layout, &stations, &examined_stations, &all_candidates, &all_examiners)
+        .into_iter()
+        .filter(|i| !matches!(i.condition, Condition::UngroupedCandidates | Condition::CandidateCapacity))
+        .collect();
+    if !infeasibilities.is_empty() {
+        return Err(AppError::Infeasible(infeasibilities));
     }
-
-    // all examiners
-    if total_examiners > exam_circuit_cap {
-        trace!("There are more examiners than capacity. {} over from {}", total_examiners, exam_circuit_cap);
-        return Ok(total_examiners - exam_circuit_cap);
-    } else if total_examiners < exam_circuit_cap {
-        for _ in 0..examiner_diff {
-            let _ = super::examiners::create_fill(session_id.clone(), organisation_id.clone(), &pool, Some(Availability { am, pm }), false).await?;
-        }
+    let candidate_allocations = reallocate_by_slot(&layout, &stations, &all_candidates, &current_groups, &locked_groups, &blocked)?;
+    let examiner_allocations = reallocate_by_time(&layout, &examined_stations, &all_examiners, &current_examiners, &candidate_allocations, &exclusions)?;
+    let actor_allocations = allocate_actors(&layout, &actor_stations, &actors, &current_actors)?;
+
+    let runs: Vec<&Run> = am_runs.iter().chain(pm_runs.iter()).collect();
+    let mut allocations = merge_allocations(&circuits, &stations, &runs, candidate_allocations, examiner_allocations, actor_allocations);
+    for allocation in allocations.iter_mut() { // locks are kept where the same people stayed
+        let before = current.iter().find(|a| a.run_id == allocation.run_id && a.circuit_id == allocation.circuit_id && a.station_id == allocation.station_id);
+        allocation.pair_locked = before.is_some_and(|a| a.pair_locked && a.candidates == allocation.candidates);
+        allocation.examiner_locked = before.is_some_and(|a| a.examiner_locked && a.examiner == allocation.examiner);
     }
-    Ok(0)
-}
-
-async fn fill_slot_fixed_time(  // CANDIDATES ONLY. for sessions with 1 slot with runs in either AM or PM
-    session_id: &Uuid,
-    organisation_id: &Uuid,
-    pool: &sqlx::PgPool,
-    total_candidates: usize,
-    total_female_candidates: usize,
-    can_circuit_cap: usize,
-    can_female_circuit_cap: usize,
-    am: bool,
- ) -> Result<(), AppError> {
-    // female candidates
-    let mut total_candidates = total_candidates.clone();
-    if total_female_candidates > can_female_circuit_cap {
-        return Err(AppError::from(anyhow!("There are more AM/PM female candidates than capacity. {} over from {}", total_female_candidates, can_female_circuit_cap)));
-    } else if total_female_candidates < can_female_circuit_cap {
-        if total_female_candidates % 2 != 0 {
-            let _ = super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: am, pm: !am }), true).await?;
-            total_candidates += 1;
-        }
+    assign_roles(&mut allocations, &runs);
+    let placeholders: Vec<Uuid> = all_candidates.iter().filter(|c| c.placeholder).map(|c| c.id)
+        .chain(all_examiners.iter().filter(|e| e.placeholder).map(|e| e.id))
+        .collect();
+    mark_vacancies(&mut allocations, &placeholders);
+
+    fills.candidates.retain(|c| allocations.iter().any(|a| a.candidates.contains(&c.id)));
+    fills.examiners.retain(|e| allocations.iter().any(|a| a.examiner == Some(e.id)));
+    // continuity is not checked, on the day a gap can be covered by a different examiner in each run
+    let rules = AllocationRules {
+        slot_plans: &slot_plans,
+        stations: &stations,
+        candidates: &all_candidates,
+        examiners: &all_examiners,
+        actors: &actors,
+        exclusions: &exclusions,
+        require_continuity: false,
+        group_size,
+    };
+    if let Err(message) = rules.validate(&allocations) {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response())
     }
 
-    // all candidates
-    if total_candidates > can_circuit_cap {
-        return Err(AppError::from(anyhow!("There are more candidates than capacity. {} over from {}", total_candidates, can_circuit_cap)));
-    } else if total_candidates < can_circuit_cap {
-        if total_candidates % 2 != 0 {
-            let _ = super::candidates::create_fill(session_id.clone(), organisation_id.clone(), &pool, Some(Availability { am: am, pm: !am }), false).await?;
+    let diff = diff_allocations(&current, &allocations);
+    let mut saved = save_allocation(&pool, &session_id, &claim.id, &fills, &allocations).await?;
+    saved.missing_actors = missing_actors(&allocations, &circuits, &actor_stations, &runs);
+    let response = ReallocateResponse {
+        saved,
+        absent_candidates: absent_candidates.len(),
+        absent_examiners: absent_examiners.len(),
+        absent_actors,
+        diff,
+    };
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+fn set_group( // puts a group (or nobody, when empty) at a station for every run of the circuit
+    allocations: &mut Vec<AllocationPayload>,
+    plan: &SlotPlan,
+    circuit_id: Uuid,
+    station_id: Uuid,
+    group: &[Uuid],
+) {
+    for run in plan.am_runs.iter().chain(plan.pm_runs.iter()) {
+        let row = allocations
+            .iter_mut()
+            .find(|a| a.run_id == run.id && a.circuit_id == circuit_id && a.station_id == station_id);
+        match row {
+            Some(row) => {
+                row.candidates = group.to_vec();
+            },
+            None => if !group.is_empty() { // stations without an examiner have no row without a group
+                allocations.push(AllocationPayload {
+                    slot_id: plan.slot.id,
+                    run_id: run.id,
+                    circuit_id,
+                    station_id,
+                    candidates: group.to_vec(),
+                    examiner: None,
+                    actor: None,
+                    pair_locked: false,
+                    examiner_locked: false,
+                    performer: None,
+                    observers: Vec::new(),
+                    vacancies: Vec::new(),
+                });
+            },
         }
     }
-    Ok(())
 }
 
-fn is_odd(n: usize) -> bool {
-    n & 1 != 0 // even: LSB = 0, odd: LSB = 1
-}
-
-/// x_split, y_split, z_split, x_diff, y_diff, z_diff
-fn even_split(
-    total_count: usize,
-    mut x_fix_count: usize, // am
-    mut y_fix_count: usize, // pm
-    mut z_flex_count: usize, // any
-    z_fix_cap: usize, // any-cap
-) -> Result<(usize, usize, usize, usize, usize, usize), AppError> {
-    x_fix_count += x_fix_count & 1;
-    y_fix_count += y_fix_count & 1;
-    z_flex_count = z_flex_count.saturating_sub(x_fix_count & 1 + y_fix_count & 1);
-
-    if z_fix_cap == 0 {
-        // 2 sets
-        let target_split = total_count / 2;
-        let mut x_split = target_split;
-        let mut y_split = target_split;
-
-        if x_split & 1 != x_fix_count & 1 {
-            if x_split > 0 {
-                x_split -= 1;
-                y_split += 1;
-            } else {
-                x_split += 1;
-                y_split -= 1;
+fn apply_edit(
+    allocations: &mut Vec<AllocationPayload>,
+    edit: &AllocationEdit,
+    slot_plans: &[SlotPlan],
+) -> Result<(), String> {
+    match edit {
+        AllocationEdit::SwapCandidates { candidate_a, candidate_b } => {
+            if candidate_a == candidate_b {
+                return Err("Cannot swap a candidate with themselves".to_string());
             }
-        }
-        
-        if y_split & 1 != y_fix_count & 1 {
-            if y_split > 0 {
-                y_split -= 1;
-                x_split += 1;
-            } else {
-                return Err(AppError::from(anyhow!("y_split = 0")));
+            let swap = |candidate: Uuid| match candidate {
+                id if id == *candidate_a => *candidate_b,
+                id if id == *candidate_b => *candidate_a,
+                other => other,
+            };
+            let changes = |a: &AllocationPayload| a.candidates.iter().any(|&id| swap(id) != id);
+            if !allocations.iter().any(changes) {
+                return Err("Neither candidate is allocated".to_string());
             }
-        }
-        
-        // find the z-flex split into each x and y respectively
-        let x_diff = x_split.checked_sub(x_fix_count).ok_or_else(|| anyhow!("Underflow: x_split = {}, x_fix_count = {}", x_split, x_fix_count))?;
-        let y_diff = y_split.checked_sub(y_fix_count).ok_or_else(|| anyhow!("Underflow: y_split = {}, y_fix_count = {}", y_split, y_fix_count))?;
-        
-        let all_sum = x_diff.checked_add(y_diff).ok_or_else(|| anyhow!("Overflow in all_sum"))?;
-        if all_sum != z_flex_count || x_split & 1 != 0 || y_split & 1 != 0 {
-            trace!("Invalid split: all_sum = {}, z_flex_count = {}, x_split = {}, y_split = {}", all_sum, z_flex_count, x_split, y_split);
-            if all_sum < z_flex_count {
-                let diff = z_flex_count - all_sum;
-                if x_split > y_split {
-                    x_split += diff;
-                } else {
-                    y_split += diff;
-                }
+            if allocations.iter().any(|a| a.pair_locked && changes(a)) {
+                return Err("Candidate is in a locked group, unlock it first".to_string());
             }
-        }
-        Ok((x_split, y_split, z_fix_cap, x_diff, y_diff, z_fix_cap))
-    } else {
-        // 3 sets
-        if z_flex_count > z_fix_cap {
-            return Err(AppError::from(anyhow!(
-                "z_flex_count = {} exceeds z_fix_cap = {}",
-                z_flex_count,
-                z_fix_cap
-            )));
-        }
-
-        let base_split = (total_count / 3) & !1; // round down to nearest even number
-        let mut remainder = total_count - base_split * 3; // 0, 2, or 4
-
-        let mut x_split = base_split;
-        let mut y_split = base_split;
-        let mut z_split = base_split;
-
-        if remainder >= 2 {
-            x_split += 2;
-            remainder -= 2;
-        }
-        if remainder >= 2 {
-            y_split += 2;
-            remainder -= 2;
-        }
-
-        let x_diff_needed = x_split.checked_sub(x_fix_count)
-            .ok_or_else(|| anyhow!("Underflow: x_split = {}, x_fix_count = {}", x_split, x_fix_count))?;
-        let y_diff_needed = y_split.checked_sub(y_fix_count)
-            .ok_or_else(|| anyhow!("Underflow: y_split = {}, y_fix_count = {}", y_split, y_fix_count))?;
-        let mut x_diff = x_diff_needed;
-        let mut y_diff = y_diff_needed;
-        let mut z_diff = z_flex_count.saturating_sub(x_diff + y_diff);
-        z_split = z_diff;
-
-        x_split = x_fix_count.checked_add(x_diff)
-            .ok_or_else(|| anyhow!("Overflow in x_split"))?;
-        y_split = y_fix_count.checked_add(y_diff)
-            .ok_or_else(|| anyhow!("Overflow in y_split"))?;
-
-        // total assigned so far
-        let mut total_assigned = x_diff.checked_add(y_diff)
-            .and_then(|sum| sum.checked_add(z_diff))
-            .ok_or_else(|| anyhow!("Overflow in total_assigned"))?;
-
-        // Minimising differences between x, y, z splits
-        while total_assigned != z_flex_count || x_split & 1 != 0 || y_split & 1 != 0 || z_split & 1 != 0 {
-            if total_assigned > z_flex_count {
-                return Err(AppError::from(anyhow!("Overassigned z_flex_count")));
+            for allocation in allocations.iter_mut() {
+                allocation.candidates = allocation.candidates.iter().map(|&id| swap(id)).collect();
             }
-
-            let max_split = x_split.max(y_split).max(z_split);
-            let min_split = x_split.min(y_split).min(z_split);
-
-            if max_split - min_split > 2 && total_assigned < z_flex_count {
-                let available = z_flex_count - total_assigned;
-                if available >= 2 {
-                    if max_split == x_split && x_diff > 0 {
-                        x_split -= 2;
-                        x_diff -= 2;
-                        if y_split == min_split {
-                            y_split += 2;
-                            y_diff += 2;
-                        } else {
-                            z_split += 2;
-                            z_diff += 2;
-                        }
-                    } else if max_split == y_split && y_diff > 0 {
-                        y_split -= 2;
-                        y_diff -= 2;
-                        if x_split == min_split {
-                            x_split += 2;
-                            x_diff += 2;
-                        } else {
-                            z_split += 2;
-                            z_diff += 2;
-                        }
-                    } else if max_split == z_split && z_diff > 0 {
-                        z_split -= 2;
-                        z_diff -= 2;
-                        if x_split == min_split {
-                            x_split += 2;
-                            x_diff += 2;
-                        } else {
-                            y_split += 2;
-                            y_diff += 2;
-                        }
-                    }
-                }
-            } else if total_assigned < z_flex_count {
-                let remaining = z_flex_count - total_assigned;
-                if remaining >= 2 {
-                    if x_split == min_split {
-                        x_split += 2;
-                        x_diff += 2;
-                    } else if y_split == min_split {
-                        y_split += 2;
-                        y_diff += 2;
-                    } else {
-                        z_split += 2;
-                        z_diff += 2;
-                    }
-                } else {
-                    // Can't add more without exceeding
-                    break;
-                }
-            } else {
-                // All assigned, check evenness
-                break;
+        },
+        AllocationEdit::SwapExaminers { examiner_a, examiner_b, run_id } => {
+            if examiner_a == examiner_b {
+                return Err("Cannot swap an examiner with themselves".to_string());
             }
-
-            total_assigned = x_diff.checked_add(y_diff)
-                .and_then(|sum| sum.checked_add(z_diff))
-                .ok_or_else(|| anyhow!("Overflow in total_assigned"))?;
-        }
-
-        // Final validation
-        if total_assigned != z_flex_count || x_split & 1 != 0 || y_split & 1 != 0 || z_split & 1 != 0 {
-            trace!("Invalid 3-way split: total_assigned = {}, z_flex_count = {}, x_split = {}, y_split = {}, z_split = {}",
-                total_assigned, z_flex_count, x_split, y_split, z_split);
-            if total_assigned < z_flex_count {
-                let diff = z_flex_count - total_assigned;
-                let min_split = cmp::min(x_split, cmp::min(y_split, z_split));
-                if x_split == min_split {
-                    x_split += diff;
-                } else if y_split == min_split {
-                    y_split += diff;
-                } else {
-                    z_split += diff;
-                }
+            let swap = |examiner: Option<Uuid>| match examiner {
+                Some(id) if id == *examiner_a => Some(*examiner_b),
+                Some(id) if id == *examiner_b => Some(*examiner_a),
+                other => other,
+            };
+            if allocations.iter().any(|a| a.examiner_locked && run_id.is_none_or(|id| a.run_id == id) && a.examiner != swap(a.examiner)) {
+                return Err("Examiner is locked, unlock them first".to_string());
             }
-        }
-
-        Ok((x_split, y_split, z_split, x_diff, y_diff, z_diff))
-    }
-    
-}
-
-async fn fill_any_priority(
-    session_id: &Uuid,
-    organisation_id: &Uuid,
-    pool: &sqlx::PgPool,
-    ppl_type: PeopleType, 
-    total_count: usize,
-    x_fix_count: usize, // am_only
-    x_cap: usize, // am_cap
-    y_fix_count: usize, // pm_only
-    y_cap: usize, //pm_cap
-    z_flex_count: usize, // any
-    z_cap: usize, // any_cap
-) -> Result<(), AppError> { // sorry this part is cooked af frfr, defo need that refactoring
-    if is_odd(total_count) {
-        let odd_count = is_odd(x_fix_count) as isize + is_odd(y_fix_count) as isize + is_odd(z_flex_count) as isize;
-        if z_cap == 0 {
-            // total_count = x_fix_count + y_fix_count + z_flex_count
-            let (x_split, y_split, ..) = even_split(total_count, x_fix_count, y_fix_count, z_flex_count, z_cap)?;
-            // either all 3 are odd, or 1 of them is odd
-            if odd_count == 1 {
-                if is_odd(x_fix_count) {
-                    if x_fix_count > x_cap {
-                        return Err(AppError::from(anyhow!("There are more x_fix_count than capacity. {} over from {}", x_fix_count, x_cap)));
-                    } else if x_fix_count < x_cap {
-                        match ppl_type {
-                            PeopleType::Candidate => {
-                                super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: false }), true).await?;
-                            },
-                            PeopleType::Examiner => {
-                                super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: false }), true).await?;
-                            },
-                        };
-                    }
-                } else if is_odd(y_fix_count) {
-                    if y_fix_count > y_cap {
-                        return Err(AppError::from(anyhow!("There are more y_fix_count than capacity. {} over from {}", y_fix_count, y_cap)));
-                    } else if y_fix_count < y_cap {
-                        match ppl_type {
-                            PeopleType::Candidate => {
-                                super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: false, pm: true }), true).await?;
-                            },
-                            PeopleType::Examiner => {
-                                super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: false, pm: true }), true).await?;
-                            }
-                        }
-                    }
-                } else if is_odd(z_flex_count) {
-                    if x_split < x_cap || y_split < y_cap {
-                        match ppl_type {
-                            PeopleType::Candidate => {
-                                super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                            },
-                            PeopleType::Examiner => {
-                                super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                            }
-                        }
-                    } else if x_split > x_cap || y_split > y_cap {
-                        return Err(AppError::from(anyhow!("There are more total_count than capacity. x: {} over from {} | y: {} over from {}", x_split, x_cap, y_split, y_cap)));
-                    }
-                }
+            let mut swapped = false;
+            for allocation in allocations.iter_mut().filter(|a| run_id.is_none_or(|id| a.run_id == id)) {
+                let examiner = swap(allocation.examiner);
+                swapped |= examiner != allocation.examiner;
+                allocation.examiner = examiner;
             }
-            if odd_count == 3 { // only need 1 addition
-                if x_split < x_cap || y_split < y_cap {
-                    match ppl_type {
-                        PeopleType::Candidate => {
-                            super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                        },
-                        PeopleType::Examiner => {
-                            super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                        }
-                    }
-                } else if x_split > x_cap || y_split > y_cap {
-                    return Err(AppError::from(anyhow!("There are more total_count than capacity. x: {} over from {} | y: {} over from {}", x_split, x_cap, y_split, y_cap)));
-                }
+            if !swapped {
+                return Err("Neither examiner is allocated".to_string());
             }
-        } else {
-            if odd_count == 1 {
-                if is_odd(x_fix_count) {
-                    if x_fix_count > x_cap {
-                        return Err(AppError::from(anyhow!("There are more x_fix_count than capacity. {} over from {}", x_fix_count, x_cap)));
-                    } else if x_fix_count < x_cap {
-                        if z_flex_count > 0 { // any_count must be 0 or even
-                            match ppl_type {
-                                PeopleType::Candidate => {
-                                    super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                                },
-                                PeopleType::Examiner => {
-                                    super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                                }
-                            }
-                        } else {
-                            match ppl_type {
-                                PeopleType::Candidate => {
-                                    super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: false }), true).await?;
-                                },
-                                PeopleType::Examiner => {
-                                    super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: false }), true).await?;
-                                },
-                            };
-                        }
-                    }
-                } else if is_odd(y_fix_count) {
-                    if y_fix_count > y_cap {
-                        return Err(AppError::from(anyhow!("There are more y_fix_count than capacity. {} over from {}", y_fix_count, y_cap)));
-                    } else if y_fix_count < y_cap {
-                        if z_flex_count > 0 { // any_count must be 0 or even
-                            match ppl_type {
-                                PeopleType::Candidate => {
-                                    super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                                },
-                                PeopleType::Examiner => {
-                                    super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                                }
-                            }
-                        } else {
-                            match ppl_type {
-                                PeopleType::Candidate => {
-                                    super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: false, pm: true }), true).await?;
-                                },
-                                PeopleType::Examiner => {
-                                    super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: false, pm: true }), true).await?;
-                                }
-                            }
-                        }
-                    }
-                } else if is_odd(z_flex_count) {
-                    // currently prefer filling with ANY (more flexability, less hard-coded logic)
-                    match ppl_type {
-                        PeopleType::Candidate => {
-                            super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                        },
-                        PeopleType::Examiner => {
-                            super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                        }
-                    }
-                }
+        },
+        AllocationEdit::SwapActors { actor_a, actor_b, run_id } => {
+            if actor_a == actor_b {
+                return Err("Cannot swap an actor with themselves".to_string());
             }
-            if odd_count == 3 {
-                if x_fix_count < x_cap && y_fix_count < y_cap {
-                    match ppl_type {
-                        PeopleType::Candidate => {
-                            super::candidates::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                        },
-                        PeopleType::Examiner => {
-                            super::examiners::create_fill(session_id.clone(), organisation_id.clone(), pool, Some(Availability { am: true, pm: true }), true).await?;
-                        }
+            let swap = |actor: Option<Uuid>| match actor {
+                Some(id) if id == *actor_a => Some(*actor_b),
+                Some(id) if id == *actor_b => Some(*actor_a),
+                other => other,
+            };
+            let mut swapped = false;
+            for allocation in allocations.iter_mut().filter(|a| run_id.is_none_or(|id| a.run_id == id)) {
+                let actor = swap(allocation.actor);
+                swapped |= actor != allocation.actor;
+                allocation.actor = actor;
+            }
+            if !swapped {
+                return Err("Neither actor is allocated".to_string());
+            }
+        },
+        AllocationEdit::MoveGroup { candidate_id, circuit_id, station_id } => {
+            let source = allocations
+                .iter()
+                .find(|a| a.candidates.contains(candidate_id))
+                .map(|a| (a.slot_id, a.circuit_id, a.station_id, a.candidates.clone()))
+                .ok_or_else(|| "Candidate is not allocated".to_string())?;
+            let (source_slot, source_circuit, source_station, source_group) = source;
+            if source_circuit == *circuit_id && source_station == *station_id {
+                return Err("Group is already at this station".to_string());
+            }
+            let target_plan = slot_plans
+                .iter()
+                .find(|p| p.circuits.iter().any(|c| c.id == *circuit_id))
+                .ok_or_else(|| "Circuit not found in session".to_string())?;
+            let source_plan = slot_plans
+                .iter()
+                .find(|p| p.slot.id == source_slot)
+                .ok_or_else(|| "Slot not found in session".to_string())?;
+            // the group already at the target (if any) takes the moved group's old station
+            let target_group = allocations
+                .iter()
+                .find(|a| a.circuit_id == *circuit_id && a.station_id == *station_id)
+                .map(|a| a.candidates.clone())
+                .unwrap_or_default();
+            if allocations.iter().any(|a| a.pair_locked && ((a.circuit_id == source_circuit && a.station_id == source_station) || (a.circuit_id == *circuit_id && a.station_id == *station_id))) {
+                return Err("Group is locked, unlock it first".to_string());
+            }
+
+            set_group(allocations, source_plan, source_circuit, source_station, &target_group);
+            set_group(allocations, target_plan, *circuit_id, *station_id, &source_group);
+        },
+        AllocationEdit::FillVacancy { placeholder, person } => {
+            if allocations.iter().any(|a| a.candidates.contains(person) || a.examiner == Some(*person)) {
+                return Err("Replacement is already allocated".to_string());
+            }
+            let mut replaced = false;
+            for allocation in allocations.iter_mut() {
+                for id in allocation.candidates.iter_mut().chain(allocation.examiner.iter_mut()) {
+                    if *id == *placeholder {
+                        *id = *person;
+                        replaced = true;
                     }
-                } else if x_fix_count > x_cap {
-                    return Err(AppError::from(anyhow!("There are more x_fix_count than capacity. {} over from {}", x_fix_count, x_cap)));
-                } else if y_fix_count > y_cap {
-                    return Err(AppError::from(anyhow!("There are more y_fix_count than capacity. {} over from {}", y_fix_count, y_cap)));
                 }
             }
-        }
+            if !replaced {
+                return Err("Placeholder is not allocated".to_string());
+            }
+        },
     }
+    // stations without an examiner, an actor or a group have no row
+    allocations.retain(|a| !a.candidates.is_empty() || a.examiner.is_some() || a.actor.is_some());
     Ok(())
 }
 
-async fn gen_new( // for static/initial allocation
-    State(pool): State<sqlx::PgPool>,
-    Extension(claim): Extension<AccessClaims>,
-    session: Query<SomethingID> // session id
-) -> Result<impl IntoResponse, AppError> {
-    if !User::is_admin(&pool, &claim.id).await? {
-        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
-    }
-    let session_id = session.0.id;
-
-    let slots = Slot::get_all_by_session(&pool, &session_id).await?;
-    let stations = Station::get_by_session(&pool, &session_id).await?;
-    let stations_not_rest = Station::get_not_rest_by_session(&pool, &session_id).await?;
-
-    let batch_id = Uuid::new_v4();
-    let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
-
-    // how to manage overflow examiners? go ahead?
-    match slots.len() {
-        1 => { // as only 1 slot, check if any runs are in AM or PM, as all candidates need to be there for its full duration
-            trace!("1 Slot Only");
-            let cur_slot = &slots[0];
-            let (circuit_result, female_circuit_result, am_runs_result, pm_runs_result) = tokio::join!(
-                Circuit::get_by_slot(&pool, &cur_slot.id),
-                Circuit::get_female_slot(&pool, &cur_slot.id),
-                Run::get_by_time(&pool, &cur_slot.id, RunTime::AM),
-                Run::get_by_time(&pool, &cur_slot.id, RunTime::PM)
-            );
-            let circuits = circuit_result?;
-            let female_circuits = female_circuit_result?; 
-            let am_runs = am_runs_result?;
-            let pm_runs = pm_runs_result?;
-
-            let am_runs_count = am_runs.len();
-            let pm_runs_count = pm_runs.len();
-            let mut examiners = Vec::new();
-            let mut candidates = Vec::new();
-            let exam_circuit_cap = circuits.len() * stations_not_rest.len();
-            let can_circuit_cap = circuits.len() * stations.len() * 2;
-            let exam_female_circuit_cap = female_circuits.len() * stations_not_rest.len();
-            let can_female_circuit_cap = female_circuits.len() * stations.len() * 2;
-
-            if am_runs_count > 0 && pm_runs_count == 0 { // Slot is AM runs only
-                trace!("AM runs only");
-                // candidates
-                let (candidate_result, female_candidate_result) = tokio::join!(
-                    Candidate::get_all_by_time(&pool, &session_id, RunTime::AM),
-                    Candidate::get_all_female_by_time(&pool, &session_id, RunTime::AM)
-                );
-                candidates = candidate_result?;
-                let female_candidates = female_candidate_result?;
-                fill_slot_fixed_time(&session_id, &claim.organisation_id, &pool, candidates.len(), female_candidates.len(), can_circuit_cap, can_female_circuit_cap, true).await?;
-                candidates = Candidate::get_all_by_time(&pool, &session_id, RunTime::AM).await?;
-
-                // female examiners
-                let female_examiners = Examiner::get_all_female_by_time(&pool, &session_id, RunTime::AM).await?;
-                let total_female_examiners = female_examiners.len();
-                if total_female_examiners > exam_female_circuit_cap {
-                    trace!("There are more AM female examiners than capacity. Checking if there's space in non-female circuits");
-                    let am_examiners = Examiner::get_all_by_time(&pool, &session_id, RunTime::AM).await?;
-                    if am_examiners.len() >= exam_circuit_cap { // if there's no way to move them
-                        return Err(AppError::from(anyhow!("There are more AM examiners than capacity. {} over from {}", am_examiners.len(), exam_circuit_cap)));
-                    }
-                } else if total_female_examiners < exam_female_circuit_cap {
-                    if total_female_examiners % 2 != 0 {
-                        let _ = super::examiners::create_fill(session_id.clone(), claim.organisation_id.clone(), &pool, Some(Availability { am: true, pm: false }), true).await?;
-                    }
-                }
+struct AllocationRules<'a> { // the rules enforced by the solver, to check allocations that did not come from it
+    slot_plans: &'a [SlotPlan],
+    stations: &'a [Station],
+    candidates: &'a [Candidate],
+    examiners: &'a [Examiner],
+    actors: &'a [Actor],
+    exclusions: &'a [(Uuid, Uuid)], // (examiner id, candidate id)
+    require_continuity: bool,
+    group_size: usize, // candidates per station
+}
 
-                // all examiners
-                examiners = Examiner::get_all_by_time(&pool, &session_id, RunTime::AM).await?;
-                let total_examiners = examiners.len();
-                if total_examiners > exam_circuit_cap {
-                    return Err(AppError::from(anyhow!("There are more examiners than capacity. {} over from {}", total_examiners, exam_circuit_cap)));
-                } else if total_examiners < exam_circuit_cap {
-                    if total_examiners % 2 != 0 {
-                        let new_examiner = super::examiners::create_fill(session_id.clone(), claim.organisation_id.clone(), &pool, Some(Availability { am: true, pm: false }), false).await?;
-                        examiners.push(new_examiner);
-                    }
-                }
-            } else if am_runs_count == 0 && pm_runs_count > 0 { // Slot is PM runs only
-                trace!("PM runs only");
-                // candidates
-                let (candidate_result, female_candidate_result) = tokio::join!(
-                    Candidate::get_all_by_time(&pool, &session_id, RunTime::PM),
-                    Candidate::get_all_female_by_time(&pool, &session_id, RunTime::PM)
-                );
-                candidates = candidate_result?;
-                let female_candidates = female_candidate_result?;
-                fill_slot_fixed_time(&session_id, &claim.organisation_id, &pool, candidates.len(), female_candidates.len(), can_circuit_cap, can_female_circuit_cap, false).await?;
-                candidates = Candidate::get_all_by_time(&pool, &session_id, RunTime::PM).await?;
-
-                // female examiners
-                let female_examiners = Examiner::get_all_female_by_time(&pool, &session_id, RunTime::PM).await?;
-                let total_female_examiners = female_examiners.len();
-                if total_female_examiners > exam_female_circuit_cap {
-                    trace!("There are more PM female examiners than capacity. Checking if there's space in non-female circuits");
-                    let pm_examiners = Examiner::get_all_by_time(&pool, &session_id, RunTime::PM).await?;
-                    if pm_examiners.len() >= exam_circuit_cap { // if there's no way to move them
-                        return Err(AppError::from(anyhow!("There are more PM examiners than capacity. {} over from {}", pm_examiners.len(), exam_circuit_cap)));
-                    }
-                } else if total_female_examiners < exam_female_circuit_cap {
-                    if total_female_examiners % 2 != 0 {
-                        let _ = super::examiners::create_fill(session_id.clone(), claim.organisation_id.clone(), &pool, Some(Availability { am: false, pm: true }), true).await?;
-                    }
-                }
+impl AllocationRules<'_> {
+    /// Checks a whole allocation. Returns the first broken rule
+    fn validate(
+        &self,
+        allocations: &[AllocationPayload],
+    ) -> Result<(), String> {
+        match self.violations(allocations).into_iter().next() {
+            Some(message) => Err(message),
+            None => Ok(()),
+        }
+    }
 
-                // all examiners
-                examiners = Examiner::get_all_by_time(&pool, &session_id, RunTime::PM).await?;
-                let total_examiners = examiners.len();
-                if total_examiners > exam_circuit_cap {
-                    return Err(AppError::from(anyhow!("There are more examiners than capacity. {} over from {}", total_examiners, exam_circuit_cap)));
-                } else if total_examiners < exam_circuit_cap {
-                    if total_examiners % 2 != 0 {
-                        let new_examiner = super::examiners::create_fill(session_id.clone(), claim.organisation_id.clone(), &pool, Some(Availability { am: false, pm: true }), false).await?;
-                        examiners.push(new_examiner);
-                    }
-                }
-            } else if am_runs_count > 0 && pm_runs_count > 0 { // Slot has runs in both AM and PM
-                trace!("AM and PM runs");
-                // female candidates
-                let female_candidates = Candidate::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: true }).await?;
-                let total_female_candidates = female_candidates.len();
-                if total_female_candidates > can_female_circuit_cap {
-                    return Err(AppError::from(anyhow!("There are more female candidates than capacity. {} over from {}", total_female_candidates, can_female_circuit_cap)));
-                } else if total_female_candidates < can_female_circuit_cap {
-                    if total_female_candidates % 2 != 0 { // just need to make it even, don't need to fill it all, only examiners need to be filled to MAX
-                        let _ = super::candidates::create_fill(session_id.clone(), claim.organisation_id.clone(), &pool, Some(Availability { am: true, pm: true }), true).await?;
+    /// Every rule the allocation breaks, in the order of its rows
+    fn violations(
+        &self,
+        allocations: &[AllocationPayload],
+    ) -> Vec<String> {
+        let mut violations = Vec::new();
+        let mut candidate_positions: HashMap<Uuid, (Uuid, Uuid)> = HashMap::new();
+        let mut examiner_positions: HashMap<(Uuid, Uuid), (Uuid, Uuid)> = HashMap::new(); // (examiner, slot) -> (circuit, station)
+        let mut examiner_runs: HashMap<Uuid, Vec<&Run>> = HashMap::new();
+        let mut actor_runs: HashMap<Uuid, Vec<&Run>> = HashMap::new();
+        let overlaps = |a: &Run, b: &Run| a.scheduled_start < b.scheduled_end && b.scheduled_start < a.scheduled_end;
+
+        for allocation in allocations {
+            let Some(plan) = self.slot_plans.iter().find(|p| p.slot.id == allocation.slot_id) else {
+                violations.push("Allocation is in a slot from another session".to_string());
+                continue;
+            };
+            let Some(circuit) = plan.circuits.iter().find(|c| c.id == allocation.circuit_id) else {
+                violations.push("Allocation is in a circuit from another slot".to_string());
+                continue;
+            };
+            let Some((run, is_am)) = plan.am_runs
+                .iter()
+                .map(|run| (run, true))
+                .chain(plan.pm_runs.iter().map(|run| (run, false)))
+                .find(|(run, _)| run.id == allocation.run_id)
+            else {
+                violations.push("Allocation is in a run from another slot".to_string());
+                continue;
+            };
+            let Some(station) = self.stations.iter().find(|s| s.id == allocation.station_id) else {
+                violations.push("Station not found in session".to_string());
+                continue;
+            };
+            let run_name = format!("the {:02}:{:02} run", run.scheduled_start.hour(), run.scheduled_start.minute());
+
+            // candidates
+            if !allocation.candidates.is_empty() && allocation.candidates.len() != self.group_size {
+                violations.push(format!("Circuit {} has a group of {} candidates, stations take {}", circuit.key, allocation.candidates.len(), self.group_size));
+            }
+            if allocation.candidates.iter().collect::<HashSet<_>>().len() != allocation.candidates.len() {
+                violations.push(format!("Circuit {} has the same candidate twice at a station", circuit.key));
+            }
+            let group: Vec<&Candidate> = self.candidates.iter().filter(|c| allocation.candidates.contains(&c.id)).collect();
+            for (i, a) in group.iter().enumerate() {
+                for b in &group[(i + 1)..] {
+                    if avoids(a, b) || avoids(b, a) {
+                        violations.push(format!("{} {} and {} {} cannot be in the same group", a.first_name, a.last_name, b.first_name, b.last_name));
                     }
                 }
-
-                // all candidates
-                candidates = Candidate::get_ava_all(&pool, &session_id, Availability { am: true, pm: true }).await?;
-                let total_candidates = candidates.len();
-                if total_candidates > can_circuit_cap {
-                    return Err(AppError::from(anyhow!("There are more candidates than capacity. {} over from {}", total_candidates, can_circuit_cap)));
-                } else if total_candidates < can_circuit_cap {
-                    if total_candidates % 2 != 0 {
-                        let new_candidate = super::candidates::create_fill(session_id.clone(), claim.organisation_id.clone(), &pool, Some(Availability { am: true, pm: true }), false).await?;
-                        candidates.push(new_candidate);
-                    }
+            }
+            for &candidate_id in &allocation.candidates {
+                let Some(candidate) = self.candidates.iter().find(|c| c.id == candidate_id) else {
+                    violations.push("Candidate not found in session".to_string());
+                    continue;
+                };
+                if circuit.female_only && !candidate.female_only {
+                    violations.push(format!("{} {} cannot be in female-only circuit {}", candidate.first_name, candidate.last_name, circuit.key));
                 }
-
-                // female examiners
-                let (am_result, pm_result) = tokio::join!(
-                    Examiner::get_all_female_by_time(&pool, &session_id, RunTime::AM),
-                    Examiner::get_all_female_by_time(&pool, &session_id, RunTime::PM)
-                );
-                let am_female_examiners = am_result?;
-                let pm_female_examiners = pm_result?;
-                
-                let am_female_examiner_diff = female_circuits.len() as isize - am_female_examiners.len() as isize;
-                let pm_female_examiner_diff = female_circuits.len() as isize - pm_female_examiners.len() as isize;
-                if am_female_examiner_diff < 0 {
-                    trace!("There are more AM female examiners than capacity. Checking if there's space in non-female circuits");
-                    let am_examiners = Examiner::get_all_by_time(&pool, &session_id, RunTime::AM).await?;
-                    if am_examiners.len() >= exam_circuit_cap { // if there's no way to move them
-                        return Err(AppError::from(anyhow!("There are more AM examiners than capacity. {} over from {}", am_examiners.len(), exam_circuit_cap)));
-                    }
+                if !can_attend(candidate, &plan.time()) {
+                    violations.push(format!("{} {} is not available for circuit {}", candidate.first_name, candidate.last_name, circuit.key));
                 }
-                if pm_female_examiner_diff < 0 {
-                    trace!("There are more PM female examiners than capacity. Checking if there's space in non-female circuits");
-                    let pm_examiners = Examiner::get_all_by_time(&pool, &session_id, RunTime::PM).await?;
-                    if pm_examiners.len() >= exam_circuit_cap { // if there's no way to move them
-                        return Err(AppError::from(anyhow!("There are more PM examiners than capacity. {} over from {}", pm_examiners.len(), exam_circuit_cap)));
-                    }
+                let position = (allocation.circuit_id, allocation.station_id);
+                if *candidate_positions.entry(candidate_id).or_insert(position) != position {
+                    violations.push(format!("{} {} is allocated to more than 1 station", candidate.first_name, candidate.last_name));
                 }
-
-                let _ = fill_by_time(
-                    &session_id,
-                    &claim.organisation_id,
-                    &pool,
-                    am_female_examiners.len(),
-                    pm_female_examiners.len(),
-                    exam_female_circuit_cap,
-                    exam_female_circuit_cap,
-                    true
-                ).await?;
-
-                // all examiners
-                let (am_result, pm_result) = tokio::join!(
-                    Examiner::get_all_by_time(&pool, &session_id, RunTime::AM),
-                    Examiner::get_all_by_time(&pool, &session_id, RunTime::PM),
-                );
-                let mut am_examiners = am_result?;
-                let mut pm_examiners = pm_result?;
-                
-                let (mut new_full_examiners, mut new_am_examiners, mut new_pm_examiners) = fill_by_time(
-                    &session_id,
-                    &claim.organisation_id,
-                    &pool,
-                    am_examiners.len(),
-                    pm_examiners.len(),
-                    exam_circuit_cap,
-                    exam_circuit_cap,
-                    false
-                ).await?;
-
-                am_examiners.append(&mut new_full_examiners);
-                pm_examiners.append(&mut new_full_examiners);
-                am_examiners.append(&mut new_am_examiners);
-                pm_examiners.append(&mut new_pm_examiners);
-
-                trace!("Total Candidates: {} = Total Examiners: AM: {}, PM: {}", candidates.len(), am_examiners.len(), pm_examiners.len());
-                // allocation needs to take account that if theres more female examiners than capacity, move them into non-female circuits
-                // let candidate_allocations = allocate_by_slot(&circuits, &stations, &candidates)?;
-                // let am_examiner_allocations = allocate_by_time(&circuits, &stations, &am_examiners)?;
-                // let pm_examiner_allocations = allocate_by_time(&circuits, &stations, &pm_examiners)?;
-
-                // Allocation::add_by_slot(&mut transaction, candidate_allocations, &batch_id, &cur_slot.id, &claim.id).await?;
-                // Allocation::add_by_time(&mut transaction, am_examiner_allocations, &batch_id, &cur_slot.id, &claim.id).await?;
-                // Allocation::add_by_time(&mut transaction, pm_examiner_allocations, &batch_id, &cur_slot.id, &claim.id).await?;
-
-                // transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
-
-                return Ok(StatusCode::OK.into_response());
-            } else {
-                return Err(AppError::from(anyhow!("Slot has no runs")));
             }
 
-            trace!("Total Candidates: {} = Total Examiners: {}", candidates.len(), examiners.len());
-            // allocations (for either AM or PM runs)
-            // let candidates_allocation = allocate_by_slot(&circuits, &stations, &candidates)?;
-            // let examiners_allocations = allocate_by_time(&circuits, &stations, &examiners)?;
-
-            // Allocation::add_by_slot(&mut transaction, candidates_allocation, &batch_id, &cur_slot.id, &claim.id).await?;
-            // Allocation::add_by_time(&mut transaction, examiners_allocations, &batch_id, &cur_slot.id, &claim.id).await?;
+            // examiners
+            if let Some(examiner_id) = allocation.examiner {
+                match self.examiners.iter().find(|e| e.id == examiner_id) {
+                    None => violations.push("Examiner not found in session".to_string()),
+                    Some(examiner) => {
+                        if circuit.female_only && !examiner.female {
+                            violations.push(format!("{} {} cannot examine in female-only circuit {}", examiner.first_name, examiner.last_name, circuit.key));
+                        }
+                        if !station.is_examined() {
+                            violations.push(format!("Station '{}' is a {} station and has no examiner", station.title, station.kind));
+                        }
+                        if !has_tags(examiner, &station.required_tags) {
+                            violations.push(format!("{} {} does not have the tags required by station '{}'", examiner.first_name, examiner.last_name, station.title));
+                        }
+                        // candidates rotate through every station of the circuit
+                        let excluded = allocations
+                            .iter()
+                            .filter(|a| a.circuit_id == allocation.circuit_id)
+                            .flat_map(|a| a.candidates.iter().copied())
+                            .any(|candidate_id| self.exclusions.contains(&(examiner_id, candidate_id)));
+                        if excluded {
+                            violations.push(format!("{} {} cannot examine a candidate in circuit {} due to a conflict of interest", examiner.first_name, examiner.last_name, circuit.key));
+                        }
+                        if (is_am && !examiner.am) || (!is_am && !examiner.pm) {
+                            violations.push(format!("{} {} is not available for {}", examiner.first_name, examiner.last_name, run_name));
+                        }
+                        let runs = examiner_runs.entry(examiner_id).or_default();
+                        if runs.iter().any(|other| overlaps(other, run)) {
+                            violations.push(format!("{} {} is examining more than 1 station at the same time as {}", examiner.first_name, examiner.last_name, run_name));
+                        }
+                        runs.push(run);
 
-            // transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
-        },
-        _ => { // slots > 1
-            trace!("Multiple Slots");
-            // sort slots into AM and PM (before 12:00 start = AM, else = PM)
-            let mut am_only_slots: Vec<Slot> = Vec::new();
-            let mut pm_only_slots: Vec<Slot> = Vec::new();
-            let mut any_slots: Vec<Slot> = Vec::new();
-            for cur_slot in slots {
-                let (am_runs_result, pm_runs_result) = tokio::join!(
-                    Run::get_by_time(&pool, &cur_slot.id, RunTime::AM),
-                    Run::get_by_time(&pool, &cur_slot.id, RunTime::PM)
-                );
-                let am_runs = am_runs_result?;
-                let pm_runs = pm_runs_result?;
-                if am_runs.len() > 0 && pm_runs.len() == 0 { // AM runs only
-                    am_only_slots.push(cur_slot);
-                } else if am_runs.len() == 0 && pm_runs.len() > 0 { // PM runs only
-                    pm_only_slots.push(cur_slot);
-                } else if am_runs.len() > 0 && pm_runs.len() > 0 { // AM & PM runs
-                    any_slots.push(cur_slot);
-                } else {
-                    return Err(AppError::from(anyhow!("Slot has no runs")));
+                        let position = (allocation.circuit_id, allocation.station_id);
+                        if self.require_continuity && *examiner_positions.entry((examiner_id, allocation.slot_id)).or_insert(position) != position {
+                            violations.push(format!("{} {} must keep the same station for every run of the slot", examiner.first_name, examiner.last_name));
+                        }
+                    },
                 }
             }
 
-            trace!("Slots: AM: {}, PM: {}, Both: {}", am_only_slots.len(), pm_only_slots.len(), any_slots.len());
-            // CANDIDATES
-            let (can_am_result, can_pm_result) = tokio::join!(
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: true, pm: false }),
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: false, pm: true }),
-            );
-            let candidates_am_only = can_am_result?; // candidates that can ONLY do AM
-            let candidates_pm_only = can_pm_result?; // candidates that can ONLY do PM
-            if candidates_am_only.len() != 0 && am_only_slots.len() == 0 {
-                return Err(AppError::from(anyhow!("No AM slots but have {} candidates who can only make it to AM", candidates_am_only.len())));
-            } else if candidates_pm_only.len() != 0 && pm_only_slots.len() == 0 {
-                return Err(AppError::from(anyhow!("No PM slots but have {} candidates who can only make it to PM", candidates_pm_only.len())));
-            }
-
-            // split candidates evenly into each slot (and make exceptions for candidates with specific availability)
-            let (can_am_fem_result, can_pm_fem_result, can_any_fem_result, can_fem_result) = tokio::join!(
-                Candidate::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: false }),
-                Candidate::get_female_ava_all(&pool, &session_id, Availability { am: false, pm: true }),
-                Candidate::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: true }),
-                Candidate::get_female_all(&pool, &session_id)
-            );
-            let candidates_female_am_only = can_am_fem_result?;
-            let can_female_am_only_count = candidates_female_am_only.len();
-
-            let mut am_can_cap = 0;
-            let mut am_can_female_cap = 0;
-            for am_slot in &am_only_slots {
-                let circuits = Circuit::get_by_slot(&pool, &am_slot.id).await?;
-                let female_circuits = Circuit::get_female_slot(&pool, &am_slot.id).await?;
-                am_can_cap += circuits.len() * stations.len() * 2;
-                am_can_female_cap += female_circuits.len() * stations.len() * 2;
-            }
-
-            let candidates_female_pm_only = can_pm_fem_result?;
-            let can_female_pm_only_count = candidates_female_pm_only.len();
-
-            let mut pm_can_cap = 0;
-            let mut pm_can_female_cap = 0;
-            for pm_slot in &pm_only_slots {
-                let circuits = Circuit::get_by_slot(&pool, &pm_slot.id).await?;
-                let female_circuits = Circuit::get_female_slot(&pool, &pm_slot.id).await?;
-                pm_can_cap += circuits.len() * stations.len() * 2;
-                pm_can_female_cap += female_circuits.len() * stations.len() * 2;
-            }
-
-            let candidates_female_any = can_any_fem_result?;
-            let can_female_any_count = candidates_female_any.len();
-
-            let mut any_can_cap = 0;
-            let mut any_can_female_cap = 0;
-            for any_slot in &any_slots {
-                let circuits = Circuit::get_by_slot(&pool, &any_slot.id).await?;
-                let female_circuits = Circuit::get_female_slot(&pool, &any_slot.id).await?;
-                any_can_cap += circuits.len() * stations.len() * 2;
-                any_can_female_cap += female_circuits.len() * stations.len() * 2;
-            }
-
-            let candidates_female = can_fem_result?; // all female candidates
-            let can_female_count = candidates_female.len();
-
-            trace!("Female Candidates: AM-only = {}, PM-only = {}, Any = {}, TOTAL: {}", can_female_am_only_count, can_female_pm_only_count, can_female_any_count, can_female_count);
-
-            //// FILLING ODD PAIRS BASED ON AM + PM + ANY
-            
-            // female_candidates
-            fill_any_priority(&session_id, &claim.organisation_id, &pool, PeopleType::Candidate,
-                can_female_count,
-                can_female_am_only_count,
-                am_can_female_cap,
-                can_female_pm_only_count,
-                pm_can_female_cap,
-                can_female_any_count,
-                any_can_female_cap
-            ).await?;
-
-            let (can_am_result, can_pm_result, can_any_result, can_result) = tokio::join!(
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: true, pm: false }),
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: false, pm: true }),
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: true, pm: true }),
-                Candidate::get_all_by_session(&pool, &session_id)
-            );
-            let candidates_am_only = can_am_result?; // candidates that can ONLY do AM
-            let candidates_pm_only = can_pm_result?; // candidates that can ONLY do PM
-            let candidates_any = can_any_result?; // candidates that can do AM or PM
-            let candidates = can_result?; // all candidates
-
-            let can_am_only_count = candidates_am_only.len();
-            let can_pm_only_count = candidates_pm_only.len();
-            let can_any_count = candidates_any.len();
-            let can_count = candidates.len();
-
-            trace!("Candidates: AM-only = {}, PM-only = {}, Any = {}, TOTAL: {}", can_am_only_count, can_pm_only_count, can_any_count, can_count);
-
-            // all candidates
-            fill_any_priority(&session_id, &claim.organisation_id, &pool, PeopleType::Candidate,
-                can_count,
-                can_am_only_count,
-                am_can_cap,
-                can_pm_only_count,
-                pm_can_cap,
-                can_any_count,
-                any_can_cap
-            ).await?;
-
-            //// SPLITTING INTO AM + PM + ANY
-            
-            // female candidates
-            let (can_am_fem_result, can_pm_fem_result, can_any_fem_result, can_fem_result) = tokio::join!(
-                Candidate::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: false }),
-                Candidate::get_female_ava_all(&pool, &session_id, Availability { am: false, pm: true }),
-                Candidate::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: true }),
-                Candidate::get_female_all(&pool, &session_id)
-            );
-            let can_female_am_only_count = can_am_fem_result?.len();
-            let can_female_pm_only_count = can_pm_fem_result?.len();
-            let can_female_any_count = can_any_fem_result?.len();
-            let can_female_count = can_fem_result?.len();
-
-            // purpose is to split can_female_any equally among am, pm (and any if exists), whilst giving priority to people who can only make it to AM/PM
-            let (
-                can_am_female_split,
-                can_pm_female_split,
-                can_any_female_split,
-                can_am_female_diff,
-                can_pm_female_diff,
-                can_any_female_diff
-            ) = even_split(
-                can_female_count,
-                can_female_am_only_count,
-                can_female_pm_only_count,
-                can_female_any_count,
-                any_can_female_cap,
-            )?;
-
-            // all candidates
-            let (can_am_result, can_pm_result, can_any_result, can_result) = tokio::join!(
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: true, pm: false }),
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: false, pm: true }),
-                Candidate::get_ava_all(&pool, &session_id, Availability { am: true, pm: true }),
-                Candidate::get_all_by_session(&pool, &session_id)
-            );
-            
-            // female is distributed independent from the rest, this could create inbalance?
-            let can_am_only_count = can_am_result?.len() - can_female_am_only_count;
-            let can_pm_only_count = can_pm_result?.len() - can_female_pm_only_count;
-            let can_any_count = can_any_result?.len() - can_female_any_count;
-            let can_count = can_result?.len() - can_female_count;
-
-            let (
-                can_am_split,
-                can_pm_split,
-                can_any_split,
-                can_am_diff,
-                can_pm_diff,
-                can_any_diff,
-            ) = even_split(
-                can_count,
-                can_am_only_count,
-                can_pm_only_count,
-                can_any_count,
-                any_can_cap,
-            )?;
-
-            let total_can_am = can_am_female_split + can_am_split;
-            let total_can_pm = can_pm_female_split + can_pm_split;
-            let total_can_any = can_any_female_split + can_any_split;
-
-            trace!("Total Candidates split: AM: {}, PM: {}, BOTH: {} ==> Total Female Candidates split: AM: {}, PM: {}, BOTH: {}", total_can_am, total_can_pm, total_can_any, can_am_female_split, can_pm_female_split, can_any_female_split);
-
-            // EXAMINERS
-            let (exam_am_result, exam_pm_result) = tokio::join!(
-                Examiner::get_ava_all(&pool, &session_id, Availability { am: true, pm: false }),
-                Examiner::get_ava_all(&pool, &session_id, Availability { am: false, pm: true }),
-            );
-            let examiners_am_only = exam_am_result?;
-            let examiners_pm_only = exam_pm_result?;
-            if examiners_am_only.len() != 0 && am_only_slots.len() == 0 {
-                return Err(AppError::from(anyhow!("No AM slots but have {} examiners who can only make it to AM", examiners_am_only.len())));
-            } else if examiners_pm_only.len() != 0 && pm_only_slots.len() == 0 {
-                return Err(AppError::from(anyhow!("No PM slots but have {} examiners who can only make it to PM", examiners_pm_only.len())));
-            }
-            
-            // creating fills for examiners
-            // havent implemented finding common missing examiners in both AM & PM and merging them into a both examiner insertion
-
-            let mut am_excess_exam: usize = 0;
-            for am_slot in &am_only_slots {
-                let (circuit_result, fem_circuit_result, am_fem_result, am_result) = tokio::join!(
-                    Circuit::get_by_slot(&pool, &am_slot.id),
-                    Circuit::get_female_slot(&pool, &am_slot.id),
-                    Examiner::get_all_female_by_time(&pool, &session_id, RunTime::AM),
-                    Examiner::get_all_by_time(&pool, &session_id, RunTime::AM)
-                );
-                let circuits = circuit_result?;
-                let female_circuits = fem_circuit_result?;
-                let am_female_examiners = am_fem_result?;
-                let am_examiners = am_result?;
-
-                let temp_excess = fill_by_slot(
-                    &session_id,
-                    &claim.organisation_id,
-                    &pool,
-                    am_examiners.len(),
-                    am_female_examiners.len(),
-                    circuits.len() * stations_not_rest.len(),
-                    female_circuits.len() * stations_not_rest.len(),
-                    true, // am
-                    false // pm
-                ).await?;
-                if am_excess_exam == 0 {
-                    am_excess_exam = temp_excess; 
-                }
-                else if temp_excess < am_excess_exam {
-                    am_excess_exam = temp_excess;
+            // actors
+            if let Some(actor_id) = allocation.actor {
+                match self.actors.iter().find(|a| a.id == actor_id) {
+                    None => violations.push("Actor not found in session".to_string()),
+                    Some(actor) => {
+                        if !station.needs_actor {
+                            violations.push(format!("Station '{}' does not need an actor", station.title));
+                        }
+                        if !can_play(actor, station, circuit) {
+                            violations.push(format!("{} {} cannot play station '{}' in circuit {}", actor.first_name, actor.last_name, station.title, circuit.key));
+                        }
+                        if (is_am && !actor.am) || (!is_am && !actor.pm) {
+                            violations.push(format!("{} {} is not available for {}", actor.first_name, actor.last_name, run_name));
+                        }
+                        let runs = actor_runs.entry(actor_id).or_default();
+                        if runs.iter().any(|other| overlaps(other, run)) {
+                            violations.push(format!("{} {} is playing more than 1 station at the same time as {}", actor.first_name, actor.last_name, run_name));
+                        }
+                        runs.push(run);
+                    },
                 }
             }
+        }
+        violations
+    }
+}
 
-            let mut pm_excess_exam: usize = 0;
-            for pm_slot in &pm_only_slots {
-                let (circuit_result, fem_circuit_result, pm_fem_result, pm_result) = tokio::join!(
-                    Circuit::get_by_slot(&pool, &pm_slot.id),
-                    Circuit::get_female_slot(&pool, &pm_slot.id),
-                    Examiner::get_all_female_by_time(&pool, &session_id, RunTime::PM),
-                    Examiner::get_all_by_time(&pool, &session_id, RunTime::PM)
-                );
-                let circuits = circuit_result?;
-                let female_circuits = fem_circuit_result?;
-                let pm_female_examiners = pm_fem_result?;
-                let pm_examiners = pm_result?;
-
-                let temp_excess = fill_by_slot(
-                    &session_id,
-                    &claim.organisation_id,
-                    &pool,
-                    pm_examiners.len(),
-                    pm_female_examiners.len(),
-                    circuits.len() * stations_not_rest.len(),
-                    female_circuits.len() * stations_not_rest.len(),
-                    false, // am
-                    true // pm
-                ).await?;
-                if pm_excess_exam == 0 {
-                    pm_excess_exam = temp_excess; 
-                }
-                else if temp_excess < pm_excess_exam {
-                    pm_excess_exam = temp_excess;
-                }
-            }
+fn diff_allocations( // compares the live allocation against a proposed one
+    current: &[Allocation],
+    proposed: &[AllocationPayload],
+) -> AllocationDiff {
+    type Row = (Vec<Uuid>, Option<Uuid>, Option<Uuid>); // candidates, examiner, actor
+    let current_rows: HashMap<(Uuid, Uuid, Uuid), Row> = current
+        .iter()
+        .map(|a| ((a.run_id, a.circuit_id, a.station_id), (a.candidates.clone(), a.examiner, a.actor)))
+        .collect();
+    let proposed_rows: HashMap<(Uuid, Uuid, Uuid), Row> = proposed
+        .iter()
+        .map(|a| ((a.run_id, a.circuit_id, a.station_id), (a.candidates.clone(), a.examiner, a.actor)))
+        .collect();
+
+    // per run / circuit / station
+    let mut stations = Vec::new();
+    let mut keys: Vec<&(Uuid, Uuid, Uuid)> = current_rows.keys().chain(proposed_rows.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let before = current_rows.get(key).cloned().unwrap_or_default();
+        let after = proposed_rows.get(key).cloned().unwrap_or_default();
+        if before != after {
+            stations.push(StationDiff {
+                run_id: key.0,
+                circuit_id: key.1,
+                station_id: key.2,
+                current_candidates: before.0,
+                current_examiner: before.1,
+                current_actor: before.2,
+                proposed_candidates: after.0,
+                proposed_examiner: after.1,
+                proposed_actor: after.2,
+            });
+        }
+    }
 
-            let mut any_excess_exam: usize = 0;
-            for any_slot in &any_slots { // for slots with runs in AM & PM, only examiners with full-day availability are taken account of
-                let (circuit_result, fem_circuit_result, fem_result, any_result) = tokio::join!(
-                    Circuit::get_by_slot(&pool, &any_slot.id),
-                    Circuit::get_female_slot(&pool, &any_slot.id),
-                    Examiner::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: true }),
-                    Examiner::get_ava_all(&pool, &session_id, Availability { am: true, pm: true })
-                );
-                let circuits = circuit_result?;
-                let female_circuits = fem_circuit_result?;
-                let any_female_examiners = fem_result?;
-                let any_examiners = any_result?;
-
-                let temp_excess = fill_by_slot(
-                    &session_id,
-                    &claim.organisation_id,
-                    &pool,
-                    any_examiners.len(),
-                    any_female_examiners.len(),
-                    circuits.len() * stations_not_rest.len(),
-                    female_circuits.len() * stations_not_rest.len(),
-                    true, // am
-                    true // pm
-                ).await?;
-                if any_excess_exam == 0 {
-                    any_excess_exam = temp_excess; 
-                }
-                else if temp_excess < any_excess_exam {
-                    any_excess_exam = temp_excess;
-                }
+    // candidates keep the same station for every run of a slot
+    let candidate_positions = |rows: &HashMap<(Uuid, Uuid, Uuid), Row>| {
+        let mut positions: HashMap<Uuid, (Uuid, Uuid)> = HashMap::new();
+        for (key, row) in rows {
+            for &candidate in &row.0 {
+                positions.insert(candidate, (key.1, key.2));
             }
-
-            // ALLOCATION
-            let (am_examiner, pm_examiner, both_examiner, am_fem_examiner, pm_fem_examiner, both_fem_examiner) = tokio::join!(
-                Examiner::get_all_by_time(&pool, &session_id, RunTime::AM),
-                Examiner::get_all_by_time(&pool, &session_id, RunTime::PM),
-                Examiner::get_ava_all(&pool, &session_id, Availability { am: true, pm: true }),
-                Examiner::get_all_female_by_time(&pool, &session_id, RunTime::AM),
-                Examiner::get_all_female_by_time(&pool, &session_id, RunTime::PM),
-                Examiner::get_female_ava_all(&pool, &session_id, Availability { am: true, pm: true })
-            );
-            let total_am_examiners = am_examiner?;
-            let total_pm_examiners = pm_examiner?;
-            let total_both_examiners = both_examiner?;
-            let total_am_fem_examiners = am_fem_examiner?;
-            let total_pm_fem_examiners = pm_fem_examiner?;
-            let total_both_fem_examiners = both_fem_examiner?;
-
-            trace!("Total Examiners split: AM: {}, PM: {}, BOTH: {} ==> Total Female Examiners split: AM: {}, PM: {}, BOTH: {}",
-                total_am_examiners.len(), total_pm_examiners.len(), total_both_examiners.len(), total_am_fem_examiners.len(), total_pm_fem_examiners.len(), total_both_fem_examiners.len());
-            trace!("Excess Examiners: AM: {}, PM: {}, BOTH: {}", am_excess_exam, pm_excess_exam, any_excess_exam);
-            // let total_can_am = can_am_female_split + can_am_split;
-            // let total_can_pm = can_pm_female_split + can_pm_split;
-            // let total_can_any = can_any_female_split + can_any_split;
-
-            // let candidates_allocation = allocate_by_slot(&circuits, &stations, &candidates)?;
-
-            // let examiners_allocations = allocate_by_slot(&circuits, &stations, &examiners)?;
-
-            // Allocation::add_by_slot(&mut transaction, candidates_allocation, &batch_id, &cur_slot.id, &claim.id).await?;
-            // Allocation::add_by_slot(&mut transaction, examiners_allocations, &batch_id, &cur_slot.id, &claim.id).await?;
-
-            // transaction.commit().await.map_err(|e| AppError::from(anyhow!("Failed to commit transaction: {}", e)))?;
-        },
+        }
+        positions
     };
-
-    Ok(StatusCode::OK.into_response())
+    let current_candidates = candidate_positions(&current_rows);
+    let proposed_candidates = candidate_positions(&proposed_rows);
+    let mut candidate_ids: Vec<&Uuid> = current_candidates.keys().chain(proposed_candidates.keys()).collect();
+    candidate_ids.sort();
+    candidate_ids.dedup();
+    let moved_candidates = candidate_ids
+        .into_iter()
+        .filter_map(|id| {
+            let from = current_candidates.get(id).copied();
+            let to = proposed_candidates.get(id).copied();
+            (from != to).then(|| PersonMove {
+                id: *id,
+                run_id: None,
+                from_circuit: from.map(|p| p.0),
+                from_station: from.map(|p| p.1),
+                to_circuit: to.map(|p| p.0),
+                to_station: to.map(|p| p.1),
+            })
+        })
+        .collect();
+
+    // examiners and actors can change station between runs, so they are compared per run
+    let moved_per_run = |person: fn(&Row) -> Option<Uuid>| {
+        let positions = |rows: &HashMap<(Uuid, Uuid, Uuid), Row>| {
+            rows.iter()
+                .filter_map(|(key, row)| person(row).map(|id| ((id, key.0), (key.1, key.2))))
+                .collect::<HashMap<(Uuid, Uuid), (Uuid, Uuid)>>()
+        };
+        let current_positions = positions(&current_rows);
+        let proposed_positions = positions(&proposed_rows);
+        let mut keys: Vec<&(Uuid, Uuid)> = current_positions.keys().chain(proposed_positions.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter_map(|key| {
+                let from = current_positions.get(key).copied();
+                let to = proposed_positions.get(key).copied();
+                (from != to).then(|| PersonMove {
+                    id: key.0,
+                    run_id: Some(key.1),
+                    from_circuit: from.map(|p| p.0),
+                    from_station: from.map(|p| p.1),
+                    to_circuit: to.map(|p| p.0),
+                    to_station: to.map(|p| p.1),
+                })
+            })
+            .collect::<Vec<PersonMove>>()
+    };
+    let moved_examiners = moved_per_run(|row| row.1);
+    let moved_actors = moved_per_run(|row| row.2);
+
+    // partners in the live allocation who are no longer in the same group
+    let partners = |rows: &HashMap<(Uuid, Uuid, Uuid), Row>| {
+        rows.values()
+            .flat_map(|row| {
+                let group = &row.0;
+                (0..group.len()).flat_map(move |i| ((i + 1)..group.len()).map(move |j| (group[i].min(group[j]), group[i].max(group[j]))))
+            })
+            .collect::<HashSet<(Uuid, Uuid)>>()
+    };
+    let proposed_pairs = partners(&proposed_rows);
+    let mut broken_pairs: Vec<(Uuid, Uuid)> = partners(&current_rows)
+        .into_iter()
+        .filter(|pair| !proposed_pairs.contains(pair))
+        .collect();
+    broken_pairs.sort();
+    broken_pairs.dedup();
+
+    AllocationDiff {
+        stations,
+        moved_candidates,
+        moved_examiners,
+        moved_actors,
+        broken_pairs: broken_pairs.into_iter().map(|(candidate_1, candidate_2)| BrokenPair { candidate_1, candidate_2 }).collect(),
+    }
 }
 
 impl Allocation {
-    pub async fn add_by_slot(
+    pub async fn get_by_session(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<Allocation>, AppError> {
+        sqlx::query_as!(
+            Allocation,
+            r#"
+            SELECT
+                a.id, a.slot_id, a.circuit_id, a.station_id, a.examiner, a.modified_at, a.run_id, a.pair_locked, a.examiner_locked, a.actor,
+                COALESCE(ARRAY_AGG(m.candidate_id ORDER BY m.position) FILTER (WHERE m.candidate_id IS NOT NULL), '{}') AS "candidates!"
+            FROM records.allocations a
+            JOIN records.slots s ON a.slot_id = s.id
+            LEFT JOIN records.allocation_members m ON m.allocation_id = a.id
+            WHERE s.session_id = $1
+            GROUP BY a.id
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get all allocations with specific session_id")))
+    }
+
+    pub async fn add_batch( // replaces the session's current allocation, and records it under batch_id
         tx: &mut Transaction<'static, sqlx::Postgres>,
-        allocations: Vec<SlotAllocation>,
+        session_id: &Uuid,
+        allocations: &[AllocationPayload],
         batch_id: &Uuid,
-        slot_id: &Uuid,
         user_id: &Uuid,
-    ) -> Result<(), AppError> { // for candidates ONLY
+        auto_gen: bool,
+    ) -> Result<(), AppError> {
         sqlx::query!(
             r#"
             DELETE FROM records.allocations
-            WHERE slot_id = $1
+            WHERE slot_id IN (SELECT id FROM records.slots WHERE session_id = $1)
             "#,
-            slot_id
+            session_id
         )
         .execute(&mut **tx)
         .await
         .with_context(|| "Failed to delete existing allocations")?;
 
-        for allocation in &allocations {
-            sqlx::query!(
+        for allocation in allocations {
+            let allocation_id = sqlx::query_scalar!(
                 r#"
                 INSERT INTO records.allocations (
-                    slot_id, circuit_id, station_id, candidate_1, candidate_2
-                ) VALUES ($1, $2, $3, $4, $5)
+                    slot_id, run_id, circuit_id, station_id, examiner, pair_locked, examiner_locked, actor
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING id
                 "#,
-                slot_id,
+                allocation.slot_id,
+                allocation.run_id,
                 allocation.circuit_id,
                 allocation.station_id,
-                allocation.candidate_1,
-                allocation.candidate_2,
+                allocation.examiner,
+                allocation.pair_locked,
+                allocation.examiner_locked,
+                allocation.actor,
             )
-            .execute(&mut **tx)
+            .fetch_one(&mut **tx)
             .await
             .with_context(|| "Failed to insert allocation")?;
 
+            // members keep the order of the group, position 1 performs first
             sqlx::query!(
+                r#"
+                INSERT INTO records.allocation_members (allocation_id, run_id, candidate_id, position)
+                SELECT $1, $2, m.candidate_id, m.position::smallint
+                FROM UNNEST($3::uuid[]) WITH ORDINALITY AS m(candidate_id, position)
+                "#,
+                allocation_id,
+                allocation.run_id,
+                &allocation.candidates
+            )
+            .execute(&mut **tx)
+            .await
+            .with_context(|| "Failed to insert allocation members")?;
+
+            let history_id = sqlx::query_scalar!(
                 r#"
                 INSERT INTO records.allocations_history (
-                    batch_id, slot_id, circuit_id, station_id, candidate_1, candidate_2, modified_by, auto_gen
-                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    batch_id, slot_id, run_id, circuit_id, station_id, examiner, modified_by, auto_gen, pair_locked, examiner_locked, actor
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING id
                 "#,
                 batch_id,
-                slot_id,
+                allocation.slot_id,
+                allocation.run_id,
                 allocation.circuit_id,
                 allocation.station_id,
-                allocation.candidate_1,
-                allocation.candidate_2,
+                allocation.examiner,
                 user_id,
-                true
+                auto_gen,
+                allocation.pair_locked,
+                allocation.examiner_locked,
+                allocation.actor
             )
-            .execute(&mut **tx)
+            .fetch_one(&mut **tx)
             .await
             .with_context(|| "Failed to insert allocation history")?;
+
+            sqlx::query!(
+                r#"
+                INSERT INTO records.allocations_history_members (allocation_id, candidate_id, position)
+                SELECT $1, m.candidate_id, m.position::smallint
+                FROM UNNEST($2::uuid[]) WITH ORDINALITY AS m(candidate_id, position)
+                "#,
+                history_id,
+                &allocation.candidates
+            )
+            .execute(&mut **tx)
+            .await
+            .with_context(|| "Failed to insert allocation history members")?;
         }
         Ok(())
     }
+}
 
-    pub async fn add_by_time(
-        tx: &mut Transaction<'static, sqlx::Postgres>,
-        allocations: Vec<TimeAllocation>,
+impl AllocationHistory {
+    pub async fn get_batches_by_session(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<AllocationBatch>, AppError> {
+        sqlx::query_as!(
+            AllocationBatch,
+            r#"
+            SELECT
+                h.batch_id,
+                h.modified_by,
+                u.username,
+                bool_and(h.auto_gen) AS "auto_gen!",
+                MAX(h.modified_at) AS "modified_at!",
+                COUNT(*) AS "allocations!"
+            FROM records.allocations_history h
+            JOIN records.slots s ON h.slot_id = s.id
+            JOIN auth.users u ON h.modified_by = u.id
+            WHERE s.session_id = $1
+            GROUP BY h.batch_id, h.modified_by, u.username
+            ORDER BY MAX(h.modified_at) DESC
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get allocation batches with specific session_id")))
+    }
+
+    pub async fn get_by_batch(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
         batch_id: &Uuid,
-        slot_id: &Uuid,
-        user_id: &Uuid,
-    ) -> Result<(), AppError> { // for examiners ONLY
-        Ok(())
+    ) -> Result<Vec<AllocationHistory>, AppError> {
+        sqlx::query_as!(
+            AllocationHistory,
+            r#"
+            SELECT
+                h.id, h.batch_id, h.slot_id, h.circuit_id, h.station_id, h.examiner, h.modified_by, h.auto_gen, h.modified_at, h.run_id, h.pair_locked, h.examiner_locked, h.actor,
+                COALESCE(ARRAY_AGG(m.candidate_id ORDER BY m.position) FILTER (WHERE m.candidate_id IS NOT NULL), '{}') AS "candidates!"
+            FROM records.allocations_history h
+            JOIN records.slots s ON h.slot_id = s.id
+            LEFT JOIN records.allocations_history_members m ON m.allocation_id = h.id
+            WHERE s.session_id = $1 AND h.batch_id = $2
+            GROUP BY h.id
+            "#,
+            session_id,
+            batch_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get allocation history with specific batch_id")))
+    }
+}
+
+impl PastPairings {
+    pub async fn get_by_session( // pairings from sessions of the organisation scheduled before this one
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<PastPairings, AppError> {
+        let candidate_pairs = sqlx::query!(
+            r#"
+            SELECT DISTINCT c1.shortcode AS candidate_1, c2.shortcode AS candidate_2
+            FROM records.allocation_members m1
+            JOIN records.allocation_members m2 ON m2.allocation_id = m1.allocation_id AND m2.position > m1.position
+            JOIN records.allocations a ON m1.allocation_id = a.id
+            JOIN records.slots s ON a.slot_id = s.id
+            JOIN records.sessions past ON s.session_id = past.id
+            JOIN records.sessions cur ON cur.id = $1
+            JOIN people.candidates c1 ON m1.candidate_id = c1.id
+            JOIN people.candidates c2 ON m2.candidate_id = c2.id
+            WHERE past.organisation_id = cur.organisation_id AND past.scheduled_date < cur.scheduled_date
+            AND NOT c1.placeholder AND NOT c2.placeholder
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get candidate pairs from earlier sessions")))?
+        .into_iter()
+        .map(|row| (row.candidate_1, row.candidate_2))
+        .collect();
+
+        // candidates rotate through every station of their circuit, so the examiner meets everyone in it
+        let examiner_candidates = sqlx::query!(
+            r#"
+            SELECT DISTINCT e.shortcode AS examiner, c.shortcode AS candidate
+            FROM records.allocations a
+            JOIN records.allocations b ON b.run_id = a.run_id AND b.circuit_id = a.circuit_id
+            JOIN records.slots s ON a.slot_id = s.id
+            JOIN records.sessions past ON s.session_id = past.id
+            JOIN records.sessions cur ON cur.id = $1
+            JOIN people.examiners e ON a.examiner = e.id
+            JOIN records.allocation_members m ON m.allocation_id = b.id
+            JOIN people.candidates c ON m.candidate_id = c.id
+            WHERE past.organisation_id = cur.organisation_id AND past.scheduled_date < cur.scheduled_date
+            AND NOT e.placeholder AND NOT c.placeholder
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get examiner pairings from earlier sessions")))?
+        .into_iter()
+        .map(|row| (row.examiner, row.candidate))
+        .collect();
+
+        Ok(PastPairings { candidate_pairs, examiner_candidates })
+    }
+
+    pub fn resolve_pairs( // candidate id pairs of this session, people not in it are ignored
+        &self,
+        candidates: &[Candidate],
+    ) -> Vec<(Uuid, Uuid)> {
+        let ids: HashMap<&str, Uuid> = candidates.iter().map(|c| (c.shortcode.as_str(), c.id)).collect();
+        self.candidate_pairs
+            .iter()
+            .filter_map(|(a, b)| Some((*ids.get(a.as_str())?, *ids.get(b.as_str())?)))
+            .collect()
+    }
+
+    pub fn resolve_examiners( // (examiner id, candidate id) pairs of this session, people not in it are ignored
+        &self,
+        examiners: &[Examiner],
+        candidates: &[Candidate],
+    ) -> Vec<(Uuid, Uuid)> {
+        let examiner_ids: HashMap<&str, Uuid> = examiners.iter().map(|e| (e.shortcode.as_str(), e.id)).collect();
+        let candidate_ids: HashMap<&str, Uuid> = candidates.iter().map(|c| (c.shortcode.as_str(), c.id)).collect();
+        self.examiner_candidates
+            .iter()
+            .filter_map(|(e, c)| Some((*examiner_ids.get(e.as_str())?, *candidate_ids.get(c.as_str())?)))
+            .collect()
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 use anyhow::{Context, anyhow};
+use sqlx::Transaction;
 use axum::{extract::{Json, State, Query}, http::StatusCode, response::IntoResponse, routing::{get, post}, Extension};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -23,10 +24,14 @@ pub struct Candidate {
     pub last_name: String,
     pub shortcode: String,
     pub female_only: bool,
-    pub partner_pref: Option<String>,
+    pub partner_pref: Option<String>, // first choice partner shortcode
     pub checked_in: bool, 
     pub am: Option<bool>,
     pub pm: Option<bool>,
+    pub partner_pref_2: Option<String>, // second choice
+    pub avoid: Vec<String>, // shortcodes they must not be paired with
+    #[serde(default)]
+    pub placeholder: bool, // holds a vacancy in an allocation, shown as "Vacancy"
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,6 +45,9 @@ pub struct CandidatePayload {
     pub checked_in: bool, 
     pub am: Option<bool>,
     pub pm: Option<bool>,
+    pub partner_pref_2: Option<String>,
+    #[serde(default)]
+    pub avoid: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -54,6 +62,8 @@ pub struct CandidateChange {
     pub checked_in: Option<bool>, 
     pub am: Option<bool>,
     pub pm: Option<bool>,
+    pub partner_pref_2: Option<String>,
+    pub avoid: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -64,7 +74,9 @@ pub struct CandidateExcel {
     pub female_only: bool,
     pub partner_pref: Option<String>,
     pub am: Option<bool>,
-    pub pm: Option<bool>
+    pub pm: Option<bool>,
+    pub partner_pref_2: Option<String>,
+    pub avoid: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -111,33 +123,25 @@ async fn delete(
     Ok((StatusCode::OK).into_response())
 }
 
-pub async fn create_fill(session_id: Uuid, organsation_id: Uuid, pool: &sqlx::PgPool, time: Option<Availability>, female_only: bool) -> Result<Candidate, AppError> {
-    if let Some(can_ava) = time {
-        let candidate = CandidatePayload {
-            session_id,
-            first_name: "fill".to_string(),
-            last_name: "candidate".to_string(),
-            shortcode: Uuid::new_v4().to_string(),
-            female_only,
-            partner_pref: None,
-            am: Some(can_ava.am),
-            pm: Some(can_ava.pm),
-            checked_in: false,
-        };
-        Candidate::create(pool, organsation_id, candidate).await
-    } else {
-        let candidate = CandidatePayload {
-            session_id,
-            first_name: "fill".to_string(),
-            last_name: "candidate".to_string(),
-            shortcode: Uuid::new_v4().to_string(),
-            female_only,
-            partner_pref: None,
-            am: Some(true),
-            pm: Some(true),
-            checked_in: false,
-        };
-        Candidate::create(pool, organsation_id, candidate).await
+pub const PLACEHOLDER_NAME: &str = "Vacancy";
+
+// placeholder candidates only exist in memory until the allocation using them is saved
+pub fn create_fill(session_id: Uuid, time: Option<Availability>, female_only: bool) -> Candidate {
+    let can_ava = time.unwrap_or(Availability { am: true, pm: true });
+    Candidate {
+        id: Uuid::new_v4(),
+        session_id,
+        first_name: PLACEHOLDER_NAME.to_string(),
+        last_name: String::new(),
+        shortcode: Uuid::new_v4().to_string(),
+        female_only,
+        partner_pref: None,
+        checked_in: false,
+        am: Some(can_ava.am),
+        pm: Some(can_ava.pm),
+        partner_pref_2: None,
+        avoid: Vec::new(),
+        placeholder: true,
     }
 }
 
@@ -155,7 +159,7 @@ impl Candidate {
         )
         .fetch_one(&pool)
         .await
-        .with_context(|| format!("Cannot get candidate with specific id"))?;
+        .context("Cannot get candidate with specific id")?;
 
         Ok((StatusCode::OK, Json(result)).into_response())
     }
@@ -167,7 +171,7 @@ impl Candidate {
         sqlx::query_as!(
             Candidate,
             r#"
-            SELECT * FROM people.candidates WHERE session_id = $1
+            SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE
             "#,
             session_id
         )
@@ -176,6 +180,22 @@ impl Candidate {
         .map_err(|_| AppError::from(anyhow!("Cannot get all candidates with specific session_id")))
     }
 
+    pub async fn get_placeholders_by_session( // hidden from listings, only needed to check and show allocations
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<Candidate>, AppError> {
+        sqlx::query_as!(
+            Candidate,
+            r#"
+            SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = TRUE
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get all placeholder candidates with specific session_id")))
+    }
+
     pub async fn get_ava_all(
         pool: &sqlx::PgPool,
         session_id: &Uuid,
@@ -184,7 +204,7 @@ impl Candidate {
         sqlx::query_as!(
             Candidate,
             r#"
-            SELECT * FROM people.candidates WHERE session_id = $1 AND am = $2 AND pm = $3
+            SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND am = $2 AND pm = $3
             "#,
             session_id,
             ava.am,
@@ -203,7 +223,7 @@ impl Candidate {
         sqlx::query_as!(
             Candidate,
             r#"
-            SELECT * FROM people.candidates WHERE session_id = $1 AND female_only = TRUE AND am = $2 AND pm = $3
+            SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND female_only = TRUE AND am = $2 AND pm = $3
             "#,
             session_id,
             ava.am,
@@ -224,7 +244,7 @@ impl Candidate {
                 sqlx::query_as!(
                     Candidate,
                     r#"
-                    SELECT * FROM people.candidates WHERE session_id = $1 AND am = TRUE
+                    SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND am = TRUE
                     "#,
                     session_id
                 )
@@ -236,7 +256,7 @@ impl Candidate {
                 sqlx::query_as!(
                     Candidate,
                     r#"
-                    SELECT * FROM people.candidates WHERE session_id = $1 AND pm = TRUE
+                    SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND pm = TRUE
                     "#,
                     session_id
                 )
@@ -258,7 +278,7 @@ impl Candidate {
                 sqlx::query_as!(
                     Candidate,
                     r#"
-                    SELECT * FROM people.candidates WHERE session_id = $1 AND am = TRUE AND female_only = TRUE
+                    SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND am = TRUE AND female_only = TRUE
                     "#,
                     session_id,
                 )
@@ -270,7 +290,7 @@ impl Candidate {
                 sqlx::query_as!(
                     Candidate,
                     r#"
-                    SELECT * FROM people.candidates WHERE session_id = $1 AND pm = TRUE  AND female_only = TRUE
+                    SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND pm = TRUE  AND female_only = TRUE
                     "#,
                     session_id,
                 )
@@ -288,7 +308,7 @@ impl Candidate {
         sqlx::query_as!(
             Candidate,
             r#"
-            SELECT * FROM people.candidates WHERE session_id = $1 AND female_only = TRUE
+            SELECT * FROM people.candidates WHERE session_id = $1 AND placeholder = FALSE AND female_only = TRUE
             "#,
             session_id,
         )
@@ -322,13 +342,13 @@ impl Candidate {
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Session not found or you do not have permission for this operation"))?;
+        .context("Session not found or you do not have permission for this operation")?;
 
         let candidate = sqlx::query_as!(
             Candidate,
             r#"
-            INSERT INTO people.candidates (session_id, first_name, last_name, shortcode, female_only, partner_pref, checked_in, am, pm)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            INSERT INTO people.candidates (session_id, first_name, last_name, shortcode, female_only, partner_pref, checked_in, am, pm, partner_pref_2, avoid)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             RETURNING *
             "#,
             candidate.session_id,
@@ -340,13 +360,62 @@ impl Candidate {
             candidate.checked_in,
             candidate.am,
             candidate.pm,
+            candidate.partner_pref_2,
+            &candidate.avoid,
         )
         .fetch_one(&mut *transaction)
         .await
         .map_err(|_| AppError::from(anyhow!("Cannot create new candidate")));
 
-        transaction.commit().await.with_context(|| format!("Transaction failed to commit"))?;
-        return candidate
+        transaction.commit().await.context("Transaction failed to commit")?;
+        candidate
+    }
+
+    pub async fn create_fill_tx(
+        tx: &mut Transaction<'static, sqlx::Postgres>,
+        candidate: &Candidate,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO people.candidates (id, session_id, first_name, last_name, shortcode, female_only, partner_pref, checked_in, am, pm, partner_pref_2, avoid, placeholder)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            "#,
+            candidate.id,
+            candidate.session_id,
+            candidate.first_name,
+            candidate.last_name,
+            candidate.shortcode,
+            candidate.female_only,
+            candidate.partner_pref,
+            candidate.checked_in,
+            candidate.am,
+            candidate.pm,
+            candidate.partner_pref_2,
+            &candidate.avoid,
+            candidate.placeholder,
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to insert fill candidate by transaction")?;
+        Ok(())
+    }
+
+    pub async fn delete_unused_placeholders_tx( // placeholders the live allocation no longer has in a group
+        tx: &mut Transaction<'static, sqlx::Postgres>,
+        session_id: &Uuid,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM people.candidates c
+            WHERE c.session_id = $1 AND c.placeholder
+            AND NOT EXISTS (SELECT 1 FROM records.allocation_members m WHERE m.candidate_id = c.id)
+            "#,
+            session_id
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to delete unused placeholder candidates by transaction")?;
+        Ok(())
     }
 
     pub async fn update(
@@ -370,7 +439,7 @@ impl Candidate {
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Session not found or you do not have permission for this operation"))?;
+        .context("Session not found or you do not have permission for this operation")?;
 
         sqlx::query!(
             r#"
@@ -383,7 +452,9 @@ impl Candidate {
                 partner_pref = COALESCE($7, partner_pref),
                 checked_in = COALESCE($8, checked_in),
                 am = COALESCE($9, am),
-                pm = COALESCE($10, pm)
+                pm = COALESCE($10, pm),
+                partner_pref_2 = COALESCE($11, partner_pref_2),
+                avoid = COALESCE($12, avoid)
             WHERE id = $1 AND session_id = $2
             "#,
             candidate.id,
@@ -396,12 +467,14 @@ impl Candidate {
             candidate.checked_in,
             candidate.am,
             candidate.pm,
+            candidate.partner_pref_2,
+            candidate.avoid.as_deref(),
         )
         .execute(&mut *transaction)
         .await
-        .with_context(|| format!("Cannot update candidate"))?;
+        .context("Cannot update candidate")?;
 
-        transaction.commit().await.with_context(|| format!("Transaction failed to commit"))?;
+        transaction.commit().await.context("Transaction failed to commit")?;
 
         Ok(StatusCode::OK.into_response())
     }
@@ -423,7 +496,7 @@ impl Candidate {
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Session not found or you do not have permission for this operation"))?;
+        .context("Session not found or you do not have permission for this operation")?;
 
         sqlx::query!(
             r#"
@@ -435,9 +508,9 @@ impl Candidate {
         )
         .execute(&mut *transaction)
         .await
-        .with_context(|| format!("Failed to delete candidates"))?;
+        .context("Failed to delete candidates")?;
 
-        transaction.commit().await.with_context(|| format!("Transaction failed to commit"))?;
+        transaction.commit().await.context("Transaction failed to commit")?;
         Ok(())
     }
 }
\ No newline at end of file
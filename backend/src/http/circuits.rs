@@ -12,7 +12,7 @@ pub fn router() -> axum::Router<AppState> {
         .route("/get-slot", get(get_by_slot))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Circuit {
     pub id: Uuid,
     pub session_id: Uuid,
@@ -12,8 +12,8 @@ use crate::{error::AppError, http::{users::AccessClaims}};
 
 type HmacSha256 = Hmac<Sha256>;
 
-const CSRF_KEY: Lazy<String> = Lazy::new(|| { // should be replaced with external secret management
-    return dotenvy::var("CSRF_SECRET").expect("CSRF_SECRET must be set");
+static CSRF_KEY: Lazy<String> = Lazy::new(|| { // should be replaced with external secret management
+    dotenvy::var("CSRF_SECRET").expect("CSRF_SECRET must be set")
 });
 
 // -- REFACTOR --
@@ -1,3 +1,15 @@
 pub fn default_uuid_v4() -> uuid::Uuid {
     uuid::Uuid::new_v4()
-}
\ No newline at end of file
+}
+
+pub fn default_station_kind() -> String {
+    String::from("examined")
+}
+
+pub fn default_candidates_per_station() -> i16 {
+    2
+}
+
+pub fn default_timezone() -> String {
+    String::from("Europe/London")
+}
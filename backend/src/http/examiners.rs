@@ -6,10 +6,11 @@ use axum::{
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 use anyhow::{anyhow, Context};
+use sqlx::Transaction;
 
 use crate::error::AppError;
 
-use super::{runs::RunTime, users::{AccessClaims, User}, AppState, SomethingID, allocations::Availability};
+use super::{runs::RunTime, users::{AccessClaims, User}, AppState, SomethingID, allocations::Availability, candidates::PLACEHOLDER_NAME};
 
 pub fn router() -> axum::Router<AppState> {
     axum::Router::new()
@@ -31,6 +32,9 @@ pub struct Examiner {
     pub am: bool,
     pub pm: bool,
     pub checked_in: bool, 
+    pub tags: Vec<String>, // specialties, matched against station tags
+    #[serde(default)]
+    pub placeholder: bool, // holds a vacancy in an allocation, shown as "Vacancy"
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,6 +47,8 @@ pub struct ExaminerPayload {
     pub am: bool,
     pub pm: bool,
     pub checked_in: bool, 
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,7 +58,8 @@ pub struct ExaminerExcel {
     pub shortcode: String,
     pub female: bool,
     pub am: bool,
-    pub pm: bool
+    pub pm: bool,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,6 +73,7 @@ pub struct ExaminerChange {
     pub am: Option<bool>,
     pub pm: Option<bool>,
     pub checked_in: Option<bool>, 
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -93,11 +101,12 @@ async fn get_all_by_session(
 async fn create(
     State(pool): State<sqlx::PgPool>,
     Extension(claim): Extension<AccessClaims>,
-    Json(examiner): Json<ExaminerPayload>,
+    Json(mut examiner): Json<ExaminerPayload>,
 ) -> Result<impl IntoResponse, AppError> {
     if !User::is_admin(&pool, &claim.id).await? {
         return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
     }
+    examiner.tags = examiner.tags.iter().map(|tag| tag.trim().to_lowercase()).collect(); // matched exactly against station tags
     let result = Examiner::create(&pool, claim.organisation_id, examiner).await?;
     Ok((StatusCode::OK, Json(result)).into_response())
 }
@@ -105,40 +114,31 @@ async fn create(
 async fn update(
     State(pool): State<sqlx::PgPool>,
     Extension(claim): Extension<AccessClaims>,
-    Json(examiner): Json<ExaminerChange>,
+    Json(mut examiner): Json<ExaminerChange>,
 ) -> Result<impl IntoResponse, AppError> {
     if !User::is_admin(&pool, &claim.id).await? {
         return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
     }
+    examiner.tags = examiner.tags.map(|tags| tags.iter().map(|tag| tag.trim().to_lowercase()).collect());
     let result = Examiner::update(pool, claim.organisation_id, examiner).await?;
     Ok((StatusCode::OK, Json(result)).into_response())
 }
 
-pub async fn create_fill(session_id: Uuid, organisation_id: Uuid, pool: &sqlx::PgPool, time: Option<Availability>, female: bool) -> Result<Examiner, AppError> {
-    if let Some(exam_ava) = time {
-        let examiner = ExaminerPayload {
-            session_id,
-            first_name: "fill".to_string(),
-            last_name: "candidate".to_string(),
-            shortcode: Uuid::new_v4().to_string(),
-            female,
-            am: exam_ava.am,
-            pm: exam_ava.pm,
-            checked_in: false,
-        };
-        Examiner::create(pool, organisation_id, examiner).await
-    } else {
-        let examiner = ExaminerPayload {
-            session_id,
-            first_name: "fill".to_string(),
-            last_name: "candidate".to_string(),
-            shortcode: Uuid::new_v4().to_string(),
-            female,
-            am: true,
-            pm: true,
-            checked_in: false,
-        };
-        Examiner::create(pool, organisation_id, examiner).await
+// placeholder examiners only exist in memory until the allocation using them is saved
+pub fn create_fill(session_id: Uuid, time: Option<Availability>, female: bool) -> Examiner {
+    let exam_ava = time.unwrap_or(Availability { am: true, pm: true });
+    Examiner {
+        id: Uuid::new_v4(),
+        session_id,
+        first_name: PLACEHOLDER_NAME.to_string(),
+        last_name: String::new(),
+        shortcode: Uuid::new_v4().to_string(),
+        female,
+        am: exam_ava.am,
+        pm: exam_ava.pm,
+        checked_in: false,
+        tags: Vec::new(),
+        placeholder: true,
     }
 }
 
@@ -178,7 +178,7 @@ impl Examiner {
         sqlx::query_as!(
             Examiner,
             r#"
-            SELECT * FROM people.examiners WHERE session_id = $1
+            SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE
             "#,
             session_id
         )
@@ -197,7 +197,7 @@ impl Examiner {
                 sqlx::query_as!(
                     Examiner,
                     r#"
-                    SELECT * FROM people.examiners WHERE session_id = $1 AND am = TRUE
+                    SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND am = TRUE
                     "#,
                     session_id
                 )
@@ -209,7 +209,7 @@ impl Examiner {
                 sqlx::query_as!(
                     Examiner,
                     r#"
-                    SELECT * FROM people.examiners WHERE session_id = $1 AND pm = TRUE
+                    SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND pm = TRUE
                     "#,
                     session_id
                 )
@@ -230,7 +230,7 @@ impl Examiner {
                 sqlx::query_as!( 
                     Examiner,
                     r#"
-                    SELECT * FROM people.examiners WHERE session_id = $1 AND am = TRUE AND female = TRUE
+                    SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND am = TRUE AND female = TRUE
                     "#,
                     session_id,
                 )
@@ -242,7 +242,7 @@ impl Examiner {
                 sqlx::query_as!(
                     Examiner,
                     r#"
-                    SELECT * FROM people.examiners WHERE session_id = $1 AND pm = TRUE  AND female = TRUE
+                    SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND pm = TRUE  AND female = TRUE
                     "#,
                     session_id,
                 )
@@ -253,6 +253,22 @@ impl Examiner {
         }
     }
 
+    pub async fn get_placeholders_by_session( // hidden from listings, only needed to check and show allocations
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<Examiner>, AppError> {
+        sqlx::query_as!(
+            Examiner,
+            r#"
+            SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = TRUE
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get all placeholder examiners with specific session_id")))
+    }
+
     pub async fn get_ava_all(
         pool: &sqlx::PgPool,
         session_id: &Uuid,
@@ -261,7 +277,7 @@ impl Examiner {
         sqlx::query_as!(
             Examiner,
             r#"
-            SELECT * FROM people.examiners WHERE session_id = $1 AND am = $2 AND pm = $3
+            SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND am = $2 AND pm = $3
             "#,
             session_id,
             ava.am,
@@ -280,7 +296,7 @@ impl Examiner {
         sqlx::query_as!(
             Examiner,
             r#"
-            SELECT * FROM people.examiners WHERE session_id = $1 AND am = $2 AND pm = $3 AND female = TRUE
+            SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND am = $2 AND pm = $3 AND female = TRUE
             "#,
             session_id,
             ava.am,
@@ -298,7 +314,7 @@ impl Examiner {
         sqlx::query_as!(
             Examiner,
             r#"
-            SELECT * FROM people.examiners WHERE session_id = $1 AND female = TRUE
+            SELECT * FROM people.examiners WHERE session_id = $1 AND placeholder = FALSE AND female = TRUE
             "#,
             session_id,
         )
@@ -324,13 +340,13 @@ impl Examiner {
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Session not found or you do not have permission for this operation"))?;
+        .context("Session not found or you do not have permission for this operation")?;
 
         let examiner = sqlx::query_as!(
             Examiner,
             r#"
-            INSERT INTO people.examiners (session_id, first_name, last_name, shortcode, female, am, pm, checked_in)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            INSERT INTO people.examiners (session_id, first_name, last_name, shortcode, female, am, pm, checked_in, tags)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING *
             "#,
             examiner.session_id,
@@ -340,16 +356,62 @@ impl Examiner {
             examiner.female,
             examiner.am,
             examiner.pm,
-            examiner.checked_in
+            examiner.checked_in,
+            &examiner.tags
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Cannot create examiner"))?;
+        .context("Cannot create examiner")?;
 
-        transaction.commit().await.with_context(|| format!("Transaction failed to commit"))?;
+        transaction.commit().await.context("Transaction failed to commit")?;
         Ok(examiner)
     }
 
+    pub async fn create_fill_tx(
+        tx: &mut Transaction<'static, sqlx::Postgres>,
+        examiner: &Examiner,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO people.examiners (id, session_id, first_name, last_name, shortcode, female, am, pm, checked_in, tags, placeholder)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            examiner.id,
+            examiner.session_id,
+            examiner.first_name,
+            examiner.last_name,
+            examiner.shortcode,
+            examiner.female,
+            examiner.am,
+            examiner.pm,
+            examiner.checked_in,
+            &examiner.tags,
+            examiner.placeholder
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to insert fill examiner by transaction")?;
+        Ok(())
+    }
+
+    pub async fn delete_unused_placeholders_tx( // placeholders the live allocation no longer has at a station
+        tx: &mut Transaction<'static, sqlx::Postgres>,
+        session_id: &Uuid,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM people.examiners e
+            WHERE e.session_id = $1 AND e.placeholder
+            AND NOT EXISTS (SELECT 1 FROM records.allocations a WHERE a.examiner = e.id)
+            "#,
+            session_id
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to delete unused placeholder examiners by transaction")?;
+        Ok(())
+    }
+
     pub async fn update(
         pool: sqlx::PgPool,
         organisation_id: Uuid,
@@ -367,7 +429,7 @@ impl Examiner {
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Session not found or you do not have permission for this operation"))?;
+        .context("Session not found or you do not have permission for this operation")?;
 
         let examiner = sqlx::query_as!(
             Examiner,
@@ -380,7 +442,8 @@ impl Examiner {
                 female = COALESCE($6, female),
                 am = COALESCE($7, am),
                 pm = COALESCE($8, pm),
-                checked_in = COALESCE($9, checked_in)
+                checked_in = COALESCE($9, checked_in),
+                tags = COALESCE($10, tags)
             WHERE id = $1 AND session_id = $2
             RETURNING *
             "#,
@@ -392,13 +455,14 @@ impl Examiner {
             examiner.female,
             examiner.am,
             examiner.pm,
-            examiner.checked_in
+            examiner.checked_in,
+            examiner.tags.as_deref()
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Cannot update examiner"))?;
+        .context("Cannot update examiner")?;
 
-        transaction.commit().await.with_context(|| format!("Transaction failed to commit"))?;
+        transaction.commit().await.context("Transaction failed to commit")?;
         Ok(examiner)
     }
 
@@ -419,7 +483,7 @@ impl Examiner {
         )
         .fetch_one(&mut *transaction)
         .await
-        .with_context(|| format!("Session not found or you do not have permission for this operation"))?;
+        .context("Session not found or you do not have permission for this operation")?;
 
         sqlx::query!(
             r#"
@@ -431,7 +495,7 @@ impl Examiner {
         )
         .execute(&mut *transaction)
         .await
-        .with_context(|| format!("Failed to delete examiners"))?;
+        .context("Failed to delete examiners")?;
 
         transaction.commit().await
             .with_context(|| "Failed to commit changes to the database")?;
@@ -0,0 +1,178 @@
+use anyhow::{Context, anyhow};
+use axum::{extract::{Json, State, Query}, http::StatusCode, response::IntoResponse, routing::{get, post}, Extension};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use super::{users::AccessClaims, AppState, SomethingID};
+use crate::{error::AppError, http::users::User};
+
+pub fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/get-session-all", get(get_session_all))
+        .route("/create", post(create))
+        .route("/delete", post(delete))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Exclusion { // examiner must not examine the candidate
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub examiner_shortcode: String,
+    pub candidate_shortcode: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExclusionExcel {
+    pub examiner_shortcode: String,
+    pub candidate_shortcode: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExclusionPayload {
+    pub session_id: Uuid,
+    pub examiner_shortcode: String,
+    pub candidate_shortcode: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteExclusionPayload {
+    pub session_id: Uuid,
+    pub ids: Vec<Uuid>
+}
+
+async fn get_session_all(
+    State(pool): State<sqlx::PgPool>,
+    session_id: Query<SomethingID>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = Exclusion::get_all_by_session(&pool, &session_id.0.id).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn create(
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(exclusion): Json<ExclusionPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    let result = Exclusion::create(&pool, claim.organisation_id, exclusion).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+async fn delete(
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Json(exclusions): Json<DeleteExclusionPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::is_admin(&pool, &claim.id).await? {
+        return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
+    }
+    Exclusion::delete(pool, claim.organisation_id, exclusions).await?;
+    Ok((StatusCode::OK).into_response())
+}
+
+impl Exclusion {
+    pub async fn get_all_by_session(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<Exclusion>, AppError> {
+        sqlx::query_as!(
+            Exclusion,
+            r#"
+            SELECT * FROM people.exclusions WHERE session_id = $1
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get all exclusions with specific session_id")))
+    }
+
+    pub async fn create(
+        pool: &sqlx::PgPool,
+        organisation_id: Uuid,
+        exclusion: ExclusionPayload,
+    ) -> Result<Exclusion, AppError> {
+        let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+
+        sqlx::query!(
+            r#"
+            SELECT organisation_id FROM records.sessions
+            WHERE id = $1 AND organisation_id = $2
+            "#,
+            &exclusion.session_id, // given by client
+            &organisation_id // from claims
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Session not found or you do not have permission for this operation")?;
+
+        let result = sqlx::query_as!(
+            Exclusion,
+            r#"
+            INSERT INTO people.exclusions (session_id, examiner_shortcode, candidate_shortcode)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+            exclusion.session_id,
+            exclusion.examiner_shortcode.to_lowercase(),
+            exclusion.candidate_shortcode.to_lowercase(),
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot create new exclusion")))?;
+
+        transaction.commit().await.context("Transaction failed to commit")?;
+        Ok(result)
+    }
+
+    pub async fn delete(
+        pool: sqlx::PgPool,
+        organisation_id: Uuid,
+        exclusions: DeleteExclusionPayload,
+    ) -> Result<(), AppError> {
+        let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
+
+        sqlx::query!(
+            r#"
+            SELECT organisation_id FROM records.sessions
+            WHERE id = $1 AND organisation_id = $2
+            "#,
+            &exclusions.session_id, // given by client
+            &organisation_id // from claims
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Session not found or you do not have permission for this operation")?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM people.exclusions
+            WHERE id = ANY($1) AND session_id = $2
+            "#,
+            &exclusions.ids,
+            &exclusions.session_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete exclusions")?;
+
+        transaction.commit().await.context("Transaction failed to commit")?;
+        Ok(())
+    }
+
+    pub fn resolve( // (examiner id, candidate id) pairs, unknown shortcodes are ignored
+        exclusions: &[Exclusion],
+        examiners: &[super::examiners::Examiner],
+        candidates: &[super::candidates::Candidate],
+    ) -> Vec<(Uuid, Uuid)> {
+        exclusions
+            .iter()
+            .filter_map(|x| {
+                let examiner = examiners.iter().find(|e| e.shortcode == x.examiner_shortcode)?;
+                let candidate = candidates.iter().find(|c| c.shortcode == x.candidate_shortcode)?;
+                Some((examiner.id, candidate.id))
+            })
+            .collect()
+    }
+}
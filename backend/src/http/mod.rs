@@ -24,8 +24,11 @@ pub mod circuits;
 pub mod runs;
 pub mod candidates;
 pub mod examiners;
+pub mod actors;
+pub mod exclusions;
 mod upload;
-mod allocations;
+pub mod allocations;
+pub mod timetable;
 mod templates;
 mod pg_interval;
 mod option_pg_interval;
@@ -81,7 +84,11 @@ pub fn router_app(db: sqlx::PgPool) -> Router {
         .nest("/circuits", circuits::router())
         .nest("/examiners", examiners::router())
         .nest("/candidates", candidates::router())
+        .nest("/actors", actors::router())
+        .nest("/exclusions", exclusions::router())
         .nest("/allocations", allocations::router())
+        .nest("/timetable", timetable::router())
+        .merge(websocket::handler::router()) // live timetable announcements at /ws
         .nest("/templates", templates::router())
         .nest("/files", upload::router())
         .nest("/users", users::router()) //l they can login without jwt tokens, perhaps i should implement pre-session auth
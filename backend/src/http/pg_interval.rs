@@ -88,6 +88,6 @@ where
         }
     }
     
-    const FIELDS: &'static[&'static str] = &["months", "days", "microseconds"];
+    const FIELDS: &[&str] = &["months", "days", "microseconds"];
     deserializer.deserialize_struct("PgInterval", FIELDS, PgIntervalVisitor)
 }
\ No newline at end of file
@@ -12,7 +12,7 @@ pub fn router() -> axum::Router<AppState> {
         .route("/get-slot", get(get_by_slot))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Run {
     pub id: Uuid,
     pub slot_id: Uuid,
@@ -37,7 +37,7 @@ pub struct RunPayload {
 }
 
 #[derive(Debug)]
-pub enum RunTime {
+pub enum RunTime { // in the session's timezone
     AM, // runs that START before 12:00
     PM, // runs that START after 12:00
 }
@@ -77,25 +77,31 @@ impl Run {
                 sqlx::query_as!(
                     Run,
                     r#"
-                    SELECT * FROM records.runs WHERE slot_id = $1 AND EXTRACT(HOUR FROM scheduled_start) < 12
+                    SELECT r.* FROM records.runs r
+                    JOIN records.slots s ON r.slot_id = s.id
+                    JOIN records.sessions se ON s.session_id = se.id
+                    WHERE r.slot_id = $1 AND EXTRACT(HOUR FROM r.scheduled_start AT TIME ZONE se.timezone) < 12
                     "#,
                     slot_id
                 )
                 .fetch_all(pool)
                 .await
-                .map_err(|_| AppError::from(anyhow!("Unable to fetch all runs that start before 12pm in your timezone currently")))
+                .map_err(|_| AppError::from(anyhow!("Unable to fetch all runs that start before 12pm in the session's timezone")))
             },
             RunTime::PM => {
                 sqlx::query_as!(
                     Run,
                     r#"
-                    SELECT * FROM records.runs WHERE slot_id = $1 AND EXTRACT(HOUR FROM scheduled_start) > 11
+                    SELECT r.* FROM records.runs r
+                    JOIN records.slots s ON r.slot_id = s.id
+                    JOIN records.sessions se ON s.session_id = se.id
+                    WHERE r.slot_id = $1 AND EXTRACT(HOUR FROM r.scheduled_start AT TIME ZONE se.timezone) > 11
                     "#,
                     slot_id
                 )
                 .fetch_all(pool)
                 .await
-                .map_err(|_| AppError::from(anyhow!("Unable to fetch all runs that start after 12pm in your timezone currently")))
+                .map_err(|_| AppError::from(anyhow!("Unable to fetch all runs that start after 12pm in the session's timezone")))
             }
         }
     }
@@ -120,7 +126,7 @@ impl Run {
         )
         .fetch_one(&mut **tx)
         .await
-        .with_context(|| format!("Failed to create run from transaction"))?;
+        .context("Failed to create run from transaction")?;
 
         Ok(())
     }
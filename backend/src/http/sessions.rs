@@ -2,7 +2,7 @@ use anyhow::{Context, anyhow};
 use axum::{extract::{Json, Query, State}, http::StatusCode, response::IntoResponse, routing::{get, post}, Extension};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use super::{users::{AccessClaims, User}, AppState, SomethingID, SomethingMultipleID, circuits::Circuit, runs::Run, slots::{Slot, SlotPayload}, stations::{Station, StationPayload}};
+use super::{users::{AccessClaims, User}, AppState, candidates::Candidate, examiners::Examiner, SomethingID, SomethingMultipleID, default::{default_candidates_per_station, default_timezone}, circuits::Circuit, runs::Run, slots::{Slot, SlotPayload}, stations::{Station, StationPayload}, timetable::{check_schedule, is_timezone, local_run_starts, utc_offsets, ScheduleCondition, ScheduleIssue, Timing}};
 use crate::error::AppError;
 use sqlx::postgres::types::PgInterval;
 use tracing::{instrument, trace};
@@ -37,7 +37,11 @@ pub struct Session {
     pub static_at_end: bool,
     pub status: String,
     #[serde(with = "time::serde::iso8601")]
-    pub created_at: time::OffsetDateTime
+    pub created_at: time::OffsetDateTime,
+    pub require_examiner_continuity: bool, // otherwise only preferred by the solver
+    pub forbid_repeats: bool, // partners and examiners from earlier sessions, otherwise only penalised by the solver
+    pub candidates_per_station: i16, // 1 to 3
+    pub timezone: String, // IANA name, run times are stored in UTC
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -50,6 +54,14 @@ pub struct SessionPayload {
     #[serde(default, with = "crate::http::pg_interval")]
     pub intermission_duration: PgInterval,
     pub static_at_end: bool,
+    #[serde(default)]
+    pub require_examiner_continuity: bool,
+    #[serde(default)]
+    pub forbid_repeats: bool,
+    #[serde(default = "default_candidates_per_station")]
+    pub candidates_per_station: i16,
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
     // organiser_id and organisation_id are taken from the token claims
     // status default to 'new'
 }
@@ -57,12 +69,20 @@ pub struct SessionPayload {
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct CreateSessionPayload {
     pub session: SessionPayload,
-    #[validate(length(min = 1, max = 256, message = "Must have between 1 and 256 stations"))]
+    #[validate(length(min = 1, max = 256, message = "Must have between 1 and 256 stations"), nested)]
     pub stations: Vec<StationPayload>,
     #[validate(length(min = 1, max = 26, message = "Must have between 1 and 26 slots"), nested)]
     pub slots: Vec<SlotPayload> // runs and circuits inside slots
 }
 
+#[derive(Debug, Serialize)]
+pub struct CreatedSession {
+    #[serde(flatten)]
+    pub session: Session,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ScheduleIssue>, // overlapping slots
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SessionChange {
     pub id: Uuid,
@@ -77,6 +97,10 @@ pub struct SessionChange {
     #[serde(default, with = "crate::http::option_pg_interval")]
     pub intermission_duration: Option<PgInterval>,
     pub static_at_end: Option<bool>,
+    pub require_examiner_continuity: Option<bool>,
+    pub forbid_repeats: Option<bool>,
+    pub candidates_per_station: Option<i16>,
+    pub timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -112,13 +136,11 @@ impl Add for PgIntervalWrapper {
 
 impl AddAssign for PgIntervalWrapper {
     fn add_assign(&mut self, other: Self) {
-        *self = Self {
-            0: PgInterval {
-                months: self.0.months + other.0.months,
-                days: self.0.days + other.0.days,
-                microseconds: self.0.microseconds + other.0.microseconds,
-            }
-        };
+        *self = Self(PgInterval {
+            months: self.0.months + other.0.months,
+            days: self.0.days + other.0.days,
+            microseconds: self.0.microseconds + other.0.microseconds,
+        });
     }
 }
 
@@ -136,13 +158,11 @@ impl Sub for PgIntervalWrapper {
 
 impl SubAssign for PgIntervalWrapper {
     fn sub_assign(&mut self, other: Self) {
-        *self = Self {
-            0: PgInterval {
-                months: self.0.months - other.0.months,
-                days: self.0.days - other.0.days,
-                microseconds: self.0.microseconds - other.0.microseconds,
-            }
-        };
+        *self = Self(PgInterval {
+            months: self.0.months - other.0.months,
+            days: self.0.days - other.0.days,
+            microseconds: self.0.microseconds - other.0.microseconds,
+        });
     }
 }
 
@@ -176,59 +196,72 @@ impl Session {
     pub async fn create(
         State(pool): State<sqlx::PgPool>,
         Extension(claim): Extension<AccessClaims>,
-        Json(req): Json<CreateSessionPayload>,
+        Json(mut req): Json<CreateSessionPayload>,
     ) -> Result<impl IntoResponse, AppError> {
         if !User::is_admin(&pool, &claim.id).await? {
             return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
         }
 
         req.validate().map_err(|e| AppError::from(anyhow!("Invalid payload: {}", e)))?;
+        for station in req.stations.iter_mut() { // matched exactly against examiner tags
+            station.required_tags = station.required_tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+            station.preferred_tags = station.preferred_tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+        }
 
         let session_payload = req.session;
         let total_stations = req.stations.len() as i16;
+        if !(1..=3).contains(&session_payload.candidates_per_station) {
+            return Err(AppError::from(anyhow!("Candidates per station must be between 1 and 3")));
+        }
+        if !is_timezone(&pool, &session_payload.timezone).await? {
+            return Err(AppError::from(anyhow!("Unknown timezone: {}", session_payload.timezone)));
+        }
 
         let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
 
-        let mut runtime_duration = PgIntervalWrapper::from(session_payload.intermission_duration) * total_stations;
-        if session_payload.feedback {
-            if let Some(x) = session_payload.feedback_duration {
-                runtime_duration += PgIntervalWrapper::from(x) * total_stations;
-            } else {
-                return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
-            }
-        } else {
-            if session_payload.feedback_duration.is_some() {
-                return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
-            }
+        if session_payload.feedback && session_payload.feedback_duration.is_none() {
+            return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
+        }
+        if !session_payload.feedback && session_payload.feedback_duration.is_some() {
+            return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
         }
 
         // REFACTOR: make static at end calculated in backend, not passed from frontend
-        if let Some(st_duration) = req.stations.first() {
-            for i in 0..req.stations.len() { // station duration checker
-                if i == req.stations.len() - 1 && session_payload.static_at_end { // check if the last station is different only if static at end is true
-                    break;
-                }
-                if req.stations[i].duration != st_duration.duration { // check all stations have the same duration
-                    return Err(AppError::from(anyhow!("Stations have different durations. Try turning on static at end.")));
-                }
-            }
-            runtime_duration += PgIntervalWrapper::from(st_duration.duration) * (total_stations - 1);
-            if let Some(last_station) = req.stations.last() {
-                runtime_duration += PgIntervalWrapper::from(last_station.duration); // incase static at end is true
-            } else {
-                return Err(AppError::from(anyhow!("No stations have been provided")));
-            }
-        } else {
-            return Err(AppError::from(anyhow!("No stations have been provided")));
+        // rest stations last as long as the rotation, so only examined and written stations set the duration
+        let mut timed_stations: Vec<&StationPayload> = req.stations.iter().filter(|s| s.kind != "rest").collect();
+        timed_stations.sort_by_key(|s| s.index); // same order as the timetable
+        let timed_durations: Vec<&PgInterval> = timed_stations.iter().map(|s| &s.duration).collect();
+        let timing = Timing::new(
+            total_stations,
+            &timed_durations,
+            session_payload.feedback_duration.as_ref(),
+            &session_payload.intermission_duration,
+            session_payload.static_at_end,
+        )?;
+        let runtime_duration = PgInterval::try_from(timing.runtime())
+            .map_err(|_| AppError::from(anyhow!("Run duration cannot be stored")))?;
+
+        trace!("Total runtime for 1x run is {:?}", timing.runtime());
+
+        let starts: Vec<time::OffsetDateTime> = req.slots.iter().flat_map(|slot| slot.runs.iter().map(|run| run.scheduled_start)).collect();
+        let mut offsets = utc_offsets(&pool, &session_payload.timezone, &starts).await?.into_iter();
+        let local_starts: Vec<Vec<time::OffsetDateTime>> = req.slots
+            .iter()
+            .map(|slot| slot.runs.iter().zip(offsets.by_ref()).map(|(run, offset)| run.scheduled_start.to_offset(offset)).collect())
+            .collect();
+        // a new session has no people yet, so slot overlaps cannot name anyone they double-book until the timetable is fetched
+        let (warnings, errors): (Vec<ScheduleIssue>, Vec<ScheduleIssue>) = check_schedule(&local_starts, timing.runtime())
+            .into_iter()
+            .partition(|issue| issue.condition == ScheduleCondition::SlotOverlap);
+        if !errors.is_empty() {
+            return Err(AppError::InvalidSchedule(errors));
         }
 
-        trace!("Total runtime for 1x run is {:?}", runtime_duration);
-
         let session_result = sqlx::query_as!(
             Session,
             r#"
-            INSERT INTO records.sessions (organiser_id, organisation_id, scheduled_date, location, total_stations, feedback, feedback_duration, intermission_duration, static_at_end)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            INSERT INTO records.sessions (organiser_id, organisation_id, scheduled_date, location, total_stations, feedback, feedback_duration, intermission_duration, static_at_end, require_examiner_continuity, forbid_repeats, candidates_per_station, timezone)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
             RETURNING *
             "#,
             &claim.id,
@@ -239,10 +272,14 @@ impl Session {
             session_payload.feedback,
             session_payload.feedback_duration,
             session_payload.intermission_duration,
-            session_payload.static_at_end)
+            session_payload.static_at_end,
+            session_payload.require_examiner_continuity,
+            session_payload.forbid_repeats,
+            session_payload.candidates_per_station,
+            session_payload.timezone)
             .fetch_one(&mut *transaction)
             .await
-            .with_context(|| format!("Failed to create session from transaction"))?;
+            .context("Failed to create session from transaction")?;
         
         for station in &req.stations {
             Station::create_tx(&mut transaction, &session_result.id, station).await?;
@@ -253,9 +290,8 @@ impl Session {
         for (slot, key) in req.slots.iter().zip(slot_keys) {
             let slot_result = Slot::create_tx(&mut transaction, &session_result.id, key.to_string()).await?;
 
-            // REFACTOR: CHECK WHETHER RUNS HAVE THE CORRECT START + END TIME, WHETHER IT OVERLAPS
             for run in &slot.runs {
-                Run::create_tx(&mut transaction, &slot_result.id, run, &runtime_duration.0).await?;
+                Run::create_tx(&mut transaction, &slot_result.id, run, &runtime_duration).await?;
             }
 
             let circuit_keys: &[char] = &('A'..='Z').collect::<Vec<char>>()[..slot.circuits.len()];
@@ -264,9 +300,9 @@ impl Session {
             }
         }
 
-        transaction.commit().await.with_context(|| format!("Transaction failed to commit. Rolled back successful."))?;
+        transaction.commit().await.context("Transaction failed to commit. Rolled back successful.")?;
         
-        Ok((StatusCode::CREATED, Json(session_result)).into_response())
+        Ok((StatusCode::CREATED, Json(CreatedSession { session: session_result, warnings })).into_response())
     }
 
     pub async fn get(
@@ -282,7 +318,7 @@ impl Session {
         )
         .fetch_one(&pool)
         .await
-        .with_context(|| format!("Cannot get session with specific id"))?;
+        .context("Cannot get session with specific id")?;
 
         Ok((StatusCode::OK, Json(result)).into_response())
     }
@@ -303,7 +339,7 @@ impl Session {
         let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM records.sessions")
             .fetch_one(&pool)
             .await
-            .with_context(|| format!("Cannot get sessions count"))?;
+            .context("Cannot get sessions count")?;
 
         let sessions = match (sort_field.as_str(), is_ascending) {
             ("created_at", true) => sqlx::query_as!(
@@ -319,7 +355,7 @@ impl Session {
             )
             .fetch_all(&pool)
             .await
-            .with_context(|| format!("Cannot get sessions page"))?,
+            .context("Cannot get sessions page")?,
     
             ("created_at", false) => sqlx::query_as!(
                 Session,
@@ -334,7 +370,7 @@ impl Session {
             )
             .fetch_all(&pool)
             .await
-            .with_context(|| format!("Cannot get sessions page"))?,
+            .context("Cannot get sessions page")?,
     
             // Default to scheduled_date (both true and false cases)
             (_, true) => sqlx::query_as!(
@@ -350,7 +386,7 @@ impl Session {
             )
             .fetch_all(&pool)
             .await
-            .with_context(|| format!("Cannot get sessions page"))?,
+            .context("Cannot get sessions page")?,
     
             (_, false) => sqlx::query_as!(
                 Session,
@@ -365,7 +401,7 @@ impl Session {
             )
             .fetch_all(&pool)
             .await
-            .with_context(|| format!("Cannot get sessions page"))?,
+            .context("Cannot get sessions page")?,
         };
 
         Ok((StatusCode::OK, Json(PaginationResponse { sessions, total })).into_response())
@@ -384,7 +420,7 @@ impl Session {
         )
         .fetch_all(&pool)
         .await
-        .with_context(|| format!("Cannot get all sessions by organisation"))?;
+        .context("Cannot get all sessions by organisation")?;
 
         Ok((StatusCode::OK, Json(sessions)).into_response())
     }
@@ -398,14 +434,72 @@ impl Session {
             return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
         }
 
-        if session.feedback == Some(true) {
-            if session.feedback_duration.is_none() {
-                return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
+        if session.feedback == Some(true) && session.feedback_duration.is_none() {
+            return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
+        }
+        if session.feedback_duration.is_some() && session.feedback == Some(false) {
+            return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
+        }
+        if session.candidates_per_station.is_some_and(|n| !(1..=3).contains(&n)) {
+            return Err(AppError::from(anyhow!("Candidates per station must be between 1 and 3")));
+        }
+        if let Some(timezone) = &session.timezone {
+            if !is_timezone(&pool, timezone).await? {
+                return Err(AppError::from(anyhow!("Unknown timezone: {}", timezone)));
+            }
+            let current = sqlx::query_as!(
+                Session,
+                r#"
+                SELECT * FROM records.sessions WHERE id = $1 AND organiser_id = $2
+                "#,
+                session.id,
+                session.organiser_id
+            )
+            .fetch_one(&pool)
+            .await
+            .context("Session not found or you do not have permission for this operation")?;
+
+            // runs keep their instants, so their local times move with the timezone and are checked again like at creation
+            if current.timezone != *timezone {
+                let stations = Station::get_by_session(&pool, &session.id).await?;
+                let timing = Timing::from_session(&current, &stations)?;
+                let run_starts = local_run_starts(&pool, &session.id, timezone).await?;
+                let errors: Vec<ScheduleIssue> = check_schedule(&run_starts, timing.runtime())
+                    .into_iter()
+                    .filter(|issue| issue.condition != ScheduleCondition::SlotOverlap)
+                    .collect();
+                if !errors.is_empty() {
+                    return Err(AppError::InvalidSchedule(errors));
+                }
             }
         }
-        if session.feedback_duration.is_some() {
-            if session.feedback == Some(false) {
-                return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
+
+        let mut transaction = pool.begin().await.context("Unable to create a transaction in database")?;
+        if let Some(candidates_per_station) = session.candidates_per_station {
+            // the live allocation's groups are sized for the old setting, so it is cleared to be generated again. History is kept
+            let cleared = sqlx::query!(
+                r#"
+                DELETE FROM records.allocations
+                WHERE slot_id IN (
+                    SELECT sl.id FROM records.slots sl
+                    JOIN records.sessions s ON s.id = sl.session_id
+                    WHERE s.id = $1 AND s.organiser_id = $2 AND s.candidates_per_station <> $3
+                )
+                "#,
+                session.id,
+                session.organiser_id,
+                candidates_per_station
+            )
+            .execute(&mut *transaction)
+            .await
+            .context("Cannot clear the allocation of the session")?;
+            if cleared.rows_affected() > 0 {
+                Candidate::delete_unused_placeholders_tx(&mut transaction, &session.id).await?;
+                Examiner::delete_unused_placeholders_tx(&mut transaction, &session.id).await?;
+                sqlx::query!("UPDATE records.sessions SET status = 'prep' WHERE id = $1 AND status = 'allocated'", session.id)
+                    .execute(&mut *transaction)
+                    .await
+                    .context("Failed to change session allocation status")?;
             }
         }
 
@@ -419,7 +513,11 @@ impl Session {
                 feedback = COALESCE($6, feedback),
                 feedback_duration = COALESCE($7, feedback_duration),
                 intermission_duration = COALESCE($8, intermission_duration),
-                static_at_end = COALESCE($9, static_at_end)
+                static_at_end = COALESCE($9, static_at_end),
+                require_examiner_continuity = COALESCE($10, require_examiner_continuity),
+                forbid_repeats = COALESCE($11, forbid_repeats),
+                candidates_per_station = COALESCE($12, candidates_per_station),
+                timezone = COALESCE($13, timezone)
             WHERE id = $1 AND organiser_id = $2
             "#,
             session.id,
@@ -430,12 +528,18 @@ impl Session {
             session.feedback,
             session.feedback_duration,
             session.intermission_duration,
-            session.static_at_end
+            session.static_at_end,
+            session.require_examiner_continuity,
+            session.forbid_repeats,
+            session.candidates_per_station,
+            session.timezone
         )
-        .execute(&pool)
+        .execute(&mut *transaction)
         .await
         .with_context(|| format!("Cannot update session: {}", session.id))?;
 
+        transaction.commit().await.context("Transaction failed to commit. Rolled back successful.")?;
+
         Ok(StatusCode::OK.into_response())
     }
 
@@ -459,7 +563,7 @@ impl Session {
         )
         .execute(&pool)
         .await
-        .with_context(|| format!("Cannot delete sessions"))?;
+        .context("Cannot delete sessions")?;
 
         Ok(StatusCode::OK.into_response())
     }
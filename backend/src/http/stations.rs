@@ -4,8 +4,12 @@ use uuid::Uuid;
 use crate::error::AppError;
 use sqlx::{postgres::types::PgInterval, Transaction};
 use anyhow::{Context, anyhow};
+use validator::{Validate, ValidationError};
 
-use super::{SomethingID, AppState};
+use super::{SomethingID, AppState, default::default_station_kind};
+
+pub const STATION_KINDS: [&str; 3] = ["examined", "rest", "written"]; // written stations are unmanned
+pub const ACTOR_GENDERS_REQUIRED: [&str; 2] = ["female", "male"];
 
 pub fn router() -> axum::Router<AppState> {
     axum::Router::new()
@@ -20,14 +24,52 @@ pub struct Station {
     pub index: i16,
     #[serde(default, with = "crate::http::pg_interval")]
     pub duration: PgInterval,
+    #[serde(default)]
+    pub required_tags: Vec<String>, // examiner must have all of them
+    #[serde(default)]
+    pub preferred_tags: Vec<String>, // examiners with them are favoured
+    #[serde(default = "default_station_kind")]
+    pub kind: String, // one of STATION_KINDS, only examined stations get an examiner
+    #[serde(default)]
+    pub needs_actor: bool, // played by a simulated patient
+    #[serde(default)]
+    pub actor_gender: Option<String>, // one of ACTOR_GENDERS_REQUIRED, any actor when None
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct StationPayload {
     pub title: String,
     pub index: i16,
     #[serde(default, with = "crate::http::pg_interval")]
     pub duration: PgInterval,
+    #[serde(default)]
+    pub required_tags: Vec<String>, // examiner must have all of them
+    #[serde(default)]
+    pub preferred_tags: Vec<String>, // examiners with them are favoured
+    #[serde(default = "default_station_kind")]
+    #[validate(custom(function = "validate_kind"))]
+    pub kind: String,
+    #[serde(default)]
+    pub needs_actor: bool,
+    #[serde(default)]
+    #[validate(custom(function = "validate_actor_gender"))]
+    pub actor_gender: Option<String>,
+}
+
+pub fn validate_kind(kind: &str) -> Result<(), ValidationError> {
+    if STATION_KINDS.contains(&kind) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("station_kind").with_message(format!("Station kind must be one of: {}", STATION_KINDS.join(", ")).into()))
+    }
+}
+
+pub fn validate_actor_gender(gender: &str) -> Result<(), ValidationError> {
+    if ACTOR_GENDERS_REQUIRED.contains(&gender) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("actor_gender").with_message(format!("Actor gender must be one of: {}", ACTOR_GENDERS_REQUIRED.join(", ")).into()))
+    }
 }
 
 async fn get_by_session(
@@ -55,14 +97,30 @@ impl Station {
         .map_err(|_| AppError::from(anyhow!("Cannot get stations with session_id: {}", session_id)));
     }
 
-    pub async fn get_not_rest_by_session( // get all stations except rest station, for examiner allocation
+    pub async fn get_examined_by_session( // get all stations that need an examiner, for examiner allocation
         pool: &sqlx::PgPool,
         session_id: &Uuid,
     ) -> Result<Vec<Station>, AppError> {
         return sqlx::query_as!(
             Station,
             r#"
-            SELECT * FROM records.stations WHERE session_id = $1 AND LOWER(title) != 'rest'
+            SELECT * FROM records.stations WHERE session_id = $1 AND kind = 'examined'
+            "#,
+            session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AppError::from(anyhow!("Cannot get stations with session_id: {}", session_id)));
+    }
+
+    pub async fn get_with_actor_by_session( // get all stations played by an actor, for actor allocation
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+    ) -> Result<Vec<Station>, AppError> {
+        return sqlx::query_as!(
+            Station,
+            r#"
+            SELECT * FROM records.stations WHERE session_id = $1 AND needs_actor = TRUE
             "#,
             session_id
         )
@@ -79,17 +137,26 @@ impl Station {
         sqlx::query_as!(
             Station,
             r#"
-            INSERT INTO records.stations (session_id, title, index, duration)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO records.stations (session_id, title, index, duration, required_tags, preferred_tags, kind, needs_actor, actor_gender)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING *
             "#,
             session_id,
             payload.title,
             payload.index,
-            payload.duration)
+            payload.duration,
+            &payload.required_tags,
+            &payload.preferred_tags,
+            payload.kind,
+            payload.needs_actor,
+            payload.actor_gender)
             .fetch_one(&mut **tx)
             .await
-            .with_context(|| format!("Failed to insert stations by transaction"))?;
+            .context("Failed to insert stations by transaction")?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub fn is_examined(&self) -> bool {
+        self.kind == "examined"
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::{Context, anyhow};
 use axum::{extract::{Json, State}, http::StatusCode, response::IntoResponse, routing::{get, post}, Extension};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use super::{users::{AccessClaims, User}, AppState, SomethingID, SomethingMultipleID};
+use super::{users::{AccessClaims, User}, stations::{validate_kind, validate_actor_gender}, default::default_station_kind, AppState, SomethingID, SomethingMultipleID};
 use crate::error::AppError;
 use sqlx::postgres::types::PgInterval;
 use tracing::instrument;
@@ -86,20 +86,18 @@ pub struct TemplateStation {
     pub index: i16,
     #[serde(default, with = "crate::http::pg_interval")]
     pub duration: PgInterval,
+    #[serde(default)]
+    pub required_tags: Vec<String>, // examiner must have all of them
+    #[serde(default)]
+    pub preferred_tags: Vec<String>, // examiners with them are favoured
+    #[serde(default = "default_station_kind")]
+    pub kind: String, // one of STATION_KINDS, only examined stations get an examiner
+    #[serde(default)]
+    pub needs_actor: bool, // played by a simulated patient
+    #[serde(default)]
+    pub actor_gender: Option<String>, // one of ACTOR_GENDERS_REQUIRED, any actor when None
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
-pub struct TemplateStationChange {
-    pub id: Uuid,
-    pub template_id: Uuid,
-    #[validate(length(min = 1))]
-    pub title: Option<String>,
-    pub index: Option<i16>,
-    #[serde(default, with = "crate::http::option_pg_interval")]
-    pub duration: Option<PgInterval>,
-}
-
-
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct TemplateStationPayload {
     #[validate(length(min = 1))]
@@ -107,6 +105,18 @@ pub struct TemplateStationPayload {
     pub index: i16,
     #[serde(default, with = "crate::http::pg_interval")]
     pub duration: PgInterval,
+    #[serde(default)]
+    pub required_tags: Vec<String>, // examiner must have all of them
+    #[serde(default)]
+    pub preferred_tags: Vec<String>, // examiners with them are favoured
+    #[serde(default = "default_station_kind")]
+    #[validate(custom(function = "validate_kind"))]
+    pub kind: String,
+    #[serde(default)]
+    pub needs_actor: bool,
+    #[serde(default)]
+    #[validate(custom(function = "validate_actor_gender"))]
+    pub actor_gender: Option<String>,
 }
 
 
@@ -115,24 +125,24 @@ impl TemplateSession {
     pub async fn create(
         State(pool): State<sqlx::PgPool>,
         Extension(claim): Extension<AccessClaims>,
-        Json(req): Json<CreateTemplatePayload>,
+        Json(mut req): Json<CreateTemplatePayload>,
     ) -> Result<impl IntoResponse, AppError> { // validate that if static_at_end is on, there should only be 1 station that has different times than others
         if !User::is_admin(&pool, &claim.id).await? {
             return Ok((StatusCode::FORBIDDEN, "You do not have access to perform this operation").into_response())
         }
         req.validate().with_context(|| "Incorrect formatting")?;
+        for station in req.template_stations.iter_mut() { // copied to the stations of sessions made from the template
+            station.required_tags = station.required_tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+            station.preferred_tags = station.preferred_tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+        }
         let session_payload = req.template_session;
         let total_stations = req.template_stations.len() as i16;
 
-        if session_payload.feedback {
-            if session_payload.feedback_duration.is_none() {
-                return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
-            }
+        if session_payload.feedback && session_payload.feedback_duration.is_none() {
+            return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
         }
-        if session_payload.feedback_duration.is_some() {
-            if !session_payload.feedback {
-                return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
-            }
+        if session_payload.feedback_duration.is_some() && !session_payload.feedback {
+            return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
         }
 
         let mut transaction = pool.begin().await.with_context(|| "Unable to create a transaction in database")?;
@@ -167,14 +177,19 @@ impl TemplateSession {
             let station_result = sqlx::query_as!(
                 TemplateStation,
                 r#"
-                INSERT INTO templates.stations (template_id, title, index, duration)
-                VALUES ($1, $2, $3, $4)
+                INSERT INTO templates.stations (template_id, title, index, duration, required_tags, preferred_tags, kind, needs_actor, actor_gender)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 RETURNING *
                 "#,
                 &session_result.id,
                 station.title,
                 station.index,
-                station.duration)
+                station.duration,
+                &station.required_tags,
+                &station.preferred_tags,
+                station.kind,
+                station.needs_actor,
+                station.actor_gender)
                 .fetch_one(&mut *transaction)
                 .await;
 
@@ -184,7 +199,7 @@ impl TemplateSession {
             }
         }
 
-        transaction.commit().await.with_context(|| format!("Rolled back successful. Transaction failed to commit"))?;
+        transaction.commit().await.context("Rolled back successful. Transaction failed to commit")?;
         
         Ok((StatusCode::CREATED, Json(session_result)).into_response())
     }
@@ -202,7 +217,7 @@ impl TemplateSession {
         )
         .fetch_one(&pool)
         .await
-        .with_context(|| format!("Cannot get template session with specific id"))?;
+        .context("Cannot get template session with specific id")?;
 
         let stations = sqlx::query_as!(
             TemplateStation,
@@ -211,7 +226,7 @@ impl TemplateSession {
         )
         .fetch_all(&pool)
         .await
-        .with_context(|| format!("Cannot get template stations from session"))?;
+        .context("Cannot get template stations from session")?;
 
         let session_stations = TemplateSessionWithStations {
             id: result.id,
@@ -238,7 +253,7 @@ impl TemplateSession {
         )
         .fetch_all(&pool)
         .await
-        .with_context(|| format!("Cannot get all template session"))?;
+        .context("Cannot get all template session")?;
 
         let session_ids: Vec<Uuid> = sessions_result.iter().map(|s| s.id).collect();
         let stations = if !session_ids.is_empty() {
@@ -249,7 +264,7 @@ impl TemplateSession {
             )
             .fetch_all(&pool)
             .await
-            .with_context(|| format!("Cannot get template stations from all session"))?
+            .context("Cannot get template stations from all session")?
         } else {
             Vec::new()
         };
@@ -289,15 +304,11 @@ impl TemplateSession {
         }
         session.validate().with_context(|| "Incorrect formatting")?;
 
-        if session.feedback == Some(true) {
-            if session.feedback_duration.is_none() {
-                return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
-            }
+        if session.feedback == Some(true) && session.feedback_duration.is_none() {
+            return Err(AppError::from(anyhow!("Feedback set to true but feedback duration missing")));
         }
-        if session.feedback_duration.is_some() {
-            if session.feedback == Some(false) {
-                return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
-            }
+        if session.feedback_duration.is_some() && session.feedback == Some(false) {
+            return Err(AppError::from(anyhow!("Feedback duration is given but feedback is set to false")));
         }
 
         let _ = sqlx::query!(
@@ -344,7 +355,7 @@ impl TemplateSession {
         )
         .execute(&pool)
         .await
-        .with_context(|| format!("Cannot delete template sessions"))?;
+        .context("Cannot delete template sessions")?;
 
         Ok(StatusCode::OK.into_response())
     }
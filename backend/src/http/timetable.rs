@@ -0,0 +1,524 @@
+use axum::{extract::{Extension, Json, Query, State}, http::StatusCode, response::IntoResponse, routing::get};
+use anyhow::{anyhow, Context};
+use serde::Serialize;
+use sqlx::postgres::types::PgInterval;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use uuid::Uuid;
+use crate::{allocation_algo::can_attend, error::AppError};
+
+use super::{allocations::Availability, candidates::Candidate, circuits::Circuit, examiners::Examiner, runs::Run, sessions::Session, slots::Slot, stations::Station, users::AccessClaims, AppState, SomethingID};
+
+pub fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/get-session", get(get_by_session))
+}
+
+#[derive(Debug, Clone)]
+pub struct Timing { // durations of 1 run, same for every circuit in the session
+    pub rotations: i16, // 1 per station, rest stations included
+    pub station: Duration, // every timed station lasts as long
+    pub static_station: Option<Duration>, // last timed station, only when static at end
+    pub feedback: Option<Duration>,
+    pub intermission: Duration,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseKind {
+    Station,
+    StaticStation,
+    Feedback,
+    Intermission,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Phase {
+    pub kind: PhaseKind,
+    #[serde(with = "time::serde::iso8601")]
+    pub start: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub end: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Rotation {
+    pub rotation: i16, // starts at 1
+    pub phases: Vec<Phase>, // station, feedback then intermission
+}
+
+#[derive(Debug, Serialize)]
+pub struct CircuitTimetable {
+    pub circuit_id: Uuid,
+    pub key: String,
+    pub rotations: Vec<Rotation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunTimetable {
+    pub run_id: Uuid,
+    pub slot_id: Uuid,
+    pub slot_key: String,
+    #[serde(with = "time::serde::iso8601")]
+    pub scheduled_start: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub scheduled_end: OffsetDateTime,
+    pub circuits: Vec<CircuitTimetable>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Timetable {
+    pub session_id: Uuid,
+    pub timezone: String, // times are given with the session's UTC offset
+    pub runs: Vec<RunTimetable>, // in order of scheduled start
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ScheduleIssue>, // overlapping slots, with the people they could double-book
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleCondition {
+    RunOverlap, // runs of 1 slot share their examiners and candidates
+    PastMidnight,
+    SlotOverlap, // only a warning, the solver never puts anyone in 2 overlapping slots
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleIssue { // points at the run in the create session payload, or by start time for a stored session
+    pub condition: ScheduleCondition,
+    pub slot_index: usize,
+    pub slot_key: String,
+    pub run_index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other_slot_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other_run_index: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub examiners: Vec<ScheduledPerson>, // slot overlaps only, examiners available for both runs
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<ScheduledPerson>, // slot overlaps only, candidates who can attend both slots
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ScheduledPerson { // someone a slot overlap could double-book
+    pub id: Uuid,
+    pub shortcode: String,
+}
+
+pub async fn is_timezone(pool: &sqlx::PgPool, timezone: &str) -> Result<bool, AppError> { // IANA names known to the database
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "exists!"
+        "#,
+        timezone
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_| AppError::from(anyhow!("Cannot check timezone: {}", timezone)))
+}
+
+pub async fn utc_offsets( // offset of the timezone at each instant, daylight saving included
+    pool: &sqlx::PgPool,
+    timezone: &str,
+    instants: &[OffsetDateTime],
+) -> Result<Vec<UtcOffset>, AppError> {
+    let seconds = sqlx::query_scalar!(
+        r#"
+        SELECT EXTRACT(EPOCH FROM (t.instant AT TIME ZONE $2) - (t.instant AT TIME ZONE 'UTC'))::int AS "seconds!"
+        FROM UNNEST($1::timestamptz[]) WITH ORDINALITY AS t(instant, n)
+        ORDER BY t.n
+        "#,
+        instants,
+        timezone
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_| AppError::from(anyhow!("Cannot get UTC offsets in timezone: {}", timezone)))?;
+
+    seconds
+        .into_iter()
+        .map(|s| UtcOffset::from_whole_seconds(s).map_err(|_| AppError::from(anyhow!("Invalid UTC offset in timezone: {}", timezone))))
+        .collect()
+}
+
+pub async fn local_to_utc( // instant of a wall-clock time in the timezone, daylight saving included
+    pool: &sqlx::PgPool,
+    timezone: &str,
+    local: PrimitiveDateTime,
+) -> Result<OffsetDateTime, AppError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT ($1::timestamp AT TIME ZONE $2) AS "instant!"
+        "#,
+        local,
+        timezone
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_| AppError::from(anyhow!("Cannot convert local time in timezone: {}", timezone)))
+}
+
+pub async fn local_run_starts( // per slot in key order, runs in order of start, as check_schedule takes them
+    pool: &sqlx::PgPool,
+    session_id: &Uuid,
+    timezone: &str,
+) -> Result<Vec<Vec<OffsetDateTime>>, AppError> {
+    let mut slots = Slot::get_all_by_session(pool, session_id).await?;
+    slots.sort_by(|a, b| a.key.cmp(&b.key));
+    let mut run_starts = Vec::new();
+    for slot in slots {
+        let mut starts: Vec<OffsetDateTime> = Run::get_all_by_slot(pool, &slot.id).await?.iter().map(|run| run.scheduled_start).collect();
+        starts.sort();
+        let offsets = utc_offsets(pool, timezone, &starts).await?;
+        run_starts.push(starts.into_iter().zip(offsets).map(|(start, offset)| start.to_offset(offset)).collect());
+    }
+    Ok(run_starts)
+}
+
+pub fn interval_to_duration(interval: &PgInterval) -> Result<Duration, AppError> {
+    if interval.months != 0 { // months have no fixed length
+        return Err(AppError::from(anyhow!("Durations cannot be given in months")));
+    }
+    Ok(Duration::days(interval.days as i64) + Duration::microseconds(interval.microseconds))
+}
+
+impl Timing {
+    pub fn new(
+        total_stations: i16,
+        timed_durations: &[&PgInterval], // examined and written stations in order, rest stations last as long as the rotation
+        feedback_duration: Option<&PgInterval>,
+        intermission_duration: &PgInterval,
+        static_at_end: bool,
+    ) -> Result<Timing, AppError> {
+        let (Some(first), Some(last)) = (timed_durations.first(), timed_durations.last()) else {
+            return Err(AppError::from(anyhow!("No examined or written stations have been provided")));
+        };
+        let common = if static_at_end { &timed_durations[..timed_durations.len() - 1] } else { timed_durations };
+        if common.iter().any(|duration| duration != first) { // only the last station can differ, and only if static at end is true
+            return Err(AppError::from(anyhow!("Stations have different durations. Try turning on static at end.")));
+        }
+
+        Ok(Timing {
+            rotations: total_stations,
+            station: interval_to_duration(first)?,
+            static_station: if static_at_end { Some(interval_to_duration(last)?) } else { None },
+            feedback: feedback_duration.map(interval_to_duration).transpose()?,
+            intermission: interval_to_duration(intermission_duration)?,
+        })
+    }
+
+    pub fn from_session(session: &Session, stations: &[Station]) -> Result<Timing, AppError> {
+        let mut timed_stations: Vec<&Station> = stations.iter().filter(|s| s.kind != "rest").collect();
+        timed_stations.sort_by_key(|s| s.index);
+        let timed_durations: Vec<&PgInterval> = timed_stations.iter().map(|s| &s.duration).collect();
+        let feedback_duration = if session.feedback { session.feedback_duration.as_ref() } else { None };
+        Timing::new(session.total_stations, &timed_durations, feedback_duration, &session.intermission_duration, session.static_at_end)
+    }
+
+    fn station_duration(&self, rotation: i16) -> (PhaseKind, Duration) {
+        match self.static_station {
+            Some(duration) if rotation == self.rotations => (PhaseKind::StaticStation, duration),
+            _ => (PhaseKind::Station, self.station),
+        }
+    }
+
+    pub fn runtime(&self) -> Duration { // scheduled_end - scheduled_start of every run
+        (1..=self.rotations)
+            .map(|rotation| self.station_duration(rotation).1 + self.feedback.unwrap_or(Duration::ZERO) + self.intermission)
+            .sum()
+    }
+
+    pub fn rotations(&self, start: OffsetDateTime) -> Vec<Rotation> { // scheduled start for the timetable, timer start for the live timer
+        let mut at = start;
+        let mut phase = |kind: PhaseKind, duration: Duration| {
+            let phase = Phase { kind, start: at, end: at + duration };
+            at = phase.end;
+            phase
+        };
+
+        (1..=self.rotations).map(|rotation| {
+            let (kind, duration) = self.station_duration(rotation);
+            let mut phases = vec![phase(kind, duration)];
+            if let Some(feedback) = self.feedback {
+                phases.push(phase(PhaseKind::Feedback, feedback));
+            }
+            if self.intermission > Duration::ZERO {
+                phases.push(phase(PhaseKind::Intermission, self.intermission));
+            }
+            Rotation { rotation, phases }
+        }).collect()
+    }
+
+    pub fn phase_at(&self, start: OffsetDateTime, at: OffsetDateTime) -> Option<(i16, Phase)> { // None before the start or after the end
+        self.rotations(start).into_iter()
+            .flat_map(|rotation| rotation.phases.into_iter().map(move |phase| (rotation.rotation, phase)))
+            .find(|(_, phase)| phase.start <= at && at < phase.end)
+    }
+}
+
+/// Checks the runs of every slot once they last as long as the timing says.
+/// Overlapping runs in a slot would double-book its examiners and candidates, overlapping slots need different people
+pub fn check_schedule(
+    run_starts: &[Vec<OffsetDateTime>], // per slot, in the session's local time so midnight is local
+    runtime: Duration,
+) -> Vec<ScheduleIssue> {
+    let mut issues = Vec::new();
+    let slot_key = |s: usize| ((b'A' + s as u8) as char).to_string();
+    let hh_mm = |at: OffsetDateTime| format!("{:02}:{:02}", at.hour(), at.minute());
+    let times: Vec<Vec<(OffsetDateTime, OffsetDateTime)>> = run_starts
+        .iter()
+        .map(|starts| starts.iter().map(|&start| (start, start + runtime)).collect())
+        .collect();
+    let overlaps = |a: (OffsetDateTime, OffsetDateTime), b: (OffsetDateTime, OffsetDateTime)| a.0 < b.1 && b.0 < a.1;
+
+    for (s, runs) in times.iter().enumerate() {
+        for (r, &(start, end)) in runs.iter().enumerate() {
+            let midnight = start.date().next_day().map(|day| day.midnight().assume_offset(start.offset()));
+            if midnight.is_none_or(|midnight| end > midnight) {
+                issues.push(ScheduleIssue {
+                    condition: ScheduleCondition::PastMidnight,
+                    slot_index: s,
+                    slot_key: slot_key(s),
+                    run_index: r,
+                    other_slot_index: None,
+                    other_run_index: None,
+                    examiners: Vec::new(),
+                    candidates: Vec::new(),
+                    message: format!("Run {} of slot {} starts at {} and ends after midnight", r + 1, slot_key(s), hh_mm(start)),
+                });
+            }
+            if let Some(o) = (0..r).find(|&o| overlaps(runs[o], (start, end))) {
+                issues.push(ScheduleIssue {
+                    condition: ScheduleCondition::RunOverlap,
+                    slot_index: s,
+                    slot_key: slot_key(s),
+                    run_index: r,
+                    other_slot_index: Some(s),
+                    other_run_index: Some(o),
+                    examiners: Vec::new(),
+                    candidates: Vec::new(),
+                    message: format!("Run {} of slot {} starts at {} before run {} ends at {}", r + 1, slot_key(s), hh_mm(start), o + 1, hh_mm(runs[o].1)),
+                });
+            }
+        }
+    }
+
+    for s in 0..times.len() {
+        for t in (s + 1)..times.len() {
+            let clash = times[t].iter().enumerate().find_map(|(r, &b)| {
+                times[s].iter().position(|&a| overlaps(a, b)).map(|o| (r, o))
+            });
+            if let Some((r, o)) = clash {
+                issues.push(ScheduleIssue {
+                    condition: ScheduleCondition::SlotOverlap,
+                    slot_index: t,
+                    slot_key: slot_key(t),
+                    run_index: r,
+                    other_slot_index: Some(s),
+                    other_run_index: Some(o),
+                    examiners: Vec::new(),
+                    candidates: Vec::new(),
+                    message: format!("Slots {} and {} run at the same time, their examiners and candidates must be different people", slot_key(s), slot_key(t)),
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Names the people each slot overlap could double-book: examiners available for both overlapping runs, and candidates
+/// who can attend both slots. Runs are AM or PM by their local start, same as the allocation
+pub fn name_double_booked(
+    issues: &mut [ScheduleIssue],
+    run_starts: &[Vec<OffsetDateTime>], // same as check_schedule
+    candidates: &[Candidate],
+    examiners: &[Examiner],
+) {
+    let is_am = |start: &OffsetDateTime| start.hour() < 12;
+    let slot_time = |s: usize| Availability { am: run_starts[s].iter().any(is_am), pm: run_starts[s].iter().any(|start| !is_am(start)) };
+    for issue in issues.iter_mut().filter(|issue| issue.condition == ScheduleCondition::SlotOverlap) {
+        let (Some(other_slot), Some(other_run)) = (issue.other_slot_index, issue.other_run_index) else {
+            continue;
+        };
+        let runs = [run_starts[issue.slot_index][issue.run_index], run_starts[other_slot][other_run]];
+        issue.examiners = examiners
+            .iter()
+            .filter(|e| runs.iter().all(|start| if is_am(start) { e.am } else { e.pm }))
+            .map(|e| ScheduledPerson { id: e.id, shortcode: e.shortcode.clone() })
+            .collect();
+        let (slot_time, other_time) = (slot_time(issue.slot_index), slot_time(other_slot));
+        issue.candidates = candidates
+            .iter()
+            .filter(|c| can_attend(c, &slot_time) && can_attend(c, &other_time))
+            .map(|c| ScheduledPerson { id: c.id, shortcode: c.shortcode.clone() })
+            .collect();
+
+        let shortcodes = |people: &[ScheduledPerson]| people.iter().map(|p| p.shortcode.as_str()).collect::<Vec<_>>().join(", ");
+        if !issue.examiners.is_empty() {
+            issue.message.push_str(&format!(". Examiners available for both: {}", shortcodes(&issue.examiners)));
+        }
+        if !issue.candidates.is_empty() {
+            issue.message.push_str(&format!(". Candidates available for both: {}", shortcodes(&issue.candidates)));
+        }
+    }
+}
+
+async fn get_by_session(
+    State(pool): State<sqlx::PgPool>,
+    Extension(claim): Extension<AccessClaims>,
+    Query(session): Query<SomethingID>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = Timetable::build(&pool, &session.id, &claim.organisation_id).await?;
+    Ok((StatusCode::OK, Json(result)).into_response())
+}
+
+impl Timetable {
+    pub async fn build(
+        pool: &sqlx::PgPool,
+        session_id: &Uuid,
+        organisation_id: &Uuid,
+    ) -> Result<Timetable, AppError> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT * FROM records.sessions WHERE id = $1 AND organisation_id = $2
+            "#,
+            session_id,
+            organisation_id
+        )
+        .fetch_one(pool)
+        .await
+        .context("Session not found or you do not have permission for this operation")?;
+
+        let stations = Station::get_by_session(pool, session_id).await?;
+        let timing = Timing::from_session(&session, &stations)?;
+
+        let mut runs = Vec::new();
+        let mut run_starts = Vec::new(); // per slot in key order, for the schedule check
+        let mut slots = Slot::get_all_by_session(pool, session_id).await?;
+        slots.sort_by(|a, b| a.key.cmp(&b.key));
+        for slot in slots {
+            let mut circuits = Circuit::get_by_slot(pool, &slot.id).await?;
+            circuits.sort_by(|a, b| a.key.cmp(&b.key));
+            let mut slot_runs = Run::get_all_by_slot(pool, &slot.id).await?;
+            slot_runs.sort_by_key(|run| run.scheduled_start);
+            let starts: Vec<OffsetDateTime> = slot_runs.iter().map(|run| run.scheduled_start).collect();
+            let offsets = utc_offsets(pool, &session.timezone, &starts).await?;
+            run_starts.push(starts.iter().zip(&offsets).map(|(start, offset)| start.to_offset(*offset)).collect());
+            for (run, offset) in slot_runs.into_iter().zip(offsets) {
+                let rotations = timing.rotations(run.scheduled_start.to_offset(offset)); // daylight saving never changes during a run
+                runs.push(RunTimetable {
+                    run_id: run.id,
+                    slot_id: slot.id,
+                    slot_key: slot.key.clone(),
+                    scheduled_start: run.scheduled_start.to_offset(offset),
+                    scheduled_end: run.scheduled_end.to_offset(offset),
+                    circuits: circuits.iter().map(|circuit| CircuitTimetable {
+                        circuit_id: circuit.id,
+                        key: circuit.key.clone(),
+                        rotations: rotations.clone(), // circuits in a run rotate together
+                    }).collect(),
+                });
+            }
+        }
+        runs.sort_by(|a, b| a.scheduled_start.cmp(&b.scheduled_start).then_with(|| a.slot_key.cmp(&b.slot_key)));
+
+        let mut warnings: Vec<ScheduleIssue> = check_schedule(&run_starts, timing.runtime())
+            .into_iter()
+            .filter(|issue| issue.condition == ScheduleCondition::SlotOverlap)
+            .collect();
+        if !warnings.is_empty() {
+            let candidates = Candidate::get_all_by_session(pool, session_id).await?;
+            let examiners = Examiner::get_all_by_session(pool, session_id).await?;
+            name_double_booked(&mut warnings, &run_starts, &candidates, &examiners);
+        }
+
+        Ok(Timetable { session_id: *session_id, timezone: session.timezone, runs, warnings })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_at() {
+        let timing = Timing {
+            rotations: 2,
+            station: Duration::minutes(8),
+            static_station: Some(Duration::minutes(10)),
+            feedback: Some(Duration::minutes(2)),
+            intermission: Duration::minutes(1),
+        };
+        let start = OffsetDateTime::UNIX_EPOCH;
+        assert_eq!(timing.runtime(), Duration::minutes(24));
+
+        let phase = |minutes: i64| timing.phase_at(start, start + Duration::minutes(minutes)).map(|(rotation, phase)| (rotation, phase.kind));
+        assert_eq!(phase(-1), None);
+        assert_eq!(phase(0), Some((1, PhaseKind::Station)));
+        assert_eq!(phase(9), Some((1, PhaseKind::Feedback)));
+        assert_eq!(phase(10), Some((1, PhaseKind::Intermission)));
+        assert_eq!(phase(11), Some((2, PhaseKind::StaticStation)));
+        assert_eq!(phase(21), Some((2, PhaseKind::Feedback)));
+        assert_eq!(phase(23), Some((2, PhaseKind::Intermission)));
+        assert_eq!(phase(24), None);
+    }
+
+    fn person(shortcode: &str, am: bool, pm: bool) -> (Candidate, Examiner) {
+        let candidate = Candidate {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            first_name: shortcode.to_string(),
+            last_name: String::from("Test"),
+            shortcode: shortcode.to_string(),
+            female_only: false,
+            partner_pref: None,
+            checked_in: false,
+            am: Some(am),
+            pm: Some(pm),
+            partner_pref_2: None,
+            avoid: Vec::new(),
+            placeholder: false,
+        };
+        let examiner = Examiner {
+            id: Uuid::new_v4(),
+            session_id: Uuid::nil(),
+            first_name: shortcode.to_string(),
+            last_name: String::from("Test"),
+            shortcode: shortcode.to_string(),
+            female: false,
+            am,
+            pm,
+            checked_in: false,
+            tags: Vec::new(),
+            placeholder: false,
+        };
+        (candidate, examiner)
+    }
+
+    #[test]
+    fn test_name_double_booked() {
+        let day = OffsetDateTime::UNIX_EPOCH;
+        // slot A runs at 09:00 and 14:00, slot B at 09:30 so it overlaps the first run of A
+        let run_starts = vec![
+            vec![day + Duration::hours(9), day + Duration::hours(14)],
+            vec![day + Duration::minutes(9 * 60 + 30)],
+        ];
+        let mut issues = check_schedule(&run_starts, Duration::hours(1));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].condition, ScheduleCondition::SlotOverlap);
+
+        let (both, am, pm) = (person("both", true, true), person("am", true, false), person("pm", false, true));
+        let candidates = vec![both.0, am.0, pm.0];
+        let examiners = vec![both.1, am.1, pm.1];
+        name_double_booked(&mut issues, &run_starts, &candidates, &examiners);
+
+        let issue = &issues[0];
+        assert_eq!((issue.slot_index, issue.run_index, issue.other_slot_index, issue.other_run_index), (1, 0, Some(0), Some(0)));
+        // the overlapping runs are both AM, but candidates go to all of slot A, AM and PM
+        let shortcodes = |people: &[ScheduledPerson]| people.iter().map(|p| p.shortcode.clone()).collect::<Vec<_>>();
+        assert_eq!(shortcodes(&issue.examiners), vec!["both", "am"]);
+        assert_eq!(shortcodes(&issue.candidates), vec!["both"]);
+        assert!(issue.message.contains("Examiners available for both: both, am"));
+    }
+}
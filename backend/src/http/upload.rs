@@ -10,7 +10,7 @@ use std::io::Cursor;
 
 use crate::error::AppError;
 
-use super::{users::{AccessClaims, User}, AppState, SomethingID, examiners::ExaminerExcel, candidates::CandidateExcel};
+use super::{users::{AccessClaims, User}, AppState, SomethingID, examiners::ExaminerExcel, candidates::CandidateExcel, exclusions::ExclusionExcel, actors::{ActorExcel, ACTOR_GENDERS}};
 
 pub fn router() -> axum::Router<AppState> {
     axum::Router::new()
@@ -34,6 +34,20 @@ const REQUIRED_CANDIDATE_HEADERS: &[&str] = &[
     "partner_pref",
 ];
 
+const REQUIRED_EXCLUSION_HEADERS: &[&str] = &[
+    "examiner_shortcode",
+    "candidate_shortcode",
+];
+
+const REQUIRED_ACTOR_HEADERS: &[&str] = &[
+    "first_name",
+    "last_name",
+    "shortcode",
+    "gender",
+    "am",
+    "pm",
+];
+
 fn get_bool(value: &Data, row_index: usize, header: &str) -> Result<bool, AppError> {
     match value {
         Data::Empty => Ok(false),
@@ -71,6 +85,18 @@ fn get_option_bool(value: &Data, row_index: usize, header: &str) -> Result<Optio
     }
 }
 
+fn get_tags(value: &Data, row_index: usize, header: &str) -> Result<Vec<String>, AppError> { // comma separated list, e.g. tags or avoid
+    match value {
+        Data::Empty => Ok(Vec::new()),
+        Data::String(s) => Ok(s.split(',').map(|tag| tag.trim().to_lowercase()).filter(|tag| !tag.is_empty()).collect()),
+        _ => Err(anyhow!(
+            "Invalid data type at row {}, column {}",
+            row_index + 2,
+            header
+        ))?,
+    }
+}
+
 fn get_pref(value: &Data, row_index: usize, header: &str) -> Result<Option<String>, AppError> {
     match value {
         Data::Empty => Ok(None),
@@ -84,6 +110,19 @@ fn get_pref(value: &Data, row_index: usize, header: &str) -> Result<Option<Strin
     }
 }
 
+fn get_gender(value: &Data, row_index: usize, header: &str) -> Result<String, AppError> {
+    let gender = value.get_string().ok_or_else(|| anyhow!("Missing {} at row {}", header, row_index + 2))?.trim().to_lowercase();
+    if !ACTOR_GENDERS.contains(&gender.as_str()) {
+        Err(anyhow!(
+            "Invalid gender at row {}, column {}, must be one of: {}",
+            row_index + 2,
+            header,
+            ACTOR_GENDERS.join(", ")
+        ))?;
+    }
+    Ok(gender)
+}
+
 async fn upload_xlsx(
     State(pool): State<sqlx::PgPool>,
     Extension(claim): Extension<AccessClaims>,
@@ -116,6 +155,8 @@ async fn upload_xlsx(
     let mut can_time = false;
     let mut new_examiners: Vec<ExaminerExcel> = vec![];
     let mut new_candidates: Vec<CandidateExcel> = vec![];
+    let mut new_exclusions: Vec<ExclusionExcel> = vec![];
+    let mut new_actors: Vec<ActorExcel> = vec![];
     for (index, (sheet_name, sheet_data)) in workbook.worksheets().iter().enumerate() {
         println!("Index: {:?}\nSheet Name: {:?}\nSheet Data: {:?}", index, sheet_name, sheet_data);
 
@@ -151,7 +192,11 @@ async fn upload_xlsx(
                         shortcode: row[header_indices["shortcode"]].get_string().ok_or_else(|| { anyhow!( "Missing shortcode at row {}", row_index + 2 ) })?.to_string().to_lowercase(),
                         female: get_bool(&row[header_indices["female"]], row_index, "female")?,
                         am: get_bool(&row[header_indices["am"]], row_index, "am")?,
-                        pm: get_bool(&row[header_indices["pm"]], row_index, "pm")?
+                        pm: get_bool(&row[header_indices["pm"]], row_index, "pm")?,
+                        tags: match header_indices.get("tags") { // optional column
+                            Some(&i) => get_tags(&row[i], row_index, "tags")?,
+                            None => Vec::new(),
+                        },
                     };
                     new_examiners.push(examiner);
                 }
@@ -173,7 +218,15 @@ async fn upload_xlsx(
                             female_only: get_bool(&row[header_indices["female_only"]], row_index, "female_only")?,
                             partner_pref: get_pref(&row[header_indices["partner_pref"]], row_index, "partner_pref")?,
                             am: get_option_bool(&row[header_indices["am"]], row_index, "am")?,
-                            pm: get_option_bool(&row[header_indices["am"]], row_index, "am")?
+                            pm: get_option_bool(&row[header_indices["am"]], row_index, "am")?,
+                            partner_pref_2: match header_indices.get("partner_pref_2") { // optional column
+                                Some(&i) => get_pref(&row[i], row_index, "partner_pref_2")?,
+                                None => None,
+                            },
+                            avoid: match header_indices.get("avoid") { // optional column
+                                Some(&i) => get_tags(&row[i], row_index, "avoid")?,
+                                None => Vec::new(),
+                            },
                         };
                         new_candidates.push(candidate);
                     }
@@ -187,12 +240,58 @@ async fn upload_xlsx(
                             partner_pref: get_pref(&row[header_indices["partner_pref"]], row_index, "partner_pref")?,
                             am: None,
                             pm: None,
+                            partner_pref_2: match header_indices.get("partner_pref_2") { // optional column
+                                Some(&i) => get_pref(&row[i], row_index, "partner_pref_2")?,
+                                None => None,
+                            },
+                            avoid: match header_indices.get("avoid") { // optional column
+                                Some(&i) => get_tags(&row[i], row_index, "avoid")?,
+                                None => Vec::new(),
+                            },
                         };
                         new_candidates.push(candidate);
                     }
                 }
             },
-            _ => return Err(AppError::from(anyhow!("Cannot match sheet name with 'candidates' or 'examiners'")))
+            "exclusions" => { // optional, examiner shortcode <-> candidate shortcode
+                let required_headers: HashSet<&str> = REQUIRED_EXCLUSION_HEADERS.iter().copied().collect();
+                let header_set: HashSet<&str> = headers.iter().map(|s| s.as_str()).collect();
+                if !required_headers.is_subset(&header_set) {
+                    return Err(anyhow!("Missing required headers"))?;
+                }
+
+                for (row_index, row) in sheet_data.rows().skip(1).enumerate() {
+                    let exclusion = ExclusionExcel {
+                        examiner_shortcode: row[header_indices["examiner_shortcode"]].get_string().ok_or_else(|| { anyhow!( "Missing examiner_shortcode at row {}", row_index + 2 ) })?.to_string().to_lowercase(),
+                        candidate_shortcode: row[header_indices["candidate_shortcode"]].get_string().ok_or_else(|| { anyhow!( "Missing candidate_shortcode at row {}", row_index + 2 ) })?.to_string().to_lowercase(),
+                    };
+                    new_exclusions.push(exclusion);
+                }
+            },
+            "actors" => { // optional, simulated patients
+                let required_headers: HashSet<&str> = REQUIRED_ACTOR_HEADERS.iter().copied().collect();
+                let header_set: HashSet<&str> = headers.iter().map(|s| s.as_str()).collect();
+                if !required_headers.is_subset(&header_set) {
+                    return Err(anyhow!("Missing required headers"))?;
+                }
+
+                for (row_index, row) in sheet_data.rows().skip(1).enumerate() {
+                    let actor = ActorExcel {
+                        first_name: row[header_indices["first_name"]].get_string().ok_or_else(|| { anyhow!( "Missing first_name at row {}", row_index + 2 ) })?.to_string(),
+                        last_name: row[header_indices["last_name"]].get_string().ok_or_else(|| { anyhow!( "Missing last_name at row {}", row_index + 2 ) })?.to_string(),
+                        shortcode: row[header_indices["shortcode"]].get_string().ok_or_else(|| { anyhow!( "Missing shortcode at row {}", row_index + 2 ) })?.to_string().to_lowercase(),
+                        gender: get_gender(&row[header_indices["gender"]], row_index, "gender")?,
+                        am: get_bool(&row[header_indices["am"]], row_index, "am")?,
+                        pm: get_bool(&row[header_indices["pm"]], row_index, "pm")?,
+                        stations: match header_indices.get("stations") { // optional column, station titles
+                            Some(&i) => get_tags(&row[i], row_index, "stations")?,
+                            None => Vec::new(),
+                        },
+                    };
+                    new_actors.push(actor);
+                }
+            },
+            _ => return Err(AppError::from(anyhow!("Cannot match sheet name with 'candidates', 'examiners', 'exclusions' or 'actors'")))
         }
     }
 
@@ -211,8 +310,10 @@ async fn upload_xlsx(
                     partner_pref,
                     am,
                     pm,
-                    checked_in
-                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                    checked_in,
+                    partner_pref_2,
+                    avoid
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
                 "#,
                 &session_id,
                 c.first_name,
@@ -222,7 +323,9 @@ async fn upload_xlsx(
                 c.partner_pref,
                 c.am,
                 c.pm,
-                false
+                false,
+                c.partner_pref_2,
+                &c.avoid
             )
             .execute(&mut *transaction)
             .await
@@ -237,8 +340,10 @@ async fn upload_xlsx(
                     shortcode,
                     female_only,
                     partner_pref,
-                    checked_in
-                ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    checked_in,
+                    partner_pref_2,
+                    avoid
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 "#,
                 &session_id,
                 c.first_name,
@@ -246,7 +351,9 @@ async fn upload_xlsx(
                 c.shortcode,
                 c.female_only,
                 c.partner_pref,
-                false
+                false,
+                c.partner_pref_2,
+                &c.avoid
             )
             .execute(&mut *transaction)
             .await
@@ -264,8 +371,9 @@ async fn upload_xlsx(
                 female,
                 am,
                 pm,
-                checked_in
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                checked_in,
+                tags
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             "#,
             &session_id,
             e.first_name,
@@ -274,15 +382,63 @@ async fn upload_xlsx(
             e.female,
             e.am,
             e.pm,
-            false
+            false,
+            &e.tags
         )
         .execute(&mut *transaction)
         .await
         .map_err(|err| anyhow!("Failed to insert candidate from excel: {}", err))?;
     }
+    for x in new_exclusions.into_iter() {
+        let _ = sqlx::query!(
+            r#"
+            INSERT INTO people.exclusions (
+                session_id,
+                examiner_shortcode,
+                candidate_shortcode
+            ) VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            &session_id,
+            x.examiner_shortcode,
+            x.candidate_shortcode
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| anyhow!("Failed to insert exclusion from excel: {}", err))?;
+    }
+    for a in new_actors.into_iter() {
+        let _ = sqlx::query!(
+            r#"
+            INSERT INTO people.actors (
+                session_id,
+                first_name,
+                last_name,
+                shortcode,
+                gender,
+                am,
+                pm,
+                checked_in,
+                stations
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            &session_id,
+            a.first_name,
+            a.last_name,
+            a.shortcode,
+            a.gender,
+            a.am,
+            a.pm,
+            false,
+            &a.stations
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| anyhow!("Failed to insert actor from excel: {}", err))?;
+    }
 
     sqlx::query!("UPDATE records.sessions SET status = 'prep' WHERE id = $1", &session_id).execute(&mut *transaction).await.map_err(|err| anyhow!("Failed to change session upload status: {}", err))?;
-    transaction.commit().await.with_context(|| format!("Rolled back successful. Transaction failed to commit"))?;
+    transaction.commit().await.context("Rolled back successful. Transaction failed to commit")?;
 
     Ok(StatusCode::CREATED.into_response())
 }
\ No newline at end of file
@@ -63,10 +63,10 @@ async fn login(
     )
     .fetch_one(&pool)
     .await
-    .with_context(|| format!("Cannot get user from database when logging in"))?;
+    .context("Cannot get user from database when logging in")?;
 
     let parsed_hash = PasswordHash::new(&selected_user.password)
-        .with_context(|| format!("Cannot create PasswordHash from user's password when logging in"))?;
+        .context("Cannot create PasswordHash from user's password when logging in")?;
 
     if Argon2::default().verify_password(req.password.as_bytes(), &parsed_hash).is_ok()
     {
@@ -125,10 +125,10 @@ async fn refresh(
         let cookie_jar = cookie_jar.remove(Cookie::from("csrf_token"));
         let private_jar = private_jar.add(new_tokens.0);
         let cookie_jar = cookie_jar.add(new_tokens.1);
-        return Ok((StatusCode::OK, cookie_jar, private_jar, Json(new_tokens.2)).into_response())
+        Ok((StatusCode::OK, cookie_jar, private_jar, Json(new_tokens.2)).into_response())
     } else {
         warn!(name: "exception_error", "Refresh token not found in cookies");
-        return Err(AppError::from(anyhow!("Missing Credentials")));
+        Err(AppError::from(anyhow!("Missing Credentials")))
     }
 }
 
@@ -252,12 +252,12 @@ impl Keys {
     }
 }
 
-const ACCESS_KEYS: Lazy<Keys> = Lazy::new(|| {
+static ACCESS_KEYS: Lazy<Keys> = Lazy::new(|| {
     let secret = dotenvy::var("ACCESS_JWT_SECRET").expect("JWT_SECRET must be set");
     Keys::new(secret.as_bytes())
 });
 
-const REFRESH_KEYS: Lazy<Keys> = Lazy::new(|| {
+static REFRESH_KEYS: Lazy<Keys> = Lazy::new(|| {
     let secret = dotenvy::var("REFRESH_JWT_SECRET").expect("JWT_SECRET must be set");
     Keys::new(secret.as_bytes())
 });
@@ -411,7 +411,7 @@ impl Organisation {
             &name)
             .execute(pool)
             .await
-            .with_context(|| format!("Failed to insert new organisation into database"))?;
+            .context("Failed to insert new organisation into database")?;
 
         Ok(())
     }
@@ -472,7 +472,7 @@ impl Organisation {
         )
         .fetch_all(pool)
         .await
-        .with_context(|| format!("Failed to fetch all organisations from database"))?;
+        .context("Failed to fetch all organisations from database")?;
 
         Ok(organisations)
     }
@@ -500,7 +500,7 @@ impl User {
         )
         .execute(pool)
         .await
-            .with_context(|| format!("Failed to insert new user into database"))?;
+            .context("Failed to insert new user into database")?;
 
         Ok(())
     }
@@ -544,7 +544,7 @@ impl User {
         )
         .fetch_all(pool)
         .await
-        .with_context(|| format!("Failed to fetch all users from database"))?;
+        .context("Failed to fetch all users from database")?;
 
         Ok(users)
     }
@@ -588,7 +588,7 @@ impl User {
         .await
         .with_context(|| {
             warn!(name: "db_error", "Cannot fetch corresponding jti from db");
-            format!("Cannot fetch corresponding jti from db")
+            "Cannot fetch corresponding jti from db".to_string()
         })?;
 
         if let Some(jwt_id) = selected_user.jti {
@@ -607,9 +607,9 @@ impl User {
             "UPDATE auth.users SET jti = NULL WHERE id = $1",
             id,
         )
-        .execute(&*pool)
+        .execute(pool)
         .await
-        .with_context(|| format!("Cannot remove jti from database"))?;
+        .context("Cannot remove jti from database")?;
 
         Ok(())
     }
@@ -620,7 +620,7 @@ impl User {
         &self,
         pool: &sqlx::PgPool,
     ) -> Result<(Cookie<'static>, Cookie<'static>, AuthBody, AccessClaims), AppError> {
-        let tokens =  self.generate_jwt_tokens(&pool).await?;
+        let tokens =  self.generate_jwt_tokens(pool).await?;
         let csrf_token = generate_token(tokens.1.jti)?;
 
         println!("csrf_token: {}", csrf_token);
@@ -631,7 +631,7 @@ impl User {
         );
 
         let role = if self.admin { "admin".to_string() } else { "user".to_string() };
-        let organisation = Organisation::get_name(&pool, &self.organisation_id).await?;
+        let organisation = Organisation::get_name(pool, &self.organisation_id).await?;
         Ok((refresh_cookie, csrf_cookie, AuthBody::new(tokens.0["access_token"].clone(), self.username.clone(), role, organisation), tokens.1))
     }
 
@@ -647,9 +647,9 @@ impl User {
             refresh_jti,
             self.id,
             )
-            .execute(&*pool)
+            .execute(pool)
             .await
-            .with_context(|| format!("Cannot add jti into database"))?;
+            .context("Cannot add jti into database")?;
 
         let tokens = create_tokens(
             self.id,
@@ -695,8 +695,8 @@ pub async fn jwt_auth_middleware(
     // if no access token is found, check for refresh token
     if let Some(token) = jar.get("refresh_token") {
         let token_data = decode_token::<RefreshClaims>(token.value(), &REFRESH_KEYS.decoding)?;
-        let selected_user = User::check_jti(&pool, &token_data.claims).await?;
-        let new_tokens = selected_user.new_tokens(&pool).await?;
+        let selected_user = User::check_jti(pool, &token_data.claims).await?;
+        let new_tokens = selected_user.new_tokens(pool).await?;
         let jar = jar.remove(Cookie::from("refresh_token"));
         let jar = jar.remove(Cookie::from("csrf_token"));
         let jar = jar.add(new_tokens.0);
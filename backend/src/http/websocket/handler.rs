@@ -1,13 +1,22 @@
 use axum::{
-    extract::{State, WebSocketUpgrade, ws::{WebSocket, Message, Utf8Bytes}},
+    extract::{Query, State, WebSocketUpgrade, ws::{close_code, CloseFrame, Message, WebSocket}},
     response::IntoResponse,
     routing::{Router, get},
     Extension,
 };
-use crate::http::AppState;
+use crate::error::AppError;
+use crate::http::{AppState, SomethingID};
+use crate::http::runs::Run;
+use crate::http::sessions::Session;
+use crate::http::slots::Slot;
+use crate::http::stations::Station;
+use crate::http::timetable::{local_to_utc, utc_offsets, Phase, Timing};
 use crate::http::users::AccessClaims;
-use serde::{Deserialize, Serialize};
-use time::{OffsetDateTime, UtcOffset, Time};
+use serde::Serialize;
+use time::{OffsetDateTime, Time};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+use uuid::Uuid;
 
 pub fn router() -> Router::<AppState> {
     Router::new()
@@ -21,26 +30,143 @@ struct NotifyMessage {
     play_at: i64,     // Unix timestamp in milliseconds (UTC)
 }
 
+#[derive(Serialize)]
+struct PhaseMessage { // sent when a run moves on to its next phase, and for every run in progress on connect
+    event: String,
+    run_id: Uuid,
+    rotation: i16, // starts at 1
+    phase: Phase, // in the session's local time
+}
+
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Extension(claims): Extension<AccessClaims>,
+    Query(session): Query<SomethingID>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state, claims))
+    ws.on_upgrade(move |socket| handle_socket(socket, state, claims, session.id))
+}
+
+async fn send_json<T: Serialize>(socket: &mut WebSocket, message: &T) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+async fn close(mut socket: WebSocket, code: u16, reason: &str) { // tells the client why, instead of dropping the connection
+    let frame = CloseFrame { code, reason: reason.into() };
+    let _ = socket.send(Message::Close(Some(frame))).await;
+}
+
+async fn live_timing( // durations of a run, and the start of every run of the session in local time, by the live timer once it has started
+    pool: &sqlx::PgPool,
+    session: &Session,
+) -> Result<(Timing, Vec<(Uuid, OffsetDateTime)>), AppError> {
+    let stations = Station::get_by_session(pool, &session.id).await?;
+    let timing = Timing::from_session(session, &stations)?;
+    let mut runs: Vec<Run> = Vec::new();
+    for slot in Slot::get_all_by_session(pool, &session.id).await? {
+        runs.extend(Run::get_all_by_slot(pool, &slot.id).await?);
+    }
+    let starts: Vec<OffsetDateTime> = runs.iter().map(|run| run.timer_start.unwrap_or(run.scheduled_start)).collect();
+    let offsets = utc_offsets(pool, &session.timezone, &starts).await?;
+    Ok((timing, runs.iter().zip(starts).zip(offsets).map(|((run, start), offset)| (run.id, start.to_offset(offset))).collect()))
 }
 
-async fn handle_socket(mut socket: WebSocket, state: AppState, claims: AccessClaims) {
+async fn handle_socket(mut socket: WebSocket, state: AppState, claims: AccessClaims, session_id: uuid::Uuid) {
     let mut rx = state.tx.subscribe();
 
-    // Example: Broadcast an MP3 to play at 12:00 PM UTC today
-    let today = OffsetDateTime::now_utc().date();
-    let play_time = today.with_time(Time::from_hms(12, 0, 0).unwrap()).assume_utc();
-    let play_at = play_time.unix_timestamp(); // Convert to milliseconds
+    let session = match sqlx::query_as!(
+        Session,
+        r#"
+        SELECT * FROM records.sessions WHERE id = $1 AND organisation_id = $2
+        "#,
+        session_id,
+        claims.organisation_id
+    )
+    .fetch_one(&state.db)
+    .await {
+        Ok(session) => session,
+        Err(e) => {
+            warn!(name: "db_error", "Cannot get session {} for websocket: {}", session_id, e);
+            close(socket, close_code::POLICY, "Session not found or you do not have permission for this operation").await;
+            return;
+        },
+    };
+
+    // Example: Broadcast an MP3 to play at 12:00 PM on the day, in the session's timezone
+    let noon = session.scheduled_date.with_time(Time::from_hms(12, 0, 0).unwrap());
+    let play_time = match local_to_utc(&state.db, &session.timezone, noon).await {
+        Ok(play_time) => play_time,
+        Err(e) => {
+            warn!(name: "db_error", "Cannot get local time of session {} for websocket: {}", session_id, e);
+            close(socket, close_code::ERROR, "Cannot get the session's local time").await;
+            return;
+        },
+    };
+    let play_at = (play_time.unix_timestamp_nanos() / 1_000_000) as i64; // Convert to milliseconds
 
-    let message = NotifyMessage {
+    let _message = NotifyMessage {
         event: "play_audio".to_string(),
         mp3_data: "base64_encoded_mp3_data_here".to_string(), // Replace with actual Base64 MP3
         play_at,
     };
-    
-}
\ No newline at end of file
+
+    // runs are read once, a timer started after connecting is picked up on reconnect
+    let (timing, runs) = match live_timing(&state.db, &session).await {
+        Ok(live) => live,
+        Err(e) => {
+            warn!(name: "db_error", "Cannot get timetable of session {} for websocket: {}", session_id, e);
+            close(socket, close_code::ERROR, "Cannot get the session's timetable").await;
+            return;
+        },
+    };
+    let phase_messages = |at: OffsetDateTime, starting_only: bool| -> Vec<PhaseMessage> {
+        runs.iter()
+            .filter_map(|&(run_id, start)| timing.phase_at(start, at).map(|(rotation, phase)| (run_id, rotation, phase)))
+            .filter(|(_, _, phase)| !starting_only || phase.start == at)
+            .map(|(run_id, rotation, phase)| PhaseMessage { event: "phase".to_string(), run_id, rotation, phase })
+            .collect()
+    };
+
+    let mut announced = OffsetDateTime::now_utc(); // phases starting up to here have been sent
+    for message in phase_messages(announced, false) {
+        if send_json(&mut socket, &message).await.is_err() {
+            return;
+        }
+    }
+    loop {
+        // next time any run starts a phase
+        let next = runs.iter()
+            .flat_map(|&(_, start)| timing.rotations(start))
+            .flat_map(|rotation| rotation.phases)
+            .map(|phase| phase.start)
+            .filter(|&start| start > announced)
+            .min();
+        let wait = next.map(|at| (at - OffsetDateTime::now_utc()).max(time::Duration::ZERO).unsigned_abs());
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait.unwrap_or_default()), if next.is_some() => {
+                let Some(at) = next else { continue };
+                announced = at;
+                for message in phase_messages(at, true) {
+                    if send_json(&mut socket, &message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            broadcast = rx.recv() => match broadcast {
+                Ok(text) => {
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        return;
+                    }
+                },
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {},
+            },
+        }
+    }
+}
@@ -1 +1 @@
-mod handler;
\ No newline at end of file
+pub mod handler;